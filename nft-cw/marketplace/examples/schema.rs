@@ -3,8 +3,8 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
+use craft_marketplace::msg::{BridgedSellNft, ExecuteMsg, InitMsg, QueryMsg, SellNft, SudoMsg};
 use craft_marketplace::msg::{ContractInfoResponse, OfferingsResponse, QueryOfferingsResult};
-use craft_marketplace::msg::{ExecuteMsg, InitMsg, QueryMsg, SellNft};
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -15,7 +15,9 @@ fn main() {
     export_schema(&schema_for!(InitMsg), &out_dir);
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(SudoMsg), &out_dir);
     export_schema(&schema_for!(SellNft), &out_dir);
+    export_schema(&schema_for!(BridgedSellNft), &out_dir);
     export_schema(&schema_for!(OfferingsResponse), &out_dir);
     export_schema(&schema_for!(ContractInfoResponse), &out_dir);
     export_schema(&schema_for!(QueryOfferingsResult), &out_dir);
@@ -1,42 +1,36 @@
 use crate::error::ContractError;
-use cosmwasm_std::Coin;
+use cosmwasm_std::{Coin, MessageInfo};
+use cw_utils::{must_pay, NativeBalance, PaymentError};
 
-// Modified:
-// https://github.com/InterWasm/cw-contracts/blob/main/contracts/nameservice/src/contract.rs
-// just instead of
-// coin.amount.u128() >= required_amount
-// it is `coin.amount.u128() == required_amount`
+// Checks the sender paid at least `price` & returns the change: anything sent above the price,
+// including coins of other denoms. The caller refunds the change to the sender.
+pub fn take_payment(info: &MessageInfo, price: &Coin) -> Result<Vec<Coin>, ContractError> {
+    let mut funds = NativeBalance(info.funds.clone());
+    funds.normalize();
 
-pub fn assert_sent_exact_coin(sent: &[Coin], required: Option<Coin>) -> Result<(), ContractError> {
-    if let Some(required_coin) = required {
-        let required_amount = required_coin.amount.u128();
-        if required_amount > 0 {
-            let mut received_amounts = vec![];
-            let sent_sufficient_funds = sent.iter().any(|coin| {
-                // check if a given sent coin matches denom
-                // and has sufficient amount
-                received_amounts.push(coin.amount.u128().to_string() + " " + &coin.denom);
-                coin.denom == required_coin.denom && coin.amount.u128() == required_amount
-            });
-
-            if sent_sufficient_funds {
-                return Ok(());
-            } else {
-                return Err(ContractError::InsufficientFundsSend {
-                    needed: required_amount.to_string(),
-                    received: convert_vector_of_string_slices_to_string(received_amounts),
-                });
-            }
+    let sent = match must_pay(info, &price.denom) {
+        Ok(amount) => amount,
+        // several denoms were sent, only the listing denom counts towards the price
+        Err(PaymentError::MultipleDenoms {}) => funds
+            .0
+            .iter()
+            .find(|coin| coin.denom == price.denom)
+            .map(|coin| coin.amount)
+            .unwrap_or_default(),
+        Err(err) => {
+            return Err(ContractError::InsufficientFundsSend {
+                needed: price.to_string(),
+                received: err.to_string(),
+            })
         }
-    }
-    Ok(())
-}
+    };
 
-fn convert_vector_of_string_slices_to_string(vector: Vec<String>) -> String {
-    let mut string = String::new();
-    for s in vector {
-        string.push_str(&s);
-        string.push_str(", ");
+    if sent < price.amount {
+        return Err(ContractError::InsufficientFundsSend {
+            needed: price.to_string(),
+            received: funds.to_string(),
+        });
     }
-    string
+
+    Ok(funds.sub_saturating(price.clone())?.into_vec())
 }
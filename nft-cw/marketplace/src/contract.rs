@@ -1,19 +1,21 @@
-use crate::msg::{ContractInfoResponse, MigrateMsg};
-use crate::state::CONTRACT_INFO;
+use crate::msg::{ContractInfoResponse, MigrateMsg, DEFAULT_FEATURED_SLOTS, DEFAULT_MAX_PRICE_AGE};
+use crate::state::{CONTRACT_INFO, REBATE_CONFIG, WASH_TRADING};
 use cosmwasm_std::{
-    to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult,
+    to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdResult, Uint128,
 };
 
 use cw2::set_contract_version;
+use semver::Version;
 
 use crate::execute;
+use crate::migrations;
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InitMsg, QueryMsg};
+use crate::msg::{ExecuteMsg, InitMsg, QueryMsg, SudoMsg};
 use crate::queries;
 use cosmwasm_std::entry_point;
 
-const CONTRACT_NAME: &str = "crates.io:craft-marketplace";
+pub const CONTRACT_NAME: &str = "crates.io:craft-marketplace";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION"); // Config.toml -> [package] -> version
 
 // Note, you can use StdResult in some functions where you do not
@@ -30,7 +32,10 @@ pub fn instantiate(
 
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
-    // TODO: Add contract admin?
+    let admin = match msg.admin {
+        Some(admin) => deps.api.addr_validate(&admin)?.into_string(),
+        None => msg.fee_receive_address.clone(),
+    };
 
     let info = ContractInfoResponse {
         name: msg.name,
@@ -39,6 +44,15 @@ pub fn instantiate(
         platform_fee: msg.platform_fee,
         version: CONTRACT_VERSION.to_string(),
         contact: "reece@crafteconomy.io".to_string(),
+        admin,
+        pull_payments: false,
+        verify_collections: false,
+        max_price_age: DEFAULT_MAX_PRICE_AGE,
+        featured_slots: DEFAULT_FEATURED_SLOTS,
+        referral_fee_bps: 0,
+        listing_deposit: Uint128::zero(),
+        max_listings_per_seller: 0,
+        ics721_bridge: None,
     };
 
     CONTRACT_INFO.save(deps.storage, &info)?;
@@ -48,36 +62,280 @@ pub fn instantiate(
 #[entry_point]
 pub fn execute(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
         ExecuteMsg::WithdrawNft { offering_id } => {
-            execute::withdraw_offering(deps, info, offering_id)
+            execute::withdraw_offering(deps, info, offering_id.0)
+        }
+        ExecuteMsg::BuyNft {
+            offering_id,
+            recipient,
+            referrer,
+        } => execute::buy_nft(deps, env, info, offering_id.0, recipient, referrer, None),
+        ExecuteMsg::BuyNftOverIbc {
+            offering_id,
+            receiver,
+            referrer,
+        } => execute::buy_nft(
+            deps,
+            env,
+            info,
+            offering_id.0,
+            None,
+            referrer,
+            Some(receiver),
+        ),
+        ExecuteMsg::ReceiveNft(msg) => execute::receive_nft(deps, env, info, msg),
+        ExecuteMsg::Ics721ReceiveCallback(msg) => {
+            execute::ics721_receive_callback(deps, env, info, msg)
         }
-        ExecuteMsg::BuyNft { offering_id } => execute::buy_nft(deps, info, offering_id),
-        ExecuteMsg::ReceiveNft(msg) => execute::receive_nft(deps, info, msg),
+        ExecuteMsg::Ics721AckCallback(msg) => execute::ics721_ack_callback(deps, env, info, msg),
+        ExecuteMsg::AllowBridgedSender { channel_id, sender } => {
+            execute::allow_bridged_sender(deps, info, channel_id, sender, true)
+        }
+        ExecuteMsg::RevokeBridgedSender { channel_id, sender } => {
+            execute::allow_bridged_sender(deps, info, channel_id, sender, false)
+        }
+        ExecuteMsg::List {
+            collection,
+            token_id,
+            price,
+        } => execute::list(deps, env, info, collection, token_id, price),
+        ExecuteMsg::PruneListings { limit, start_after } => {
+            execute::prune_listings(deps, env, limit, start_after.map(|id| id.0))
+        }
+        ExecuteMsg::ReclaimExpired { offering_id } => {
+            execute::reclaim_expired(deps, env, info, offering_id.0)
+        }
+        ExecuteMsg::PrepayDeposit {} => execute::prepay_deposit(deps, info),
+        ExecuteMsg::WithdrawPrepaidDeposit {} => execute::withdraw_prepaid_deposit(deps, info),
+
+        ExecuteMsg::RetryTransfer { offering_id } => {
+            execute::retry_transfer(deps, env, info, offering_id.0)
+        }
+        ExecuteMsg::ClaimRefund { offering_id } => execute::claim_refund(deps, info, offering_id.0),
 
         ExecuteMsg::UpdateListingPrice {
             offering_id,
             new_price,
-        } => execute::update_listing_price(deps, info, offering_id, new_price),
+        } => execute::update_listing_price(deps, info, offering_id.0, new_price),
 
+        // the admin messages run the same handlers as sudo, once the sender is checked
         ExecuteMsg::UpdateFeeReceiverAddress { new_address } => {
-            execute::update_fee_receiver_address(deps, info, new_address)
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(deps, env, SudoMsg::UpdateFeeReceiverAddress { new_address })
         }
         ExecuteMsg::UpdatePlatformFee { new_fee } => {
-            execute::update_platform_fee(deps, info, new_fee)
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(deps, env, SudoMsg::UpdatePlatformFee { new_fee })
+        }
+        ExecuteMsg::UpdatePullPayments { enabled } => {
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(deps, env, SudoMsg::UpdatePullPayments { enabled })
+        }
+        ExecuteMsg::RegisterCollection {
+            address,
+            name,
+            symbol,
+        } => {
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(
+                deps,
+                env,
+                SudoMsg::RegisterCollection {
+                    address,
+                    name,
+                    symbol,
+                },
+            )
+        }
+        ExecuteMsg::UnregisterCollection { address } => {
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(deps, env, SudoMsg::UnregisterCollection { address })
+        }
+        ExecuteMsg::UpdateVerifyCollections { enabled } => {
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(deps, env, SudoMsg::UpdateVerifyCollections { enabled })
+        }
+        ExecuteMsg::UpdateFeaturedSlots { slots } => {
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(deps, env, SudoMsg::UpdateFeaturedSlots { slots })
+        }
+        ExecuteMsg::UpdateMaxPriceAge { max_price_age } => {
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(deps, env, SudoMsg::UpdateMaxPriceAge { max_price_age })
+        }
+        ExecuteMsg::UpdateReferralFee { bps } => {
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(deps, env, SudoMsg::UpdateReferralFee { bps })
+        }
+        ExecuteMsg::SetReferrerLimit { referrer, max_bps } => {
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(deps, env, SudoMsg::SetReferrerLimit { referrer, max_bps })
+        }
+        ExecuteMsg::UpdateFeeTiers { denom, tiers } => {
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(deps, env, SudoMsg::UpdateFeeTiers { denom, tiers })
+        }
+        ExecuteMsg::UpdateRebates { token, rates } => {
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(deps, env, SudoMsg::UpdateRebates { token, rates })
+        }
+        ExecuteMsg::UpdateWashTrading {
+            holding_period,
+            pair_window,
+            reject,
+        } => {
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(
+                deps,
+                env,
+                SudoMsg::UpdateWashTrading {
+                    holding_period,
+                    pair_window,
+                    reject,
+                },
+            )
+        }
+        ExecuteMsg::UpdateListingLimits {
+            deposit,
+            max_per_seller,
+        } => {
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(
+                deps,
+                env,
+                SudoMsg::UpdateListingLimits {
+                    deposit,
+                    max_per_seller,
+                },
+            )
+        }
+        ExecuteMsg::UpdateIcs721Bridge { address } => {
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(deps, env, SudoMsg::UpdateIcs721Bridge { address })
+        }
+        ExecuteMsg::PromoteOffering {
+            offering_id,
+            duration,
+        } => execute::promote_offering(deps, env, info, offering_id.0, duration),
+        ExecuteMsg::RefreshMetadata { offering_id } => {
+            execute::refresh_metadata(deps, offering_id.0)
+        }
+        ExecuteMsg::ClaimProceeds { denoms } => execute::claim_proceeds(deps, info, denoms),
+        ExecuteMsg::ForceWithdraw {
+            limit,
+            start_after,
+            collection,
+        } => {
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(
+                deps,
+                env,
+                SudoMsg::ForceWithdraw {
+                    limit,
+                    start_after,
+                    collection,
+                },
+            )
+        }
+        ExecuteMsg::Block {
+            address,
+            reason,
+            expires,
+        } => {
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(
+                deps,
+                env,
+                SudoMsg::Block {
+                    address,
+                    reason,
+                    expires,
+                },
+            )
+        }
+        ExecuteMsg::Unblock { address } => {
+            execute::check_executer_is_admin(deps.as_ref(), &info.sender)?;
+            sudo(deps, env, SudoMsg::Unblock { address })
+        }
+    }
+}
+
+// the chain runs the admin messages through governance, without an admin signature
+#[entry_point]
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::UpdateFeeReceiverAddress { new_address } => {
+            execute::update_fee_receiver_address(deps, new_address)
+        }
+        SudoMsg::UpdatePlatformFee { new_fee } => execute::update_platform_fee(deps, new_fee),
+        SudoMsg::UpdatePullPayments { enabled } => execute::update_pull_payments(deps, enabled),
+        SudoMsg::UpdateFeaturedSlots { slots } => execute::update_featured_slots(deps, slots),
+        SudoMsg::UpdateMaxPriceAge { max_price_age } => {
+            execute::update_max_price_age(deps, max_price_age)
+        }
+        SudoMsg::UpdateReferralFee { bps } => execute::update_referral_fee(deps, bps),
+        SudoMsg::SetReferrerLimit { referrer, max_bps } => {
+            execute::set_referrer_limit(deps, referrer, max_bps)
         }
-        ExecuteMsg::ForceWithdrawAll {} => execute::force_withdraw_all(deps, info),
+        SudoMsg::UpdateFeeTiers { denom, tiers } => execute::update_fee_tiers(deps, denom, tiers),
+        SudoMsg::UpdateRebates { token, rates } => execute::update_rebates(deps, env, token, rates),
+        SudoMsg::UpdateWashTrading {
+            holding_period,
+            pair_window,
+            reject,
+        } => execute::update_wash_trading(deps, holding_period, pair_window, reject),
+        SudoMsg::UpdateListingLimits {
+            deposit,
+            max_per_seller,
+        } => execute::update_listing_limits(deps, deposit, max_per_seller),
+        SudoMsg::UpdateIcs721Bridge { address } => execute::update_ics721_bridge(deps, address),
+        SudoMsg::RegisterCollection {
+            address,
+            name,
+            symbol,
+        } => execute::register_collection(deps, address, name, symbol),
+        SudoMsg::UnregisterCollection { address } => execute::unregister_collection(deps, address),
+        SudoMsg::UpdateVerifyCollections { enabled } => {
+            execute::update_verify_collections(deps, enabled)
+        }
+        SudoMsg::ForceWithdraw {
+            limit,
+            start_after,
+            collection,
+        } => execute::force_withdraw(deps, limit, start_after.map(|id| id.0), collection),
+        SudoMsg::Block {
+            address,
+            reason,
+            expires,
+        } => execute::block(deps, env, address, reason, expires),
+        SudoMsg::Unblock { address } => execute::unblock(deps, address),
     }
 }
 
 #[entry_point]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
-        QueryMsg::GetOfferings { filter_seller } => to_binary(&queries::query_offerings(deps, filter_seller)?),
+        QueryMsg::GetOfferings {
+            filter_seller,
+            include_metadata,
+            collection,
+            sort,
+            start_after,
+            limit,
+        } => to_binary(&queries::query_offerings(
+            deps,
+            filter_seller,
+            include_metadata.unwrap_or(false),
+            collection,
+            sort,
+            start_after,
+            limit,
+        )?),
         // QueryMsg::GetPlatformFee {} => to_binary(&queries::query_platform_fee(deps)?),
         // QueryMsg::GetDenom {} => to_binary(&queries::query_denom(deps)?),
         // QueryMsg::GetDaoAddress {} => to_binary(&queries::query_dao_address(deps)?),
@@ -86,34 +344,143 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::GetCollectionVolume { address } => {
             to_binary(&queries::query_collection_volume(deps, &address)?)
         }
+
+        QueryMsg::GetStuckTransfers { filter_address } => {
+            to_binary(&queries::query_stuck_transfers(deps, filter_address)?)
+        }
+
+        QueryMsg::SimulateBuy {
+            offering_id,
+            buyer,
+            referrer,
+        } => to_binary(&queries::query_simulate_buy(
+            deps,
+            env,
+            offering_id.0,
+            buyer,
+            referrer,
+        )?),
+
+        QueryMsg::GetPendingProceeds { address } => {
+            to_binary(&queries::query_pending_proceeds(deps, address)?)
+        }
+
+        QueryMsg::GetRegisteredCollections {} => {
+            to_binary(&queries::query_registered_collections(deps)?)
+        }
+
+        QueryMsg::GetFeatured { collection } => {
+            to_binary(&queries::query_featured(deps, env, collection)?)
+        }
+
+        QueryMsg::GetStaleListings { start_after, limit } => to_binary(
+            &queries::query_stale_listings(deps, env, start_after.map(|id| id.0), limit)?,
+        ),
+
+        QueryMsg::IsBlocked { address } => {
+            to_binary(&queries::query_is_blocked(deps, env, address)?)
+        }
+
+        QueryMsg::GetBlocklist { start_after, limit } => {
+            to_binary(&queries::query_blocklist(deps, start_after, limit)?)
+        }
+
+        QueryMsg::GetReferralTotals { referrer } => {
+            to_binary(&queries::query_referral_totals(deps, referrer)?)
+        }
+
+        QueryMsg::GetFeeTiers {} => to_binary(&queries::query_fee_tiers(deps)?),
+
+        QueryMsg::GetRebateConfig {} => to_binary(&REBATE_CONFIG.may_load(deps.storage)?),
+        QueryMsg::GetRebates { address } => to_binary(&queries::query_rebates(deps, address)?),
+
+        QueryMsg::GetWashTrading {} => {
+            to_binary(&WASH_TRADING.may_load(deps.storage)?.unwrap_or_default())
+        }
+
+        QueryMsg::GetPrepaidDeposit { address } => {
+            to_binary(&queries::query_prepaid_deposit(deps, address)?)
+        }
+
+        QueryMsg::GetEffectiveOwner {
+            collection,
+            token_id,
+        } => to_binary(&queries::query_effective_owner(
+            deps, env, collection, token_id,
+        )?),
+        QueryMsg::GetEffectiveOwners {
+            collection,
+            token_ids,
+        } => to_binary(&queries::query_effective_owners(
+            deps, env, collection, token_ids,
+        )?),
+        QueryMsg::GetEscrowedTokens {
+            owner,
+            start_after,
+            limit,
+        } => to_binary(&queries::query_escrowed_tokens(
+            deps,
+            env,
+            owner,
+            start_after.map(|id| id.0),
+            limit,
+        )?),
+    }
+}
+
+// every reply is the result of an NFT transfer out of the marketplace or of a failed rebate mint
+#[entry_point]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    if execute::REBATE_REPLY_IDS.contains(&msg.id) {
+        return execute::rebate_reply(deps, msg);
     }
+    execute::transfer_reply(deps, env, msg)
 }
 
 #[entry_point]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
     // https://docs.cosmwasm.com/docs/1.0/smart-contracts/migration/
     let ver = cw2::get_contract_version(deps.storage)?;
-    // ensure we are migrating from an allowed contract
-    if ver.contract != CONTRACT_NAME {
-        return Err(StdError::generic_err("Can only upgrade from same type").into());
-    }
-    // note: better to do proper semver compare, but string compare *usually* works
-    if ver.version >= (*CONTRACT_VERSION).to_string() {
-        return Err(StdError::generic_err("Cannot upgrade from a newer version").into());
+    let stored = Version::parse(&ver.version)?;
+    let new = Version::parse(CONTRACT_VERSION)?;
+
+    // ensure we are migrating from an allowed contract, and never to an older version
+    if ver.contract != CONTRACT_NAME || stored > new {
+        return Err(ContractError::CannotMigrate {
+            contract: ver.contract,
+            from: ver.version,
+            to: CONTRACT_VERSION.to_string(),
+        });
     }
 
+    // rewrite the stored state for every version in between
+    let mut deps = deps;
+    let applied = migrations::run_migrations(deps.branch(), &env, &stored, &new, &msg)?;
+
     // set the new version
     cw2::set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
-    // do any desired state migrations here...
-
-    // update the version field in the ContractInfo
+    // update the version field & any new config values in the ContractInfo
     let mut config = CONTRACT_INFO.load(deps.storage)?;
     config.version = CONTRACT_VERSION.to_string();
+    if let Some(admin) = msg.admin {
+        config.admin = deps.api.addr_validate(&admin)?.into_string();
+    }
+    if let Some(fee_receive_address) = msg.fee_receive_address {
+        config.fee_receive_address = deps.api.addr_validate(&fee_receive_address)?.into_string();
+    }
+    if let Some(platform_fee) = msg.platform_fee {
+        if platform_fee > 100 {
+            return Err(ContractError::PlatformFeeToHigh {});
+        }
+        config.platform_fee = platform_fee;
+    }
     CONTRACT_INFO.save(deps.storage, &config)?;
 
     Ok(Response::default()
         .add_attribute("action", "migration")
+        .add_attribute("from_version", ver.version)
         .add_attribute("version", CONTRACT_VERSION)
-        .add_attribute("contract", CONTRACT_NAME))
+        .add_attribute("contract", CONTRACT_NAME)
+        .add_attribute("applied", applied.join(",")))
 }
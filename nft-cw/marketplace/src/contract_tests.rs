@@ -1,19 +1,52 @@
 #[cfg(test)]
 // Example I like & am using:
 // https://github.com/osmosis-labs/cw-usdc/blob/main/contracts/cw-usdc/src/contract_tests.rs
-use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-use cosmwasm_std::{coins, from_binary, Deps, MessageInfo, Uint128};
+use cosmwasm_std::testing::{
+    mock_env, mock_info, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
+};
+use cosmwasm_std::{
+    attr, coin, coins, from_binary, BankMsg, Coin, ContractResult, CosmosMsg, Decimal, Deps, Env,
+    FullDelegation, MessageInfo, OwnedDeps, Reply, ReplyOn, Response, SubMsg, SubMsgResponse,
+    SubMsgResult, SystemResult, Uint128, WasmMsg, WasmQuery,
+};
+use std::marker::PhantomData;
 
 use cosmwasm_std::to_binary;
 
 use crate::error::ContractError;
+use crate::events::EVENT_SCHEMA_VERSION;
+use crate::ics721::{
+    IbcDeliveryCallback, IbcOutgoingMsg, Ics721AckCallbackMsg, Ics721Memo, Ics721QueryMsg,
+    Ics721ReceiveCallbackMsg, Ics721Status, NonFungibleTokenPacketData,
+};
+use crate::migrations::v0_4;
 use crate::msg::{
-    CollectionVolumeResponse, ContractInfoResponse, ExecuteMsg, InitMsg, QueryMsg, SellNft,
+    BlockedResponse, BlocklistResponse, BridgedSellNft, CollectionVolumeResponse,
+    ContractInfoResponse, EffectiveOwnerResponse, EffectiveOwnersResponse, EscrowedTokensResponse,
+    ExecuteMsg, FeaturedResponse, FeeTiersResponse, ForceWithdrawResponse, InitMsg, ListingState,
+    MigrateMsg, OfferingId, OfferingSort, OfferingsCursor, PendingProceedsResponse,
+    PrepaidDepositResponse, PurchaseFailure, QueryMsg, RebatesResponse, ReferralTotalsResponse,
+    RegisteredCollection, RegisteredCollectionsResponse, SellNft, SimulateBuyResponse,
+    StaleListing, StaleListingsResponse, StuckTransfersResponse, SudoMsg, DEFAULT_FEATURED_SLOTS,
+    DEFAULT_MAX_PRICE_AGE,
+};
+use crate::oracle::{CraftQuery, UsdPriceResponse};
+use crate::state::{
+    offerings, FeeTier, NftMetadata, Payout, Promotion, RebateRate, TransferKind,
+    APPROVAL_LISTINGS, LAST_SALES, OFFERING_METADATA, PAIR_TRADES,
 };
-use cw721::Cw721ReceiveMsg;
+use cosmwasm_std::{Addr, Order, StdResult};
+use cw20::{Cw20ExecuteMsg, Cw20QueryMsg, MinterResponse, TokenInfoResponse};
+use cw721::{
+    Approval, ApprovalResponse, ContractInfoResponse as Cw721ContractInfo, Cw721ExecuteMsg,
+    Cw721QueryMsg, Cw721ReceiveMsg, NftInfoResponse, OperatorsResponse, OwnerOfResponse,
+};
+use cw721_base::msg::QueryMsg as Cw721BaseQueryMsg;
+use cw_storage_plus::{Item, Map};
+use cw_utils::{Expiration, PaymentError};
 
 use crate::contract;
-use crate::contract::{execute, instantiate, query};
+use crate::contract::{execute, instantiate, query, CONTRACT_NAME};
 use crate::msg::OfferingsResponse;
 use cosmwasm_std::DepsMut;
 
@@ -24,7 +57,7 @@ const DENOM: &str = "ucraft";
 
 #[test]
 fn proper_initialization() {
-    let mut deps = mock_dependencies();
+    let mut deps = mock_deps();
     let (_, dao_address, platform_fee) = initialize_contract(deps.as_mut());
 
     let res: ContractInfoResponse = from_binary(
@@ -39,7 +72,7 @@ fn proper_initialization() {
 
 #[test]
 fn test_update_fee_receiver_address() {
-    let mut deps = mock_dependencies();
+    let mut deps = mock_deps();
     let (_, fee_receiver, _) = initialize_contract(deps.as_mut());
     // println!("Initial fee_receiver: {:?}", fee_receiver);
 
@@ -76,7 +109,7 @@ fn test_update_fee_receiver_address() {
 
 #[test]
 fn test_update_platform_fee() {
-    let mut deps = mock_dependencies();
+    let mut deps = mock_deps();
     let (_, fee_receiver, _platform_fee) = initialize_contract(deps.as_mut());
     // println!("Initial fee_receiver: {}, platform fee: {}", fee_receiver, platform_fee);
 
@@ -116,13 +149,138 @@ fn test_update_platform_fee() {
 }
 
 #[test]
-fn test_force_withdraw_all_from_marketplace() {
-    let mut deps = mock_dependencies();
+fn test_sudo_admin_messages() {
+    let mut deps = mock_deps();
+    let (_, fee_receiver, _) = initialize_contract(deps.as_mut());
+
+    // governance changes the config without the admin signing anything
+    let res = contract::sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::UpdatePlatformFee { new_fee: 3 },
+    )
+    .unwrap();
+    assert_eq!(res.attributes, vec![attr("action", "update_platform_fee")]);
+    contract::sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::UpdateFeeReceiverAddress {
+            new_address: "community_pool".to_string(),
+        },
+    )
+    .unwrap();
+    contract::sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::UpdatePullPayments { enabled: true },
+    )
+    .unwrap();
+
+    let res: ContractInfoResponse = from_binary(
+        &contract::query(deps.as_ref(), mock_env(), QueryMsg::GetContractInfo {}).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.platform_fee, 3);
+    assert_eq!(res.fee_receive_address, "community_pool");
+    assert!(res.pull_payments);
+    // the admin is unchanged
+    assert_eq!(res.admin, fee_receiver);
+
+    // the same validation applies as for the admin
+    let err = contract::sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::UpdatePlatformFee { new_fee: 101 },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::PlatformFeeToHigh {}));
+    let err = contract::sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::UpdateFeeReceiverAddress {
+            new_address: "Community_Pool".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Std(_)));
+
+    // allowlist a collection & turn on verification
+    contract::sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::RegisterCollection {
+            address: "collection".to_string(),
+            name: "Craft".to_string(),
+            symbol: "CRAFT".to_string(),
+        },
+    )
+    .unwrap();
+    contract::sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::UpdateVerifyCollections { enabled: true },
+    )
+    .unwrap();
+    let res: RegisteredCollectionsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetRegisteredCollections {},
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(1, res.collections.len());
+
+    // force withdraw sends every listing back
+    contract::sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::UpdateVerifyCollections { enabled: false },
+    )
+    .unwrap();
+    sell_nft(
+        deps.as_mut(),
+        mock_info("seller", &[]),
+        "token1".to_string(),
+        1_000_000,
+    );
+    let res = contract::sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::ForceWithdraw {
+            limit: None,
+            start_after: None,
+            collection: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(1, res.messages.len());
+    assert_eq!(0, get_offerings(deps.as_ref()).offerings.len());
+
+    // the admin messages still need the admin through execute
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::UpdatePlatformFee { new_fee: 1 },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized { .. }));
+}
+
+#[test]
+fn test_force_withdraw_from_marketplace() {
+    let mut deps = mock_deps();
     let (_, fee_receiver, _) = initialize_contract(deps.as_mut());
     println!("Initial fee_receiver: {}", fee_receiver);
 
     // contract::update_fee_receiver_address(deps.as_mut(), mock_info("anyone", &coins(1, "token")), "new_dao_address".to_string()).unwrap();
-    let msg = ExecuteMsg::ForceWithdrawAll {};
+    let msg = ExecuteMsg::ForceWithdraw {
+        limit: None,
+        start_after: None,
+        collection: None,
+    };
     let useless_coins = coins(1_000_000, "ucraft");
 
     // try changing the current address as a non DAO user (should fail)
@@ -167,9 +325,70 @@ fn test_force_withdraw_all_from_marketplace() {
     assert_eq!(res.offerings.len(), 0);
 }
 
+#[test]
+fn test_force_withdraw_paginated_by_collection() {
+    let mut deps = mock_deps();
+    let (_, fee_receiver, _) = initialize_contract(deps.as_mut());
+
+    // sell_nft uses the sender as the collection address
+    let collection_a = mock_info("collection_a", &[]);
+    let collection_b = mock_info("collection_b", &[]);
+    for token_id in ["token1", "token2", "token3"] {
+        sell_nft(
+            deps.as_mut(),
+            collection_a.clone(),
+            token_id.to_string(),
+            1_000_000,
+        );
+    }
+    sell_nft(
+        deps.as_mut(),
+        collection_b,
+        String::from("token4"),
+        1_000_000,
+    );
+
+    let info = mock_info(&fee_receiver, &[]);
+    let force_withdraw = |start_after: Option<OfferingId>| ExecuteMsg::ForceWithdraw {
+        limit: Some(2),
+        start_after,
+        collection: Some("collection_a".to_string()),
+    };
+
+    // first page returns a cursor since collection_a has 3 listings
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        force_withdraw(None),
+    )
+    .unwrap();
+    assert_eq!(2, res.messages.len());
+    let page: ForceWithdrawResponse = from_binary(&res.data.unwrap()).unwrap();
+    assert_eq!(2, page.withdrawn);
+    assert_eq!(Some(OfferingId(2)), page.next_start_after);
+    assert_eq!(2, get_offerings(deps.as_ref()).offerings.len());
+
+    // last page has no cursor & only touches collection_a
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        force_withdraw(page.next_start_after),
+    )
+    .unwrap();
+    let page: ForceWithdrawResponse = from_binary(&res.data.unwrap()).unwrap();
+    assert_eq!(1, page.withdrawn);
+    assert_eq!(None, page.next_start_after);
+
+    let res = get_offerings(deps.as_ref());
+    assert_eq!(1, res.offerings.len());
+    assert_eq!("token4", res.offerings[0].token_id);
+}
+
 #[test]
 fn test_sell_offering() {
-    let mut deps = mock_dependencies();
+    let mut deps = mock_deps();
 
     let (denom, _dao_address, _tax_rate) = initialize_contract(deps.as_mut());
 
@@ -177,6 +396,8 @@ fn test_sell_offering() {
     let info = mock_info("anyone", &coins(0, &denom));
     let sell_msg = SellNft {
         list_price: Uint128::new(amount), // so DAO should get 50k @ 5%
+        expiration: Expiration::Never {},
+        usd_price: None,
     };
     let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
         sender: String::from("seller_contract"),
@@ -193,7 +414,7 @@ fn test_sell_offering() {
     }
 
     // Offering should be listed = length of 1
-    let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { filter_seller: None }).unwrap();
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { filter_seller: None, include_metadata: None, collection: None, sort: None, start_after: None, limit: None }).unwrap();
     let value: OfferingsResponse = from_binary(&res).unwrap();
     assert_eq!(1, value.offerings.len());
 }
@@ -201,7 +422,7 @@ fn test_sell_offering() {
 #[test]
 fn test_buying_offering() {
     // test_sell_offering
-    let mut deps = mock_dependencies();
+    let mut deps = mock_deps();
 
     let (denom, _dao_address, _tax_rate) = initialize_contract(deps.as_mut());
 
@@ -215,7 +436,7 @@ fn test_buying_offering() {
     );
 
     // Offering should be listed = length of 1
-    let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { filter_seller: None }).unwrap();
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { filter_seller: None, include_metadata: None, collection: None, sort: None, start_after: None, limit: None }).unwrap();
     let value: OfferingsResponse = from_binary(&res).unwrap();
     assert_eq!(1, value.offerings.len());
 
@@ -227,7 +448,9 @@ fn test_buying_offering() {
         mock_env(),
         info,
         ExecuteMsg::BuyNft {
-            offering_id: value.offerings[0].offering_id.clone(),
+            offering_id: value.offerings[0].offering_id,
+            recipient: None,
+            referrer: None,
         },
     );
     match res {
@@ -239,7 +462,7 @@ fn test_buying_offering() {
     // panic!("{}", _res.unwrap_err()); // useful for debugging
 
     // check offerings again. Should be 0 since the NFT is bought
-    let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { filter_seller: None }).unwrap();
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { filter_seller: None, include_metadata: None, collection: None, sort: None, start_after: None, limit: None }).unwrap();
     let value: OfferingsResponse = from_binary(&res).unwrap();
     assert_eq!(0, value.offerings.len());
 
@@ -254,13 +477,13 @@ fn test_buying_offering() {
     );
 
     // get offering_id in the offering list
-    let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { filter_seller: None }).unwrap();
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { filter_seller: None, include_metadata: None, collection: None, sort: None, start_after: None, limit: None }).unwrap();
     let value: OfferingsResponse = from_binary(&res).unwrap();
-    let offering_id = value.offerings[0].offering_id.clone();
-    assert_eq!("2", offering_id);
+    let offering_id = value.offerings[0].offering_id;
+    assert_eq!(OfferingId(2), offering_id);
 
     // try to have the seller buy their own offering. Should error out
-    match buy_nft(deps.as_mut(), info_seller, offering_id.clone()) {
+    match buy_nft(deps.as_mut(), info_seller, offering_id) {
         //
         Ok(_) => panic!("should have failed, InsufficientFundsSend"),
         Err(e) => match e {
@@ -269,28 +492,45 @@ fn test_buying_offering() {
         },
     }
 
-    // buyer tries to buy it with an overpayment
-    let buyer_info = mock_info("addr1", &coins(overpay_amount, &denom));
+    // buyer tries to buy it with an underpayment
+    let buyer_info = mock_info("addr1", &coins(for_sale_amount - 1, &denom));
     match buy_nft(deps.as_mut(), buyer_info, offering_id) {
-        //
         Ok(_) => panic!("should have failed, InsufficientFundsSend"),
-        Err(e) => {
-            match e {
-                ContractError::InsufficientFundsSend {
-                    needed: _,
-                    received: _,
-                } => {}
-                // ContractError::UnableToPurchaseMarketplaceItemYouSold {  } => {}, // not checked this case yet
-                _ => panic!("should have failed with OverpayingForOffering"),
-            }
-        }
+        Err(e) => match e {
+            ContractError::InsufficientFundsSend { .. } => {}
+            _ => panic!("should have failed with InsufficientFundsSend"),
+        },
     }
+
+    // buyer overpays with an extra denom, the change & the other coin are refunded
+    let buyer_info = mock_info("addr1", &[coin(overpay_amount, &denom), coin(10, "uexp")]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        buyer_info,
+        ExecuteMsg::BuyNft {
+            offering_id,
+            recipient: None,
+            referrer: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[1],
+        SubMsg::new(BankMsg::Send {
+            to_address: "addr1".to_string(),
+            amount: vec![
+                coin(overpay_amount - for_sale_amount, &denom),
+                coin(10, "uexp")
+            ],
+        })
+    );
 }
 
 #[test]
 fn test_buy_with_volume() {
     // test_sell_offering
-    let mut deps = mock_dependencies();
+    let mut deps = mock_deps();
 
     let (denom, _dao_address, _tax_rate) = initialize_contract(deps.as_mut());
 
@@ -328,7 +568,9 @@ fn test_buy_with_volume() {
         mock_env(),
         info,
         ExecuteMsg::BuyNft {
-            offering_id: "1".to_string(),
+            offering_id: OfferingId(1),
+            recipient: None,
+            referrer: None,
         },
     );
     match res {
@@ -356,7 +598,7 @@ fn test_buy_with_volume() {
     assert_eq!(Uint128::from(amount), value.total_volume);
 
     // check offerings again. Should be 0 since the NFT is bought
-    let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { filter_seller: None }).unwrap();
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { filter_seller: None, include_metadata: None, collection: None, sort: None, start_after: None, limit: None }).unwrap();
     let value: OfferingsResponse = from_binary(&res).unwrap();
     assert_eq!(0, value.offerings.len());
 
@@ -371,13 +613,13 @@ fn test_buy_with_volume() {
     );
 
     // get offering_id in the offering list
-    let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { filter_seller: None }).unwrap();
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { filter_seller: None, include_metadata: None, collection: None, sort: None, start_after: None, limit: None }).unwrap();
     let value: OfferingsResponse = from_binary(&res).unwrap();
-    let offering_id = value.offerings[0].offering_id.clone();
-    assert_eq!("2", offering_id);
+    let offering_id = value.offerings[0].offering_id;
+    assert_eq!(OfferingId(2), offering_id);
 
     // try to have the seller buy their own offering. Should error out
-    match buy_nft(deps.as_mut(), info_seller, offering_id.clone()) {
+    match buy_nft(deps.as_mut(), info_seller, offering_id) {
         //
         Ok(_) => panic!("should have failed, InsufficientFundsSend"),
         Err(e) => match e {
@@ -386,158 +628,3067 @@ fn test_buy_with_volume() {
         },
     }
 
-    // buyer tries to buy it with an overpayment
+    // buyer overpays, only the list price counts towards the volume
     let buyer_info = mock_info("addr1", &coins(overpay_amount, &denom));
-    match buy_nft(deps.as_mut(), buyer_info, offering_id) {
-        //
-        Ok(_) => panic!("should have failed, InsufficientFundsSend"),
-        Err(e) => {
-            match e {
-                ContractError::InsufficientFundsSend {
-                    needed: _,
-                    received: _,
-                } => {}
-                // ContractError::UnableToPurchaseMarketplaceItemYouSold {  } => {}, // not checked this case yet
-                _ => panic!("should have failed with OverpayingForOffering"),
-            }
-        }
-    }
+    buy_nft(deps.as_mut(), buyer_info, offering_id).unwrap();
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetCollectionVolume { address: c_addr },
+    )
+    .unwrap();
+    let value: CollectionVolumeResponse = from_binary(&res).unwrap();
+    assert_eq!(Uint128::from(amount + for_sale_amount), value.total_volume);
 }
 
 #[test]
-fn test_withdraw_offering() {
-    // TODO: Cleanup
-    let mut deps = mock_dependencies();
-
-    let msg = InitMsg {
-        name: String::from("test market"),
-        denom: String::from("ucraft"),
-        fee_receive_address: String::from("craftdaoaddr"),
-        platform_fee: 5,
-    };
-    let denom = msg.denom.clone();
-
-    let info = mock_info("creator", &coins(1_000_000, &denom));
-    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-
-    // beneficiary can release it
-    let info = mock_info("anyone", &coins(1_000_000, &denom));
-    receive_nft(deps.as_mut(), info, 1_000_000, String::from("token_id")).unwrap();
-
-    // Offering should be listed
-    let value = get_offerings(deps.as_ref());
-    assert_eq!(1, value.offerings.len());
+fn test_purchase_pays_out_after_transfer() {
+    let mut deps = mock_deps();
+    let (denom, dao_address, _) = initialize_contract(deps.as_mut());
 
-    // withdraw offering
-    let withdraw_info = mock_info("seller", &coins(2, &denom));
-    let withdraw_msg = ExecuteMsg::WithdrawNft {
-        offering_id: value.offerings[0].offering_id.clone(),
-    };
-    let _res = execute(deps.as_mut(), mock_env(), withdraw_info, withdraw_msg).unwrap();
+    sell_nft(
+        deps.as_mut(),
+        mock_info("seller", &[]),
+        String::from("token1"),
+        1_000_000,
+    );
 
-    // Offering should be removed
-    let value = get_offerings(deps.as_ref());
-    assert_eq!(0, value.offerings.len());
+    // only the cw721 transfer is sent, the seller is paid once it succeeds
+    let info = mock_info("buyer", &coins(1_000_000, &denom));
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::BuyNft {
+            offering_id: OfferingId(1),
+            recipient: None,
+            referrer: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(1, res.messages.len());
+    assert_eq!(ReplyOn::Always, res.messages[0].reply_on);
+    assert_eq!(1, res.messages[0].id);
+
+    let res = contract::reply(deps.as_mut(), mock_env(), transfer_ok(1)).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(BankMsg::Send {
+                to_address: "seller".to_string(),
+                amount: coins(950_000, &denom),
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: dao_address,
+                amount: coins(50_000, &denom),
+            }),
+        ]
+    );
 }
 
 #[test]
-fn test_update_offering_price() {
-    let mut deps = mock_dependencies();
-
-    let (denom, _, _) = initialize_contract(deps.as_mut());
-
-    let amount: u128 = 1_000_000; // list price & buy price
-    let info = mock_info("anyone", &coins(0, &denom));
-    sell_nft(deps.as_mut(), info.clone(), String::from("token1"), amount);
+fn test_receive_nft_verifies_custody() {
+    let mut deps = mock_deps();
+    initialize_contract(deps.as_mut());
 
-    // Offering should be listed = length of 1
-    let value: OfferingsResponse = get_offerings(deps.as_ref());
-    assert_eq!(1, value.offerings.len());
+    // the collection still reports the seller as the owner
+    let err = receive_nft(
+        deps.as_mut(),
+        mock_info("collection", &[]),
+        1_000_000,
+        "unsent1".to_string(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::NftNotReceived { .. }));
+    assert_eq!(0, get_offerings(deps.as_ref()).offerings.len());
 
-    // get the first offering
-    let offering_id = value.offerings[0].offering_id.clone();
+    receive_nft(
+        deps.as_mut(),
+        mock_info("collection", &[]),
+        1_000_000,
+        "token1".to_string(),
+    )
+    .unwrap();
+    assert_eq!(1, get_offerings(deps.as_ref()).offerings.len());
+}
 
-    // update the price of the offering
-    let new_amount: Uint128 = Uint128::from(9_999_999_u128);
-    let update_msg = ExecuteMsg::UpdateListingPrice {
-        offering_id,
-        new_price: new_amount,
+#[test]
+fn test_collection_registry() {
+    let mut deps = mock_deps();
+    let (_, admin, _) = initialize_contract(deps.as_mut());
+    let admin_info = mock_info(&admin, &[]);
+
+    let msg = ExecuteMsg::UpdateVerifyCollections { enabled: true };
+    let res = execute(deps.as_mut(), mock_env(), admin_info.clone(), msg).unwrap();
+    let attrs = &res.events[0].attributes;
+    assert!(attrs.contains(&attr("old_enabled", "false")));
+    assert!(attrs.contains(&attr("new_enabled", "true")));
+
+    let list = |deps: DepsMut, collection: &str| {
+        receive_nft(
+            deps,
+            mock_info(collection, &[]),
+            1_000_000,
+            "token1".to_string(),
+        )
     };
-
-    let res = contract::execute(deps.as_mut(), mock_env(), info, update_msg.clone());
-    match res {
-        Ok(_) => {}
-        Err(e) => panic!("should have succeeded: {:?}", e),
+    let err = list(deps.as_mut(), "collection").unwrap_err();
+    assert!(matches!(err, ContractError::CollectionNotRegistered { .. }));
+
+    // the mock collection reports its address as the name
+    for (address, name) in [("collection", "collection"), ("imposter", "collection")] {
+        let msg = ExecuteMsg::RegisterCollection {
+            address: address.to_string(),
+            name: name.to_string(),
+            symbol: "NFT".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), admin_info.clone(), msg).unwrap();
     }
+    let err = list(deps.as_mut(), "imposter").unwrap_err();
+    assert!(matches!(err, ContractError::CollectionInfoMismatch { .. }));
+    list(deps.as_mut(), "collection").unwrap();
 
-    // confirm the attribute key of old_price is updated to new_price
-    let value: OfferingsResponse = get_offerings(deps.as_ref());
-    assert_eq!(new_amount, value.offerings[0].list_price);
-
-    // try to update the price of the offering, but with the incorrect sender
-    let info = mock_info("wrong_person", &coins(0, &denom));
-    let res = contract::execute(deps.as_mut(), mock_env(), info, update_msg);
-    match res {
-        Ok(_) => panic!("should have failed"),
-        Err(e) => match e {
-            ContractError::Unauthorized { msg: _ } => {}
-            _ => panic!("should have failed with InvalidSender"),
-        },
-    }
+    let msg = ExecuteMsg::UnregisterCollection {
+        address: "imposter".to_string(),
+    };
+    execute(deps.as_mut(), mock_env(), admin_info, msg).unwrap();
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetRegisteredCollections {},
+    )
+    .unwrap();
+    let value: RegisteredCollectionsResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        vec![RegisteredCollection {
+            address: Addr::unchecked("collection"),
+            name: "collection".to_string(),
+            symbol: "NFT".to_string(),
+        }],
+        value.collections
+    );
 }
 
 #[test]
-fn test_query_selective_seller_offerings_only() {
-    // test_sell_offering
-    let mut deps = mock_dependencies();
+fn test_featured_offerings() {
+    let mut deps = mock_deps();
+    let (denom, admin, _) = initialize_contract(deps.as_mut());
+
+    let msg = ExecuteMsg::UpdateFeaturedSlots { slots: 2 };
+    execute(deps.as_mut(), mock_env(), mock_info(&admin, &[]), msg).unwrap();
+
+    for token_id in ["token1", "token2", "token3"] {
+        sell_nft(
+            deps.as_mut(),
+            mock_info("seller", &[]),
+            String::from(token_id),
+            1_000_000,
+        );
+    }
+    let promote = |deps: DepsMut, offering_id: u64, bid: u128| {
+        let msg = ExecuteMsg::PromoteOffering {
+            offering_id: offering_id.into(),
+            duration: 60,
+        };
+        execute(
+            deps,
+            mock_env(),
+            mock_info("seller", &coins(bid, DENOM)),
+            msg,
+        )
+    };
+    let featured = |deps: Deps, env| -> Vec<(OfferingId, u128)> {
+        let msg = QueryMsg::GetFeatured { collection: None };
+        let res: FeaturedResponse = from_binary(&query(deps, env, msg).unwrap()).unwrap();
+        res.featured
+            .into_iter()
+            .map(|f| (f.offering.offering_id, f.bid.amount.u128()))
+            .collect()
+    };
 
-    let (denom, _dao_address, _tax_rate) = initialize_contract(deps.as_mut());
+    promote(deps.as_mut(), 1, 100).unwrap();
+    promote(deps.as_mut(), 2, 300).unwrap();
+    let err = promote(deps.as_mut(), 2, 500).unwrap_err();
+    assert!(matches!(err, ContractError::AlreadyPromoted { .. }));
+
+    // both slots are taken, it has to beat the lowest bid
+    let err = promote(deps.as_mut(), 3, 100).unwrap_err();
+    assert!(matches!(err, ContractError::PromotionBidTooLow { .. }));
+    let res = promote(deps.as_mut(), 3, 200).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "seller".to_string(),
+            amount: coins(100, &denom),
+        })]
+    );
+    assert_eq!(
+        vec![(OfferingId(2), 300), (OfferingId(3), 200)],
+        featured(deps.as_ref(), mock_env())
+    );
 
-    let amount = 1_000_000u128; // list price & buy price
-    let info_seller = mock_info("seller", &coins(0, &denom));
-    sell_nft(
+    // selling a featured offering pays its bid to the fee receiver
+    let info = mock_info("buyer", &coins(1_000_000, &denom));
+    let res = execute(
         deps.as_mut(),
-        info_seller.clone(),
-        String::from("token1"),
-        amount,
+        mock_env(),
+        info,
+        ExecuteMsg::BuyNft {
+            offering_id: OfferingId(2),
+            recipient: None,
+            referrer: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[1],
+        SubMsg::new(BankMsg::Send {
+            to_address: admin.clone(),
+            amount: coins(300, &denom),
+        })
     );
-    sell_nft(
+
+    // once expired it is no longer featured & the next promotion pays it out
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(60);
+    assert!(featured(deps.as_ref(), env.clone()).is_empty());
+
+    let msg = ExecuteMsg::PromoteOffering {
+        offering_id: OfferingId(1),
+        duration: 60,
+    };
+    let res = execute(
         deps.as_mut(),
-        info_seller.clone(),
-        String::from("token2"),
-        amount,
+        env.clone(),
+        mock_info("seller", &coins(50, DENOM)),
+        msg,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: admin,
+            amount: coins(200, &denom),
+        })]
     );
-    let random_seller = mock_info("random_person", &coins(0, &denom));
+    assert_eq!(vec![(OfferingId(1), 50)], featured(deps.as_ref(), env));
+}
+
+#[test]
+fn test_offering_metadata() {
+    let mut deps = mock_deps();
+    initialize_contract(deps.as_mut());
     sell_nft(
         deps.as_mut(),
-        random_seller.clone(),
-        String::from("token2"),
-        amount,
+        mock_info("collection", &[]),
+        String::from("token1"),
+        1_000_000,
     );
 
-    // Offering should be listed = length of 1
-    let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { filter_seller: Some("seller".to_string()) }).unwrap();
-    let value: OfferingsResponse = from_binary(&res).unwrap();
-    assert_eq!(2, value.offerings.len());
+    let offerings = |deps: Deps, include_metadata| -> OfferingsResponse {
+        let msg = QueryMsg::GetOfferings {
+            filter_seller: None,
+            include_metadata,
+            collection: None,
+            sort: None,
+            start_after: None,
+            limit: None,
+        };
+        from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+    };
+    assert_eq!(None, offerings(deps.as_ref(), None).offerings[0].metadata);
+
+    let metadata = offerings(deps.as_ref(), Some(true)).offerings[0]
+        .metadata
+        .clone()
+        .unwrap();
+    assert_eq!(Some("ipfs://token1".to_string()), metadata.token_uri);
+    assert_eq!(Some("token1".to_string()), metadata.name);
+    // cut down to the bound
+    assert_eq!(512, metadata.description.unwrap().len());
+
+    OFFERING_METADATA.remove(deps.as_mut().storage, 1);
+    assert_eq!(
+        None,
+        offerings(deps.as_ref(), Some(true)).offerings[0].metadata
+    );
+    let msg = ExecuteMsg::RefreshMetadata {
+        offering_id: OfferingId(1),
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg).unwrap();
+    let metadata = offerings(deps.as_ref(), Some(true)).offerings[0]
+        .metadata
+        .clone();
+    assert_eq!(Some("token1".to_string()), metadata.unwrap().name);
+
+    // dropped with the offering
+    let msg = ExecuteMsg::WithdrawNft {
+        offering_id: OfferingId(1),
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("collection", &[]), msg).unwrap();
+    assert!(OFFERING_METADATA
+        .may_load(deps.as_ref().storage, 1)
+        .unwrap()
+        .is_none());
 }
 
-// ==== TEST HELPERS ====
-fn initialize_contract(deps: DepsMut) -> (String, String, u128) {
-    let msg = InitMsg {
-        name: MP_NAME.to_string(),
-        denom: DENOM.to_string(),
+#[test]
+fn test_usd_listing() {
+    let mut deps = mock_deps();
+    initialize_contract(deps.as_mut());
+
+    let sell_msg = SellNft {
+        list_price: Uint128::zero(),
+        usd_price: Some(Decimal::from_ratio(25u128, 2u128)),
+        expiration: Expiration::Never {},
+    };
+    let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+        sender: String::from("seller"),
+        token_id: String::from("token1"),
+        msg: to_binary(&sell_msg).unwrap(),
+    });
+    execute(deps.as_mut(), mock_env(), mock_info("collection", &[]), msg).unwrap();
+
+    // $12.50 at $2 per CRAFT
+    let listed = &get_offerings(deps.as_ref()).offerings[0];
+    assert_eq!(Uint128::new(6_250_000), listed.list_price);
+
+    // the funds sent are the most the buyer pays
+    let err = buy_nft(
+        deps.as_mut(),
+        mock_info("buyer", &coins(6_249_999, DENOM)),
+        OfferingId(1),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::InsufficientFundsSend { .. }));
+
+    // no price newer than max_price_age
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(DEFAULT_MAX_PRICE_AGE + 1);
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("buyer", &coins(7_000_000, DENOM)),
+        ExecuteMsg::BuyNft {
+            offering_id: OfferingId(1),
+            recipient: None,
+            referrer: None,
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::StalePrice { .. }));
+    let msg = QueryMsg::SimulateBuy {
+        offering_id: OfferingId(1),
+        buyer: "buyer".to_string(),
+        referrer: None,
+    };
+    let res: SimulateBuyResponse = from_binary(&query(deps.as_ref(), env, msg).unwrap()).unwrap();
+    assert_eq!(Some(PurchaseFailure::PriceUnavailable), res.failure);
+    assert_eq!(coins(6_250_000, DENOM), res.funds);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("buyer", &coins(7_000_000, DENOM)),
+        ExecuteMsg::BuyNft {
+            offering_id: OfferingId(1),
+            recipient: None,
+            referrer: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[1],
+        SubMsg::new(BankMsg::Send {
+            to_address: "buyer".to_string(),
+            amount: coins(750_000, DENOM),
+        })
+    );
+    let res = contract::reply(deps.as_mut(), mock_env(), transfer_ok(1)).unwrap();
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(BankMsg::Send {
+            to_address: "seller".to_string(),
+            amount: coins(5_937_500, DENOM),
+        })
+    );
+}
+
+#[test]
+fn test_pull_payments() {
+    let mut deps = mock_deps();
+    let (denom, dao_address, _) = initialize_contract(deps.as_mut());
+
+    // only the admin (the fee receiver by default) can turn it on
+    let msg = ExecuteMsg::UpdatePullPayments { enabled: true };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("seller", &[]),
+        msg.clone(),
+    );
+    assert!(matches!(err, Err(ContractError::Unauthorized { .. })));
+    let res = execute(deps.as_mut(), mock_env(), mock_info(&dao_address, &[]), msg).unwrap();
+    let attrs = &res.events[0].attributes;
+    assert!(attrs.contains(&attr("old_enabled", "false")));
+    assert!(attrs.contains(&attr("new_enabled", "true")));
+
+    for token_id in ["token1", "token2"] {
+        sell_nft(
+            deps.as_mut(),
+            mock_info("seller", &[]),
+            String::from(token_id),
+            1_000_000,
+        );
+    }
+    for id in 1..=2 {
+        let info = mock_info("buyer", &coins(1_000_000, &denom));
+        buy_nft(deps.as_mut(), info, OfferingId(id)).unwrap();
+
+        // nothing is sent, the payouts are credited
+        let res = contract::reply(deps.as_mut(), mock_env(), transfer_ok(id)).unwrap();
+        assert!(res.messages.is_empty());
+    }
+
+    let pending = |deps: Deps, address: &str| -> PendingProceedsResponse {
+        let msg = QueryMsg::GetPendingProceeds {
+            address: address.to_string(),
+        };
+        from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+    };
+    assert_eq!(
+        coins(1_900_000, &denom),
+        pending(deps.as_ref(), "seller").proceeds
+    );
+    assert_eq!(
+        coins(100_000, &denom),
+        pending(deps.as_ref(), &dao_address).proceeds
+    );
+
+    // claiming a denom with nothing credited fails
+    let msg = ExecuteMsg::ClaimProceeds {
+        denoms: Some(vec!["uexp".to_string()]),
+    };
+    let err = execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::NoProceeds {}));
+
+    let msg = ExecuteMsg::ClaimProceeds { denoms: None };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), msg).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "seller".to_string(),
+            amount: coins(1_900_000, &denom),
+        })]
+    );
+    assert!(pending(deps.as_ref(), "seller").proceeds.is_empty());
+    assert_eq!(
+        coins(100_000, &denom),
+        pending(deps.as_ref(), &dao_address).proceeds
+    );
+}
+
+#[test]
+fn test_simulate_buy() {
+    let mut deps = mock_deps();
+    let (denom, dao_address, _) = initialize_contract(deps.as_mut());
+
+    sell_nft(
+        deps.as_mut(),
+        mock_info("seller", &[]),
+        String::from("token1"),
+        1_000_000,
+    );
+
+    let simulate = |deps: Deps, env, buyer: &str| -> SimulateBuyResponse {
+        let msg = QueryMsg::SimulateBuy {
+            offering_id: OfferingId(1),
+            buyer: buyer.to_string(),
+            referrer: None,
+        };
+        from_binary(&query(deps, env, msg).unwrap()).unwrap()
+    };
+
+    let res = simulate(deps.as_ref(), mock_env(), "buyer");
+    assert_eq!(
+        res,
+        SimulateBuyResponse {
+            funds: coins(1_000_000, &denom),
+            platform_fee: Payout {
+                recipient: Addr::unchecked(&dao_address),
+                amount: coin(50_000, &denom),
+            },
+            seller_payout: Payout {
+                recipient: Addr::unchecked("seller"),
+                amount: coin(950_000, &denom),
+            },
+            referral_fee: None,
+            fee_discount_bps: 0,
+            failure: None,
+        }
+    );
+
+    let res = simulate(deps.as_ref(), mock_env(), "seller");
+    assert_eq!(Some(PurchaseFailure::BuyerIsSeller), res.failure);
+
+    // buying with exactly the simulated funds pays out the simulated amounts
+    let info = mock_info("buyer", &res.funds);
+    buy_nft(deps.as_mut(), info, OfferingId(1)).unwrap();
+    let reply = contract::reply(deps.as_mut(), mock_env(), transfer_ok(1)).unwrap();
+    assert_eq!(
+        reply.messages,
+        vec![
+            SubMsg::new(BankMsg::Send {
+                to_address: res.seller_payout.recipient.to_string(),
+                amount: vec![res.seller_payout.amount],
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: res.platform_fee.recipient.to_string(),
+                amount: vec![res.platform_fee.amount],
+            }),
+        ]
+    );
+
+    // an offering past its expiration
+    let env = mock_env();
+    let sell_msg = SellNft {
+        list_price: Uint128::new(1_000_000),
+        expiration: Expiration::AtHeight(env.block.height + 10),
+        usd_price: None,
+    };
+    let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+        sender: String::from("seller"),
+        token_id: String::from("token2"),
+        msg: to_binary(&sell_msg).unwrap(),
+    });
+    execute(deps.as_mut(), env, mock_info("collection", &[]), msg).unwrap();
+
+    let mut env = mock_env();
+    env.block.height += 10;
+    let msg = QueryMsg::SimulateBuy {
+        offering_id: OfferingId(2),
+        buyer: "buyer".to_string(),
+        referrer: None,
+    };
+    let res: SimulateBuyResponse = from_binary(&query(deps.as_ref(), env, msg).unwrap()).unwrap();
+    assert_eq!(Some(PurchaseFailure::Expired), res.failure);
+}
+
+#[test]
+fn test_referral_fees() {
+    let mut deps = mock_deps();
+    let (denom, dao_address, _) = initialize_contract(deps.as_mut());
+    let admin = mock_info(&dao_address, &[]);
+
+    // the share is in basis points of the platform fee
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        admin.clone(),
+        ExecuteMsg::UpdateReferralFee { bps: 10_001 },
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        ContractError::ReferralFeeTooHigh { max: 10_000 }
+    ));
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        admin.clone(),
+        ExecuteMsg::UpdateReferralFee { bps: 2_000 },
+    )
+    .unwrap();
+    let set_limit = |deps: DepsMut, max_bps: Option<u16>| {
+        let msg = ExecuteMsg::SetReferrerLimit {
+            referrer: "capped".to_string(),
+            max_bps,
+        };
+        let res = execute(deps, mock_env(), admin.clone(), msg).unwrap();
+        res.events[0].attributes.clone()
+    };
+    let attrs = set_limit(deps.as_mut(), Some(1_000));
+    assert!(!attrs.iter().any(|attr| attr.key == "old_max_bps"));
+    assert!(attrs.contains(&attr("new_max_bps", "1000")));
+    let attrs = set_limit(deps.as_mut(), None);
+    assert!(attrs.contains(&attr("old_max_bps", "1000")));
+    assert!(!attrs.iter().any(|attr| attr.key == "new_max_bps"));
+    let attrs = set_limit(deps.as_mut(), Some(500));
+    assert!(!attrs.iter().any(|attr| attr.key == "old_max_bps"));
+    assert!(attrs.contains(&attr("new_max_bps", "500")));
+
+    for token_id in ["token1", "token2"] {
+        sell_nft(
+            deps.as_mut(),
+            mock_info("seller", &[]),
+            String::from(token_id),
+            1_000_000,
+        );
+    }
+
+    let buy = |offering_id: u64, referrer: &str| ExecuteMsg::BuyNft {
+        offering_id: offering_id.into(),
+        recipient: None,
+        referrer: Some(referrer.to_string()),
+    };
+    let info = mock_info("buyer", &coins(1_000_000, &denom));
+
+    // a buyer can't get a share of their own fee
+    let err = execute(deps.as_mut(), mock_env(), info.clone(), buy(1, "buyer")).unwrap_err();
+    assert!(matches!(err, ContractError::SelfReferral {}));
+
+    // 20% of the 50_000 fee goes to the referrer, the seller gets the same
+    let msg = QueryMsg::SimulateBuy {
+        offering_id: OfferingId(1),
+        buyer: "buyer".to_string(),
+        referrer: Some("storefront".to_string()),
+    };
+    let res: SimulateBuyResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(coin(40_000, &denom), res.platform_fee.amount);
+    assert_eq!(coin(950_000, &denom), res.seller_payout.amount);
+    assert_eq!(
+        Some(Payout {
+            recipient: Addr::unchecked("storefront"),
+            amount: coin(10_000, &denom),
+        }),
+        res.referral_fee
+    );
+
+    // a blocked referrer fails the purchase, as buy_nft would
+    let msg = ExecuteMsg::Block {
+        address: "shady".to_string(),
+        reason: None,
+        expires: Expiration::Never {},
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(&dao_address, &[]), msg).unwrap();
+    let msg = QueryMsg::SimulateBuy {
+        offering_id: OfferingId(1),
+        buyer: "buyer".to_string(),
+        referrer: Some("shady".to_string()),
+    };
+    let res: SimulateBuyResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(Some(PurchaseFailure::Blocked), res.failure);
+    let err = execute(deps.as_mut(), mock_env(), info.clone(), buy(1, "shady")).unwrap_err();
+    assert!(matches!(err, ContractError::Blocked { .. }));
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        buy(1, "storefront"),
+    )
+    .unwrap();
+    let event = res
+        .events
+        .iter()
+        .find(|event| event.ty == "craft_marketplace.buy")
+        .unwrap();
+    assert!(event.attributes.contains(&attr("referrer", "storefront")));
+    assert!(event.attributes.contains(&attr("referral_fee", "10000")));
+    assert!(event.attributes.contains(&attr("platform_fee", "40000")));
+
+    let res = contract::reply(deps.as_mut(), mock_env(), transfer_ok(1)).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(BankMsg::Send {
+                to_address: "seller".to_string(),
+                amount: coins(950_000, &denom),
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: dao_address.clone(),
+                amount: coins(40_000, &denom),
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "storefront".to_string(),
+                amount: coins(10_000, &denom),
+            }),
+        ]
+    );
+
+    // the limited referrer gets 5% of the fee
+    execute(deps.as_mut(), mock_env(), info, buy(2, "capped")).unwrap();
+    let res = contract::reply(deps.as_mut(), mock_env(), transfer_ok(2)).unwrap();
+    assert_eq!(
+        res.messages[1..],
+        vec![
+            SubMsg::new(BankMsg::Send {
+                to_address: dao_address,
+                amount: coins(47_500, &denom),
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "capped".to_string(),
+                amount: coins(2_500, &denom),
+            }),
+        ]
+    );
+
+    let totals = |deps: Deps, referrer: &str| -> ReferralTotalsResponse {
+        let msg = QueryMsg::GetReferralTotals {
+            referrer: referrer.to_string(),
+        };
+        from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+    };
+    let res = totals(deps.as_ref(), "storefront");
+    assert_eq!((2_000, 1), (res.share_bps, res.purchases));
+    assert_eq!(coins(10_000, &denom), res.earned);
+    let res = totals(deps.as_ref(), "capped");
+    assert_eq!((500, 1), (res.share_bps, res.purchases));
+    assert_eq!(coins(2_500, &denom), res.earned);
+}
+
+#[test]
+fn test_staker_fee_discounts() {
+    let mut deps = mock_deps();
+    let (denom, dao_address, _) = initialize_contract(deps.as_mut());
+
+    let tier = |min_stake: u128, discount_bps: u16| FeeTier {
+        min_stake: Uint128::new(min_stake),
+        discount_bps,
+    };
+    let msg = ExecuteMsg::UpdateFeeTiers {
+        denom: "uexp".to_string(),
+        tiers: vec![tier(10_000, 5_000), tier(1_000, 2_000)],
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("seller", &[]),
+        msg.clone(),
+    );
+    assert!(matches!(err, Err(ContractError::Unauthorized { .. })));
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&dao_address, &[]),
+        ExecuteMsg::UpdateFeeTiers {
+            denom: "uexp".to_string(),
+            tiers: vec![tier(1, 10_001)],
+        },
+    );
+    assert!(matches!(err, Err(ContractError::FeeDiscountTooHigh { .. })));
+    let res = execute(deps.as_mut(), mock_env(), mock_info(&dao_address, &[]), msg).unwrap();
+    let attrs = &res.events[0].attributes;
+    assert!(!attrs.iter().any(|attr| attr.key == "old_tiers"));
+    assert!(attrs.contains(&attr("new_tiers", "1000:2000,10000:5000")));
+
+    // stored lowest tier first
+    let res: FeeTiersResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetFeeTiers {}).unwrap()).unwrap();
+    assert_eq!(vec![tier(1_000, 2_000), tier(10_000, 5_000)], res.tiers);
+
+    // the seller delegates 1_500uexp over two validators, other denoms don't count
+    let delegation = |validator: &str, amount: Coin| FullDelegation {
+        delegator: Addr::unchecked("seller"),
+        validator: validator.to_string(),
+        amount: amount.clone(),
+        can_redelegate: amount,
+        accumulated_rewards: vec![],
+    };
+    deps.querier.update_staking(
+        "uexp",
+        &[],
+        &[
+            delegation("val1", coin(1_000, "uexp")),
+            delegation("val2", coin(500, "uexp")),
+            delegation("val2", coin(50_000, "ustake")),
+        ],
+    );
+
+    let sell_msg = SellNft {
+        list_price: Uint128::new(1_000_000),
+        expiration: Expiration::Never {},
+        usd_price: None,
+    };
+    let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+        sender: "seller".to_string(),
+        token_id: "token1".to_string(),
+        msg: to_binary(&sell_msg).unwrap(),
+    });
+    let res = execute(deps.as_mut(), mock_env(), mock_info("collection", &[]), msg).unwrap();
+    assert!(res.events[0]
+        .attributes
+        .contains(&attr("fee_discount_bps", "2000")));
+
+    // 20% off the 50_000 fee, which the seller keeps
+    let msg = QueryMsg::SimulateBuy {
+        offering_id: OfferingId(1),
+        buyer: "buyer".to_string(),
+        referrer: None,
+    };
+    let res: SimulateBuyResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(2_000, res.fee_discount_bps);
+    assert_eq!(coin(40_000, &denom), res.platform_fee.amount);
+    assert_eq!(coin(960_000, &denom), res.seller_payout.amount);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("buyer", &coins(1_000_000, &denom)),
+        ExecuteMsg::BuyNft {
+            offering_id: OfferingId(1),
+            recipient: None,
+            referrer: None,
+        },
+    )
+    .unwrap();
+    let event = &res.events[0];
+    assert!(event.attributes.contains(&attr("fee_discount_bps", "2000")));
+    assert!(event.attributes.contains(&attr("platform_fee", "40000")));
+    assert!(event.attributes.contains(&attr("seller_amount", "960000")));
+
+    // the buyer's stake doesn't matter, "other" has none
+    sell_nft(
+        deps.as_mut(),
+        mock_info("other", &[]),
+        "token2".to_string(),
+        1_000_000,
+    );
+    let msg = QueryMsg::SimulateBuy {
+        offering_id: OfferingId(2),
+        buyer: "seller".to_string(),
+        referrer: None,
+    };
+    let res: SimulateBuyResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(0, res.fee_discount_bps);
+    assert_eq!(coin(50_000, &denom), res.platform_fee.amount);
+}
+
+#[test]
+fn test_loyalty_rebates() {
+    let mut deps = mock_deps();
+    let (denom, dao_address, _) = initialize_contract(deps.as_mut());
+
+    for token_id in ["token1", "token2", "token3", "token4"] {
+        sell_nft(
+            deps.as_mut(),
+            mock_info("seller", &[]),
+            String::from(token_id),
+            1_000_000,
+        );
+    }
+
+    // 1% of the price to the buyer & 0.5% to the seller
+    let msg = ExecuteMsg::UpdateRebates {
+        token: "points".to_string(),
+        rates: vec![RebateRate {
+            denom: denom.clone(),
+            buyer: Decimal::percent(1),
+            seller: Decimal::permille(5),
+        }],
+    };
+    mock_loyalty_token(&mut deps, "someone", 0);
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&dao_address, &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::NotRebateMinter { .. }));
+    mock_loyalty_token(&mut deps, MOCK_CONTRACT_ADDR, 0);
+    let res = execute(deps.as_mut(), mock_env(), mock_info(&dao_address, &[]), msg).unwrap();
+    let attrs = &res.events[0].attributes;
+    assert!(!attrs.iter().any(|attr| attr.key == "old_token"));
+    assert!(attrs.contains(&attr("new_token", "points")));
+
+    let buy_and_deliver = |mut deps: DepsMut, id: u64| -> Response {
+        let info = mock_info("buyer", &coins(1_000_000, &denom));
+        buy_nft(deps.branch(), info, OfferingId(id)).unwrap();
+        contract::reply(deps, mock_env(), transfer_ok(id)).unwrap()
+    };
+    let mint = |recipient: &str, amount: u128, reply_id: u64| {
+        let msg = WasmMsg::Execute {
+            contract_addr: "points".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Mint {
+                recipient: recipient.to_string(),
+                amount: Uint128::new(amount),
+            })
+            .unwrap(),
+            funds: vec![],
+        };
+        SubMsg::reply_on_error(msg, reply_id)
+    };
+
+    let res = buy_and_deliver(deps.as_mut(), 1);
+    assert_eq!(
+        res.messages[..2],
+        [
+            mint("buyer", 10_000, u64::MAX - 1),
+            mint("seller", 5_000, u64::MAX)
+        ]
+    );
+    let event = res
+        .events
+        .iter()
+        .find(|event| event.ty == "craft_marketplace.rebate");
+    assert!(event.unwrap().attributes.contains(&attr("capped", "false")));
+
+    // only 12_000 below the cap, the seller gets what is left
+    mock_loyalty_token(&mut deps, MOCK_CONTRACT_ADDR, 1_000_000 - 12_000);
+    let res = buy_and_deliver(deps.as_mut(), 2);
+    assert_eq!(
+        res.messages[..2],
+        [
+            mint("buyer", 10_000, u64::MAX - 1),
+            mint("seller", 2_000, u64::MAX)
+        ]
+    );
+    let event = res
+        .events
+        .iter()
+        .find(|event| event.ty == "craft_marketplace.rebate");
+    assert!(event.unwrap().attributes.contains(&attr("capped", "true")));
+
+    // at the cap nothing is minted, the sale still goes through
+    mock_loyalty_token(&mut deps, MOCK_CONTRACT_ADDR, 1_000_000);
+    let res = buy_and_deliver(deps.as_mut(), 3);
+    assert_eq!(2, res.messages.len());
+    assert!(res
+        .messages
+        .iter()
+        .all(|msg| matches!(msg.msg, CosmosMsg::Bank(_))));
+
+    let rebates = |deps: Deps, address: &str| -> Uint128 {
+        let msg = QueryMsg::GetRebates {
+            address: address.to_string(),
+        };
+        let res: RebatesResponse = from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap();
+        res.total
+    };
+    assert_eq!(Uint128::new(20_000), rebates(deps.as_ref(), "buyer"));
+    assert_eq!(Uint128::new(7_000), rebates(deps.as_ref(), "seller"));
+
+    // the token failed the sellers mint of the last rebates, it is taken back
+    let failed = Reply {
+        id: u64::MAX,
+        result: SubMsgResult::Err("minting is paused".to_string()),
+    };
+    let res = contract::reply(deps.as_mut(), mock_env(), failed).unwrap();
+    assert_eq!("craft_marketplace.rebate_failed", res.events[0].ty);
+    assert!(res.events[0].attributes.contains(&attr("amount", "2000")));
+    assert_eq!(Uint128::new(5_000), rebates(deps.as_ref(), "seller"));
+
+    // a token which can't be queried mints nothing, the sale still completes
+    deps.querier.update_wasm(|query| match query {
+        WasmQuery::Smart { contract_addr, .. } if contract_addr == "points" => {
+            SystemResult::Ok(ContractResult::Err("token is broken".to_string()))
+        }
+        other => panic!("unexpected wasm query {:?}", other),
+    });
+    let res = buy_and_deliver(deps.as_mut(), 4);
+    assert_eq!(2, res.messages.len());
+    assert!(res
+        .messages
+        .iter()
+        .all(|msg| matches!(msg.msg, CosmosMsg::Bank(_))));
+    let event = res
+        .events
+        .iter()
+        .find(|event| event.ty == "craft_marketplace.rebate_failed");
+    assert!(event
+        .unwrap()
+        .attributes
+        .contains(&attr("offering_id", "4")));
+    assert_eq!(Uint128::new(20_000), rebates(deps.as_ref(), "buyer"));
+}
+
+#[test]
+fn test_wash_trading() {
+    let mut deps = mock_deps();
+    let (denom, dao_address, _) = initialize_contract(deps.as_mut());
+
+    let update = |reject: bool| ExecuteMsg::UpdateWashTrading {
+        holding_period: 60 * 60,
+        pair_window: 24 * 60 * 60,
+        reject,
+    };
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&dao_address, &[]),
+        update(false),
+    )
+    .unwrap();
+
+    let list = |deps: DepsMut, env: Env, seller: &str, token_id: &str| {
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: seller.to_string(),
+            token_id: token_id.to_string(),
+            msg: to_binary(&SellNft {
+                list_price: Uint128::new(1_000_000),
+                expiration: Expiration::Never {},
+                usd_price: None,
+            })
+            .unwrap(),
+        });
+        execute(deps, env, mock_info("collection", &[]), msg)
+    };
+    let buy = |deps: DepsMut, env: Env, buyer: &str, offering_id: u64| {
+        let msg = ExecuteMsg::BuyNft {
+            offering_id: offering_id.into(),
+            recipient: None,
+            referrer: None,
+        };
+        execute(deps, env, mock_info(buyer, &coins(1_000_000, &denom)), msg)
+    };
+    let volume = |deps: Deps| -> Uint128 {
+        let msg = QueryMsg::GetCollectionVolume {
+            address: "collection".to_string(),
+        };
+        let res: CollectionVolumeResponse =
+            from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap();
+        res.total_volume
+    };
+
+    list(deps.as_mut(), mock_env(), "alice", "token1").unwrap();
+    buy(deps.as_mut(), mock_env(), "bob", 1).unwrap();
+    assert_eq!(Uint128::new(1_000_000), volume(deps.as_ref()));
+
+    // bob can't relist what he just bought, even while wash trades are only flagged
+    let err = list(deps.as_mut(), mock_env(), "bob", "token1").unwrap_err();
+    assert!(matches!(err, ContractError::HoldingPeriod { .. }));
+
+    // selling alice another token is left out of the volume
+    list(deps.as_mut(), mock_env(), "bob", "token2").unwrap();
+    let res = buy(deps.as_mut(), mock_env(), "alice", 2).unwrap();
+    assert!(res.events[0]
+        .attributes
+        .contains(&attr("wash_trade", "ping_pong")));
+    assert_eq!(Uint128::new(1_000_000), volume(deps.as_ref()));
+
+    // rejecting them, they can't trade again within the window
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&dao_address, &[]),
+        update(true),
+    )
+    .unwrap();
+    let attrs = &res.events[0].attributes;
+    assert!(attrs.contains(&attr("old_reject", "false")));
+    assert!(attrs.contains(&attr("new_reject", "true")));
+    assert!(attrs.contains(&attr("old_holding_period", "3600")));
+    list(deps.as_mut(), mock_env(), "bob", "token3").unwrap();
+    let msg = QueryMsg::SimulateBuy {
+        offering_id: OfferingId(3),
+        buyer: "alice".to_string(),
+        referrer: None,
+    };
+    let res: SimulateBuyResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(Some(PurchaseFailure::PingPong), res.failure);
+    let err = buy(deps.as_mut(), mock_env(), "alice", 3).unwrap_err();
+    assert!(matches!(err, ContractError::WashTrade { .. }));
+
+    // once the window passed it is a normal sale
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(24 * 60 * 60);
+    buy(deps.as_mut(), env.clone(), "alice", 3).unwrap();
+    assert_eq!(Uint128::new(2_000_000), volume(deps.as_ref()));
+    // & bob's holding period is over
+    list(deps.as_mut(), env, "bob", "token1").unwrap();
+}
+
+#[test]
+fn test_refund_wash_trade() {
+    let mut deps = mock_deps();
+    let (denom, dao_address, _) = initialize_contract(deps.as_mut());
+    let msg = ExecuteMsg::UpdateWashTrading {
+        holding_period: 60 * 60,
+        pair_window: 24 * 60 * 60,
+        reject: false,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(&dao_address, &[]), msg).unwrap();
+
+    for token_id in ["token1", "token2"] {
+        let info = mock_info("collection", &[]);
+        receive_nft(deps.as_mut(), info, 1_000_000, token_id.to_string()).unwrap();
+    }
+    buy_nft(
+        deps.as_mut(),
+        mock_info("bob", &coins(1_000_000, &denom)),
+        OfferingId(1),
+    )
+    .unwrap();
+    contract::reply(deps.as_mut(), mock_env(), transfer_ok(1)).unwrap();
+
+    // the second trade between them is flagged, then gets stuck & refunded
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(60);
+    let msg = ExecuteMsg::BuyNft {
+        offering_id: OfferingId(2),
+        recipient: None,
+        referrer: None,
+    };
+    let res = execute(
+        deps.as_mut(),
+        env,
+        mock_info("bob", &coins(1_000_000, &denom)),
+        msg,
+    )
+    .unwrap();
+    assert!(res.events[0]
+        .attributes
+        .contains(&attr("wash_trade", "ping_pong")));
+    contract::reply(deps.as_mut(), mock_env(), transfer_err(2)).unwrap();
+    let msg = ExecuteMsg::ClaimRefund {
+        offering_id: OfferingId(2),
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), msg).unwrap();
+
+    // the volume of the first sale stays & the refunded one is forgotten
+    let msg = QueryMsg::GetCollectionVolume {
+        address: "collection".to_string(),
+    };
+    let res: CollectionVolumeResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(Uint128::new(1_000_000), res.total_volume);
+    let collection = Addr::unchecked("collection");
+    let storage = deps.as_ref().storage;
+    assert!(LAST_SALES
+        .may_load(storage, (&collection, "token2"))
+        .unwrap()
+        .is_none());
+    assert!(LAST_SALES
+        .may_load(storage, (&collection, "token1"))
+        .unwrap()
+        .is_some());
+    let (bob, seller) = (Addr::unchecked("bob"), Addr::unchecked("seller"));
+    assert!(PAIR_TRADES
+        .may_load(storage, (&bob, &seller))
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_listing_deposit_and_cap() {
+    let mut deps = mock_deps();
+    let (denom, dao_address, _) = initialize_contract(deps.as_mut());
+
+    let msg = ExecuteMsg::UpdateListingLimits {
+        deposit: Uint128::new(100_000),
+        max_per_seller: 2,
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(&dao_address, &[]), msg).unwrap();
+    let attrs = &res.events[0].attributes;
+    assert!(attrs.contains(&attr("old_deposit", "0")));
+    assert!(attrs.contains(&attr("new_deposit", "100000")));
+    assert!(attrs.contains(&attr("new_max_per_seller", "2")));
+
+    let list = |deps: DepsMut, token_id: &str, expiration: Expiration| {
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: "seller".to_string(),
+            token_id: token_id.to_string(),
+            msg: to_binary(&SellNft {
+                list_price: Uint128::new(1_000_000),
+                expiration,
+                usd_price: None,
+            })
+            .unwrap(),
+        });
+        execute(deps, mock_env(), mock_info("collection", &[]), msg)
+    };
+    let prepay = |deps: DepsMut, amount: u128| {
+        let info = mock_info("seller", &coins(amount, &denom));
+        execute(deps, mock_env(), info, ExecuteMsg::PrepayDeposit {})
+    };
+    let deposit_to = |recipient: &str, amount: u128| {
+        SubMsg::new(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: coins(amount, &denom),
+        })
+    };
+
+    // SendNft can't carry the deposit, it has to be paid ahead
+    let err = list(deps.as_mut(), "token1", Expiration::Never {}).unwrap_err();
+    assert!(matches!(err, ContractError::ListingDepositRequired { .. }));
+    prepay(deps.as_mut(), 250_000).unwrap();
+
+    let expires = Expiration::AtHeight(mock_env().block.height + 10);
+    list(deps.as_mut(), "token1", Expiration::Never {}).unwrap();
+    list(deps.as_mut(), "token2", expires).unwrap();
+    let err = list(deps.as_mut(), "token3", Expiration::Never {}).unwrap_err();
+    assert!(matches!(err, ContractError::TooManyListings { max: 2 }));
+
+    let msg = QueryMsg::GetPrepaidDeposit {
+        address: "seller".to_string(),
+    };
+    let res: PrepaidDepositResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(coin(50_000, &denom), res.amount);
+
+    // withdrawing returns the deposit
+    let msg = ExecuteMsg::WithdrawNft {
+        offering_id: OfferingId(1),
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), msg).unwrap();
+    assert!(res.messages.contains(&deposit_to("seller", 100_000)));
+
+    // anyone can reclaim an expired offering, its deposit then goes to the fee receiver
+    let reclaim = |deps: DepsMut, env: Env| {
+        let msg = ExecuteMsg::ReclaimExpired {
+            offering_id: OfferingId(2),
+        };
+        execute(deps, env, mock_info("cleaner", &[]), msg)
+    };
+    let err = reclaim(deps.as_mut(), mock_env()).unwrap_err();
+    assert!(matches!(err, ContractError::OfferingNotExpired { .. }));
+    let mut env = mock_env();
+    env.block.height += 10;
+    let res = reclaim(deps.as_mut(), env).unwrap();
+    // the deposit & the NFT back to the seller
+    assert_eq!(2, res.messages.len());
+    assert!(res.messages.contains(&deposit_to(&dao_address, 100_000)));
+    assert_eq!("craft_marketplace.reclaim", res.events[0].ty,);
+
+    // a sale refunds it to the seller
+    prepay(deps.as_mut(), 130_000).unwrap();
+    list(deps.as_mut(), "token3", Expiration::Never {}).unwrap();
+    let msg = ExecuteMsg::BuyNft {
+        offering_id: OfferingId(3),
+        recipient: None,
+        referrer: None,
+    };
+    let info = mock_info("buyer", &coins(1_000_000, &denom));
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert!(res.messages.contains(&deposit_to("seller", 100_000)));
+
+    // approval listings pay the deposit with List
+    mock_approvals(&mut deps, "seller", true, false);
+    let err = list_nft(deps.as_mut(), "seller", "token4").unwrap_err();
+    assert!(matches!(err, ContractError::InsufficientFundsSend { .. }));
+    let msg = ExecuteMsg::List {
+        collection: "collection".to_string(),
+        token_id: "token4".to_string(),
+        price: Uint128::new(1_000_000),
+    };
+    let info = mock_info("seller", &coins(120_000, &denom));
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(vec![deposit_to("seller", 20_000)], res.messages);
+
+    // the prepaid deposits which are left can be withdrawn
+    let withdraw = |deps: DepsMut| {
+        let msg = ExecuteMsg::WithdrawPrepaidDeposit {};
+        execute(deps, mock_env(), mock_info("seller", &[]), msg)
+    };
+    let res = withdraw(deps.as_mut()).unwrap();
+    assert_eq!(vec![deposit_to("seller", 80_000)], res.messages);
+    let err = withdraw(deps.as_mut()).unwrap_err();
+    assert!(matches!(err, ContractError::NoPrepaidDeposit {}));
+}
+
+#[test]
+fn test_marketplace_events() {
+    let mut deps = mock_deps();
+    let (denom, fee_receiver, _) = initialize_contract(deps.as_mut());
+
+    sell_nft(
+        deps.as_mut(),
+        mock_info("collection", &[]),
+        String::from("token1"),
+        1_000_000,
+    );
+
+    let info = mock_info("buyer", &coins(1_000_000, &denom));
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::BuyNft {
+            offering_id: OfferingId(1),
+            recipient: None,
+            referrer: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(1, res.events.len());
+    assert_eq!("craft_marketplace.buy", res.events[0].ty);
+    assert_eq!(
+        res.events[0].attributes,
+        vec![
+            attr("schema_version", EVENT_SCHEMA_VERSION),
+            attr("offering_id", "1"),
+            attr("collection", "collection"),
+            attr("token_id", "token1"),
+            attr("seller", "collection"),
+            attr("buyer", "buyer"),
+            attr("recipient", "buyer"),
+            attr("amount", "1000000"),
+            attr("denom", DENOM),
+            attr("platform_fee", "50000"),
+            attr("seller_amount", "950000"),
+            attr("fee_discount_bps", "0"),
+        ]
+    );
+
+    // config changes carry the old & new value
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&fee_receiver, &[]),
+        ExecuteMsg::UpdatePlatformFee { new_fee: 7 },
+    )
+    .unwrap();
+    assert_eq!(vec![attr("action", "update_platform_fee")], res.attributes);
+    assert_eq!("craft_marketplace.update_platform_fee", res.events[0].ty);
+    assert_eq!(
+        res.events[0].attributes[1..],
+        [attr("old_fee", "5"), attr("new_fee", "7")]
+    );
+}
+
+#[test]
+fn test_rejected_purchase_retry_and_refund() {
+    let mut deps = mock_deps();
+    let (denom, _, _) = initialize_contract(deps.as_mut());
+
+    let (c_addr, _) = sell_nft(
+        deps.as_mut(),
+        mock_info("seller", &[]),
+        String::from("token1"),
+        1_000_000,
+    );
+    buy_nft(
+        deps.as_mut(),
+        mock_info("buyer", &coins(1_000_000, &denom)),
+        OfferingId(1),
+    )
+    .unwrap();
+
+    // the collection rejects the transfer, nothing is paid out & the item is stuck
+    let res = contract::reply(deps.as_mut(), mock_env(), transfer_err(1)).unwrap();
+    assert_eq!(0, res.messages.len());
+    let stuck = get_stuck_transfers(deps.as_ref(), Some("buyer"));
+    assert_eq!(1, stuck.transfers.len());
+    assert_eq!(TransferKind::Purchase, stuck.transfers[0].kind);
+    assert_eq!("collection is frozen", stuck.transfers[0].error);
+
+    // a random address can not retry it
+    let retry = ExecuteMsg::RetryTransfer {
+        offering_id: OfferingId(1),
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("random", &[]),
+        retry.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized { .. }));
+
+    // the seller retries, it fails again
+    let res = execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), retry).unwrap();
+    assert_eq!(1, res.messages.len());
+    assert_eq!(0, get_stuck_transfers(deps.as_ref(), None).transfers.len());
+    contract::reply(deps.as_mut(), mock_env(), transfer_err(1)).unwrap();
+
+    // only the buyer can take the refund
+    let refund = ExecuteMsg::ClaimRefund {
+        offering_id: OfferingId(1),
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("seller", &[]),
+        refund.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized { .. }));
+
+    let res = execute(deps.as_mut(), mock_env(), mock_info("buyer", &[]), refund).unwrap();
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(BankMsg::Send {
+            to_address: "buyer".to_string(),
+            amount: coins(1_000_000, &denom),
+        })
+    );
+    // & the NFT heads back to the seller
+    assert_eq!(2, res.messages.len());
+    assert_eq!(0, get_stuck_transfers(deps.as_ref(), None).transfers.len());
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetCollectionVolume { address: c_addr },
+    )
+    .unwrap();
+    let value: CollectionVolumeResponse = from_binary(&res).unwrap();
+    assert_eq!(Uint128::zero(), value.total_volume);
+}
+
+#[test]
+fn test_gift_purchase() {
+    let mut deps = mock_deps();
+    let (denom, _, _) = initialize_contract(deps.as_mut());
+    sell_nft(
+        deps.as_mut(),
+        mock_info("seller", &[]),
+        String::from("token1"),
+        1_000_000,
+    );
+
+    let gift = |recipient: &str| ExecuteMsg::BuyNft {
+        offering_id: OfferingId(1),
+        recipient: Some(recipient.to_string()),
+        referrer: None,
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("parent", &coins(1_000_000, &denom)),
+        gift(""),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Std(_)));
+
+    // the parent pays, the player gets the NFT
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("parent", &coins(1_000_000, &denom)),
+        gift("player"),
+    )
+    .unwrap();
+    match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => {
+            let transfer: Cw721ExecuteMsg = from_binary(msg).unwrap();
+            assert_eq!(
+                transfer,
+                Cw721ExecuteMsg::TransferNft {
+                    recipient: "player".to_string(),
+                    token_id: "token1".to_string(),
+                }
+            );
+        }
+        msg => panic!("Unexpected message: {:?}", msg),
+    }
+    let event = res
+        .events
+        .iter()
+        .find(|e| e.ty == "craft_marketplace.buy")
+        .unwrap();
+    assert!(event.attributes.contains(&attr("buyer", "parent")));
+    assert!(event.attributes.contains(&attr("recipient", "player")));
+
+    // a rejected gift is refunded to whoever paid for it
+    contract::reply(deps.as_mut(), mock_env(), transfer_err(1)).unwrap();
+    let stuck = get_stuck_transfers(deps.as_ref(), Some("parent"));
+    assert_eq!(Some(Addr::unchecked("parent")), stuck.transfers[0].buyer);
+    let refund = ExecuteMsg::ClaimRefund {
+        offering_id: OfferingId(1),
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("player", &[]),
+        refund.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized { .. }));
+    let res = execute(deps.as_mut(), mock_env(), mock_info("parent", &[]), refund).unwrap();
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(BankMsg::Send {
+            to_address: "parent".to_string(),
+            amount: coins(1_000_000, &denom),
+        })
+    );
+}
+
+#[test]
+fn test_approval_listing() {
+    let mut deps = mock_deps();
+    let (denom, _, _) = initialize_contract(deps.as_mut());
+
+    mock_approvals(&mut deps, "seller", false, false);
+    let err = list_nft(deps.as_mut(), "seller", "house").unwrap_err();
+    assert!(matches!(err, ContractError::MarketplaceNotApproved { .. }));
+    mock_approvals(&mut deps, "someone", true, false);
+    let err = list_nft(deps.as_mut(), "seller", "house").unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized { .. }));
+
+    // the NFT stays with the seller, only the approval is needed
+    mock_approvals(&mut deps, "seller", true, false);
+    // without a listing deposit there is nothing to pay
+    let msg = ExecuteMsg::List {
+        collection: "collection".to_string(),
+        token_id: "house".to_string(),
+        price: Uint128::new(1_000_000),
+    };
+    let info = mock_info("seller", &coins(100_000, &denom));
+    let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert!(matches!(
+        err,
+        ContractError::Payment(PaymentError::NonPayable {})
+    ));
+    let res = list_nft(deps.as_mut(), "seller", "house").unwrap();
+    assert!(res.messages.is_empty());
+    assert!(res.events[0]
+        .attributes
+        .contains(&attr("non_custodial", "true")));
+    let offerings = get_offerings(deps.as_ref()).offerings;
+    assert_eq!(1, offerings.len());
+    assert!(offerings[0].non_custodial);
+    let err = list_nft(deps.as_mut(), "seller", "house").unwrap_err();
+    assert!(matches!(err, ContractError::AlreadyListed { .. }));
+
+    // withdrawing has nothing to send back
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("seller", &[]),
+        ExecuteMsg::WithdrawNft {
+            offering_id: OfferingId(1),
+        },
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+
+    // buying moves the token straight from the seller to the buyer
+    list_nft(deps.as_mut(), "seller", "house").unwrap();
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("buyer", &coins(1_000_000, &denom)),
+        ExecuteMsg::BuyNft {
+            offering_id: OfferingId(2),
+            recipient: None,
+            referrer: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "collection".to_string(),
+            msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: "buyer".to_string(),
+                token_id: "house".to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    );
+    // & the token can be listed again by its new owner
+    contract::reply(deps.as_mut(), mock_env(), transfer_ok(2)).unwrap();
+    mock_approvals(&mut deps, "buyer", true, false);
+    list_nft(deps.as_mut(), "buyer", "house").unwrap();
+}
+
+#[test]
+fn test_stale_approval_listings_are_pruned() {
+    let mut deps = mock_deps();
+    let (denom, _, _) = initialize_contract(deps.as_mut());
+
+    // approved for every token of the seller with ApproveAll
+    mock_approvals(&mut deps, "seller", false, true);
+    list_nft(deps.as_mut(), "seller", "house").unwrap();
+    list_nft(deps.as_mut(), "seller", "castle").unwrap();
+
+    // the seller revokes the approval
+    mock_approvals(&mut deps, "seller", false, false);
+    let res: SimulateBuyResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::SimulateBuy {
+                offering_id: OfferingId(1),
+                buyer: "buyer".to_string(),
+                referrer: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(Some(PurchaseFailure::NotApproved), res.failure);
+    let err = buy_nft(
+        deps.as_mut(),
+        mock_info("buyer", &coins(1_000_000, &denom)),
+        OfferingId(1),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::StaleListing { .. }));
+
+    let stale: StaleListingsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetStaleListings {
+                start_after: None,
+                limit: Some(1),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        vec![StaleListing {
+            offering_id: OfferingId(1),
+            reason: PurchaseFailure::NotApproved,
+        }],
+        stale.listings
+    );
+    assert_eq!(Some(OfferingId(1)), stale.next_start_after);
+
+    // anyone can prune them
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::PruneListings {
+            limit: None,
+            start_after: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(2, res.events.len());
+    assert_eq!("craft_marketplace.prune", res.events[0].ty);
+    assert!(res.events[0]
+        .attributes
+        .contains(&attr("reason", "not_approved")));
+    assert!(get_offerings(deps.as_ref()).offerings.is_empty());
+
+    // a listing of a previous owner is replaced when the new owner lists the token
+    mock_approvals(&mut deps, "seller", true, false);
+    list_nft(deps.as_mut(), "seller", "house").unwrap();
+    mock_approvals(&mut deps, "new_owner", true, false);
+    let res = list_nft(deps.as_mut(), "new_owner", "house").unwrap();
+    assert_eq!("craft_marketplace.prune", res.events[0].ty);
+    assert!(res.events[0]
+        .attributes
+        .contains(&attr("reason", "owner_changed")));
+    let offerings = get_offerings(deps.as_ref()).offerings;
+    assert_eq!(1, offerings.len());
+    assert_eq!("new_owner", offerings[0].seller);
+}
+
+#[test]
+fn test_blocklist() {
+    let mut deps = mock_deps();
+    let (denom, fee_receiver, _) = initialize_contract(deps.as_mut());
+    receive_nft(
+        deps.as_mut(),
+        mock_info("collection", &[]),
+        1_000_000,
+        "token1".to_string(),
+    )
+    .unwrap();
+
+    let block = ExecuteMsg::Block {
+        address: "seller".to_string(),
+        reason: Some("exploit".to_string()),
+        expires: Expiration::Never {},
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("seller", &[]),
+        block.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized { .. }));
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&fee_receiver, &[]),
+        block,
+    )
+    .unwrap();
+    assert_eq!("craft_marketplace.block", res.events[0].ty);
+
+    let res: BlockedResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::IsBlocked {
+                address: "seller".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert!(res.blocked);
+    assert_eq!(Some("exploit".to_string()), res.entry.unwrap().reason);
+
+    // the seller can not list anymore & their listing can not be bought
+    let err = receive_nft(
+        deps.as_mut(),
+        mock_info("collection", &[]),
+        1_000_000,
+        "token2".to_string(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Blocked { .. }));
+    let err = buy_nft(
+        deps.as_mut(),
+        mock_info("buyer", &coins(1_000_000, &denom)),
+        OfferingId(1),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Blocked { .. }));
+    let res: SimulateBuyResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::SimulateBuy {
+                offering_id: OfferingId(1),
+                buyer: "buyer".to_string(),
+                referrer: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(Some(PurchaseFailure::Blocked), res.failure);
+
+    // but they can always take their NFT back
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("seller", &[]),
+        ExecuteMsg::WithdrawNft {
+            offering_id: OfferingId(1),
+        },
+    )
+    .unwrap();
+    assert_eq!(1, res.messages.len());
+
+    // unblocked, the seller can list again
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&fee_receiver, &[]),
+        ExecuteMsg::Unblock {
+            address: "seller".to_string(),
+        },
+    )
+    .unwrap();
+
+    // a block with an expiry lifts by itself
+    let mut env = mock_env();
+    let block = ExecuteMsg::Block {
+        address: "buyer".to_string(),
+        reason: None,
+        expires: Expiration::AtHeight(env.block.height + 10),
+    };
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info(&fee_receiver, &[]),
+        block,
+    )
+    .unwrap();
+    receive_nft(
+        deps.as_mut(),
+        mock_info("collection", &[]),
+        1_000_000,
+        "token3".to_string(),
+    )
+    .unwrap();
+    let err = buy_nft(
+        deps.as_mut(),
+        mock_info("buyer", &coins(1_000_000, &denom)),
+        OfferingId(2),
+    );
+    assert!(matches!(err, Err(ContractError::Blocked { .. })));
+
+    let res: BlocklistResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetBlocklist {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(1, res.entries.len());
+    assert_eq!(Addr::unchecked("buyer"), res.entries[0].address);
+
+    // the buyer can buy once their block expired
+    env.block.height += 10;
+    execute(
+        deps.as_mut(),
+        env,
+        mock_info("buyer", &coins(1_000_000, &denom)),
+        ExecuteMsg::BuyNft {
+            offering_id: OfferingId(2),
+            recipient: None,
+            referrer: None,
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_expired_offering_can_not_be_bought() {
+    let mut deps = mock_deps();
+    let (denom, _, _) = initialize_contract(deps.as_mut());
+
+    let env = mock_env();
+    let sell_msg = SellNft {
+        list_price: Uint128::new(1_000_000),
+        expiration: Expiration::AtHeight(env.block.height + 10),
+        usd_price: None,
+    };
+    let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+        sender: String::from("seller"),
+        token_id: String::from("token1"),
+        msg: to_binary(&sell_msg).unwrap(),
+    });
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("collection", &[]),
+        msg,
+    )
+    .unwrap();
+
+    let mut env = env;
+    env.block.height += 10;
+    let err = execute(
+        deps.as_mut(),
+        env,
+        mock_info("buyer", &coins(1_000_000, &denom)),
+        ExecuteMsg::BuyNft {
+            offering_id: OfferingId(1),
+            recipient: None,
+            referrer: None,
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::OfferingExpired { .. }));
+
+    // the seller can still take it back
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("seller", &[]),
+        ExecuteMsg::WithdrawNft {
+            offering_id: OfferingId(1),
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_migrate_from_v0_3() {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct OldOffering {
+        token_id: String,
+        list_denom: String,
+        contract_addr: Addr,
+        seller: Addr,
+        list_price: Uint128,
+    }
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct OldContractInfo {
+        name: String,
+        denom: String,
+        fee_receive_address: String,
+        platform_fee: u128,
+        version: String,
+        contact: String,
+    }
+
+    let mut deps = mock_deps();
+    cw2::set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.3.6").unwrap();
+    Item::new("marketplace_info")
+        .save(
+            deps.as_mut().storage,
+            &OldContractInfo {
+                name: MP_NAME.to_string(),
+                denom: DENOM.to_string(),
+                fee_receive_address: "craftdaoaddr".to_string(),
+                platform_fee: 5,
+                version: "0.3.6".to_string(),
+                contact: "reece@crafteconomy.io".to_string(),
+            },
+        )
+        .unwrap();
+    // v0.3 offerings were saved without the indexes
+    for id in ["1", "2"] {
+        Map::new("offerings")
+            .save(
+                deps.as_mut().storage,
+                id,
+                &OldOffering {
+                    token_id: format!("token{}", id),
+                    list_denom: DENOM.to_string(),
+                    contract_addr: Addr::unchecked("collection"),
+                    seller: Addr::unchecked("seller"),
+                    list_price: Uint128::new(1_000_000),
+                },
+            )
+            .unwrap();
+    }
+
+    let msg = MigrateMsg {
+        admin: Some("new_admin".to_string()),
+        fee_receive_address: None,
+        platform_fee: Some(3),
+    };
+    let res = contract::migrate(deps.as_mut(), mock_env(), msg).unwrap();
+    assert!(res.attributes.contains(&attr("applied", "0.4.0,0.5.0")));
+
+    let info: ContractInfoResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetContractInfo {}).unwrap())
+            .unwrap();
+    assert_eq!("new_admin", info.admin);
+    assert_eq!("craftdaoaddr", info.fee_receive_address);
+    assert_eq!(3, info.platform_fee);
+    assert_eq!(env!("CARGO_PKG_VERSION"), info.version);
+
+    let value = get_offerings(deps.as_ref());
+    assert_eq!(2, value.offerings.len());
+    assert_eq!(Expiration::Never {}, value.offerings[0].expiration);
+
+    // the offerings are indexed now
+    let by_seller = offerings()
+        .idx
+        .seller
+        .prefix(Addr::unchecked("seller"))
+        .keys(deps.as_ref().storage, None, None, Order::Ascending)
+        .count();
+    assert_eq!(2, by_seller);
+}
+
+#[test]
+fn test_migrate_to_integer_offering_ids() {
+    let mut deps = mock_deps();
+    initialize_contract(deps.as_mut());
+    cw2::set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.4.0").unwrap();
+    v0_4::CONTRACT_INFO
+        .save(
+            deps.as_mut().storage,
+            &v0_4::ContractInfoResponse {
+                name: MP_NAME.to_string(),
+                denom: DENOM.to_string(),
+                fee_receive_address: "craftdaoaddr".to_string(),
+                platform_fee: 5,
+                version: "0.4.0".to_string(),
+                contact: "reece@crafteconomy.io".to_string(),
+                admin: "admin".to_string(),
+                pull_payments: false,
+                verify_collections: false,
+                max_price_age: DEFAULT_MAX_PRICE_AGE,
+                featured_slots: DEFAULT_FEATURED_SLOTS,
+                referral_fee_bps: 0,
+                listing_deposit: Uint128::zero(),
+                max_listings_per_seller: 0,
+            },
+        )
+        .unwrap();
+
+    // v0.4 stored the ids as strings, so "10" & "11" sorted before "2"
+    let collection = Addr::unchecked("collection");
+    for id in 1..=11u64 {
+        let off = v0_4::Offering {
+            token_id: format!("token{}", id),
+            list_denom: DENOM.to_string(),
+            contract_addr: collection.clone(),
+            seller: Addr::unchecked("seller"),
+            list_price: Uint128::new(1_000_000),
+            expiration: Expiration::Never {},
+            usd_price: None,
+            non_custodial: id == 11,
+            deposit: Uint128::zero(),
+        };
+        v0_4::offerings()
+            .save(deps.as_mut().storage, &id.to_string(), &off)
+            .unwrap();
+    }
+    let metadata = NftMetadata {
+        name: Some("ten".to_string()),
+        ..NftMetadata::default()
+    };
+    v0_4::OFFERING_METADATA
+        .save(deps.as_mut().storage, "10", &metadata)
+        .unwrap();
+    let promotion = Promotion {
+        seller: Addr::unchecked("seller"),
+        bid: coin(100, DENOM),
+        expires: mock_env().block.time.plus_seconds(60),
+    };
+    v0_4::PROMOTIONS
+        .save(deps.as_mut().storage, (&collection, "2"), &promotion)
+        .unwrap();
+    v0_4::APPROVAL_LISTINGS
+        .save(
+            deps.as_mut().storage,
+            (&collection, "token11"),
+            &"11".to_string(),
+        )
+        .unwrap();
+
+    let msg = MigrateMsg {
+        admin: None,
+        fee_receive_address: None,
+        platform_fee: None,
+    };
+    let res = contract::migrate(deps.as_mut(), mock_env(), msg).unwrap();
+    assert!(res.attributes.contains(&attr("applied", "0.5.0")));
+
+    // the settings added since are at their defaults
+    let info: ContractInfoResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetContractInfo {}).unwrap())
+            .unwrap();
+    assert_eq!("admin", info.admin);
+    assert_eq!(DEFAULT_FEATURED_SLOTS, info.featured_slots);
+    assert_eq!(DEFAULT_MAX_PRICE_AGE, info.max_price_age);
+    assert!(!info.pull_payments);
+
+    let ids: Vec<OfferingId> = get_offerings(deps.as_ref())
+        .offerings
+        .into_iter()
+        .map(|off| off.offering_id)
+        .collect();
+    assert_eq!((1..=11).map(OfferingId).collect::<Vec<_>>(), ids);
+    // the old index entries are gone
+    let by_seller: Vec<u64> = offerings()
+        .idx
+        .seller
+        .prefix(Addr::unchecked("seller"))
+        .keys(deps.as_ref().storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()
+        .unwrap();
+    assert_eq!((1..=11).collect::<Vec<_>>(), by_seller);
+    // and the sorted indexes are built
+    let by_price: Vec<u64> = offerings()
+        .idx
+        .price
+        .sub_prefix(collection.clone())
+        .keys(deps.as_ref().storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()
+        .unwrap();
+    assert_eq!((1..=11).collect::<Vec<_>>(), by_price);
+
+    let metadata = OFFERING_METADATA.load(deps.as_ref().storage, 10).unwrap();
+    assert_eq!(Some("ten".to_string()), metadata.name);
+    let msg = QueryMsg::GetFeatured { collection: None };
+    let res: FeaturedResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(OfferingId(2), res.featured[0].offering.offering_id);
+    let listing = APPROVAL_LISTINGS
+        .load(deps.as_ref().storage, (&collection, "token11"))
+        .unwrap();
+    assert_eq!(11, listing);
+
+    // messages take the ids as strings or numbers, responses keep returning strings
+    for msg in [
+        br#"{"withdraw_nft":{"offering_id":"11"}}"#.as_ref(),
+        br#"{"withdraw_nft":{"offering_id":11}}"#.as_ref(),
+    ] {
+        let msg: ExecuteMsg = cosmwasm_std::from_slice(msg).unwrap();
+        assert_eq!(
+            ExecuteMsg::WithdrawNft {
+                offering_id: OfferingId(11)
+            },
+            msg
+        );
+    }
+    assert_eq!(b"\"11\"".to_vec(), to_binary(&OfferingId(11)).unwrap().0);
+}
+
+#[test]
+fn test_migrate_v0_4_approval_listing() {
+    let mut deps = mock_deps();
+    initialize_contract(deps.as_mut());
+    cw2::set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.4.0").unwrap();
+    // a late 0.4.x config, with listing deposits & USD prices
+    let config = br#"{"name":"craft","denom":"ucraft","fee_receive_address":"craftdaoaddr",
+        "platform_fee":"5","version":"0.4.0","contact":"","admin":"admin","pull_payments":true,
+        "max_price_age":60,"listing_deposit":"100000","max_listings_per_seller":2}"#;
+    cosmwasm_std::Storage::set(&mut deps.storage, b"marketplace_info", config);
+
+    // an approval listing which holds a deposit
+    let collection = Addr::unchecked("collection");
+    let off = v0_4::Offering {
+        token_id: "token1".to_string(),
+        list_denom: DENOM.to_string(),
+        contract_addr: collection.clone(),
+        seller: Addr::unchecked("seller"),
+        list_price: Uint128::new(1_000_000),
+        expiration: Expiration::Never {},
+        usd_price: Some(Decimal::percent(250)),
+        non_custodial: true,
+        deposit: Uint128::new(100_000),
+    };
+    v0_4::offerings()
+        .save(deps.as_mut().storage, "1", &off)
+        .unwrap();
+    v0_4::APPROVAL_LISTINGS
+        .save(
+            deps.as_mut().storage,
+            (&collection, "token1"),
+            &"1".to_string(),
+        )
+        .unwrap();
+
+    let msg = MigrateMsg {
+        admin: None,
+        fee_receive_address: None,
+        platform_fee: None,
+    };
+    contract::migrate(deps.as_mut(), mock_env(), msg).unwrap();
+
+    // the listing stays an approval listing with its deposit & USD price
+    let migrated = offerings().load(deps.as_ref().storage, 1).unwrap();
+    assert!(migrated.non_custodial);
+    assert_eq!(Uint128::new(100_000), migrated.deposit);
+    assert_eq!(Some(Decimal::percent(250)), migrated.usd_price);
+    let listing = APPROVAL_LISTINGS
+        .load(deps.as_ref().storage, (&collection, "token1"))
+        .unwrap();
+    assert_eq!(1, listing);
+
+    // so do the settings, the ones it didn't have yet are at their defaults
+    let info: ContractInfoResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetContractInfo {}).unwrap())
+            .unwrap();
+    assert!(info.pull_payments);
+    assert_eq!(60, info.max_price_age);
+    assert_eq!(Uint128::new(100_000), info.listing_deposit);
+    assert_eq!(2, info.max_listings_per_seller);
+    assert_eq!(DEFAULT_FEATURED_SLOTS, info.featured_slots);
+    assert!(!info.verify_collections);
+}
+
+#[test]
+fn test_migrate_compares_semver() {
+    let mut deps = mock_deps();
+    initialize_contract(deps.as_mut());
+
+    // "0.10.0" < "0.4.0" as strings, but it is the newer version
+    cw2::set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.10.0").unwrap();
+    let msg = MigrateMsg {
+        admin: None,
+        fee_receive_address: None,
+        platform_fee: None,
+    };
+    let err = contract::migrate(deps.as_mut(), mock_env(), msg).unwrap_err();
+    assert!(matches!(err, ContractError::CannotMigrate { .. }));
+}
+
+#[test]
+fn test_withdraw_offering() {
+    // TODO: Cleanup
+    let mut deps = mock_deps();
+
+    let msg = InitMsg {
+        name: String::from("test market"),
+        denom: String::from("ucraft"),
+        fee_receive_address: String::from("craftdaoaddr"),
+        platform_fee: 5,
+        admin: None,
+    };
+    let denom = msg.denom.clone();
+
+    let info = mock_info("creator", &coins(1_000_000, &denom));
+    let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // beneficiary can release it
+    let info = mock_info("anyone", &coins(1_000_000, &denom));
+    receive_nft(deps.as_mut(), info, 1_000_000, String::from("token_id")).unwrap();
+
+    // Offering should be listed
+    let value = get_offerings(deps.as_ref());
+    assert_eq!(1, value.offerings.len());
+
+    // withdraw offering
+    let withdraw_info = mock_info("seller", &coins(2, &denom));
+    let withdraw_msg = ExecuteMsg::WithdrawNft {
+        offering_id: value.offerings[0].offering_id,
+    };
+    let _res = execute(deps.as_mut(), mock_env(), withdraw_info, withdraw_msg).unwrap();
+
+    // Offering should be removed
+    let value = get_offerings(deps.as_ref());
+    assert_eq!(0, value.offerings.len());
+}
+
+#[test]
+fn test_update_offering_price() {
+    let mut deps = mock_deps();
+
+    let (denom, _, _) = initialize_contract(deps.as_mut());
+
+    let amount: u128 = 1_000_000; // list price & buy price
+    let info = mock_info("anyone", &coins(0, &denom));
+    sell_nft(deps.as_mut(), info.clone(), String::from("token1"), amount);
+
+    // Offering should be listed = length of 1
+    let value: OfferingsResponse = get_offerings(deps.as_ref());
+    assert_eq!(1, value.offerings.len());
+
+    // get the first offering
+    let offering_id = value.offerings[0].offering_id;
+
+    // update the price of the offering
+    let new_amount: Uint128 = Uint128::from(9_999_999_u128);
+    let update_msg = ExecuteMsg::UpdateListingPrice {
+        offering_id,
+        new_price: new_amount,
+    };
+
+    let res = contract::execute(deps.as_mut(), mock_env(), info, update_msg.clone());
+    match res {
+        Ok(_) => {}
+        Err(e) => panic!("should have succeeded: {:?}", e),
+    }
+
+    // confirm the attribute key of old_price is updated to new_price
+    let value: OfferingsResponse = get_offerings(deps.as_ref());
+    assert_eq!(new_amount, value.offerings[0].list_price);
+
+    // try to update the price of the offering, but with the incorrect sender
+    let info = mock_info("wrong_person", &coins(0, &denom));
+    let res = contract::execute(deps.as_mut(), mock_env(), info, update_msg);
+    match res {
+        Ok(_) => panic!("should have failed"),
+        Err(e) => match e {
+            ContractError::Unauthorized { msg: _ } => {}
+            _ => panic!("should have failed with InvalidSender"),
+        },
+    }
+}
+
+#[test]
+fn test_query_selective_seller_offerings_only() {
+    // test_sell_offering
+    let mut deps = mock_deps();
+
+    let (denom, _dao_address, _tax_rate) = initialize_contract(deps.as_mut());
+
+    let amount = 1_000_000u128; // list price & buy price
+    let info_seller = mock_info("seller", &coins(0, &denom));
+    sell_nft(
+        deps.as_mut(),
+        info_seller.clone(),
+        String::from("token1"),
+        amount,
+    );
+    sell_nft(
+        deps.as_mut(),
+        info_seller.clone(),
+        String::from("token2"),
+        amount,
+    );
+    let random_seller = mock_info("random_person", &coins(0, &denom));
+    sell_nft(
+        deps.as_mut(),
+        random_seller.clone(),
+        String::from("token2"),
+        amount,
+    );
+
+    // Offering should be listed = length of 1
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOfferings { filter_seller: Some("seller".to_string()), include_metadata: None, collection: None, sort: None, start_after: None, limit: None }).unwrap();
+    let value: OfferingsResponse = from_binary(&res).unwrap();
+    assert_eq!(2, value.offerings.len());
+
+    // the pages of a seller come from the seller index
+    let page = |deps: Deps, start_after: Option<OfferingsCursor>| -> OfferingsResponse {
+        let msg = QueryMsg::GetOfferings {
+            filter_seller: Some("seller".to_string()),
+            include_metadata: None,
+            collection: None,
+            sort: Some(OfferingSort::Newest),
+            start_after,
+            limit: Some(1),
+        };
+        from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+    };
+    let first = page(deps.as_ref(), None);
+    assert_eq!(OfferingId(2), first.offerings[0].offering_id);
+    let second = page(deps.as_ref(), first.next_start_after);
+    assert_eq!(OfferingId(1), second.offerings[0].offering_id);
+    assert!(page(deps.as_ref(), second.next_start_after)
+        .offerings
+        .is_empty());
+}
+
+#[test]
+fn test_query_sorted_offerings() {
+    let mut deps = mock_deps();
+    initialize_contract(deps.as_mut());
+
+    // a listing a minute, so offering n is priced at prices[n - 1] & listed n - 1 minutes in
+    let prices = [5u128, 12, 1, 9, 3, 11, 7, 2, 10, 4, 8, 6];
+    for (i, price) in prices.iter().enumerate() {
+        let mut env = mock_env();
+        env.block.height += i as u64;
+        env.block.time = env.block.time.plus_seconds(60 * i as u64);
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: "seller".to_string(),
+            token_id: format!("token{}", i + 1),
+            msg: to_binary(&SellNft {
+                list_price: Uint128::new(price * 1_000_000),
+                expiration: Expiration::Never {},
+                usd_price: None,
+            })
+            .unwrap(),
+        });
+        execute(deps.as_mut(), env, mock_info("collection", &[]), msg).unwrap();
+    }
+    // only shows up when the offerings aren't narrowed to a collection
+    receive_nft(
+        deps.as_mut(),
+        mock_info("other", &[]),
+        1_000_000,
+        "token1".to_string(),
+    )
+    .unwrap();
+
+    let page = |deps: Deps,
+                collection: Option<&str>,
+                sort: OfferingSort,
+                start_after: Option<u64>,
+                limit: u32|
+     -> StdResult<Vec<u64>> {
+        // the cursor the page ending on the `start_after` offering returns
+        let start_after = start_after.map(|id| {
+            let off = offerings().load(deps.storage, id).unwrap();
+            OfferingsCursor {
+                offering_id: OfferingId(id),
+                list_price: off.list_price,
+                listed_at: off.listed_at,
+            }
+        });
+        let msg = QueryMsg::GetOfferings {
+            filter_seller: None,
+            include_metadata: None,
+            collection: collection.map(String::from),
+            sort: Some(sort),
+            start_after,
+            limit: Some(limit),
+        };
+        let res: OfferingsResponse = from_binary(&query(deps, mock_env(), msg)?)?;
+        Ok(res
+            .offerings
+            .into_iter()
+            .map(|off| off.offering_id.0)
+            .collect())
+    };
+    let coll = Some("collection");
+
+    assert_eq!(
+        vec![13, 12, 11],
+        page(deps.as_ref(), None, OfferingSort::Newest, None, 3).unwrap()
+    );
+    assert_eq!(
+        vec![10, 11, 12, 13],
+        page(deps.as_ref(), None, OfferingSort::Oldest, Some(9), 5).unwrap()
+    );
+    assert_eq!(
+        vec![12, 11, 10, 9, 8],
+        page(deps.as_ref(), coll, OfferingSort::Newest, None, 5).unwrap()
+    );
+    assert_eq!(
+        vec![7, 6, 5, 4, 3],
+        page(deps.as_ref(), coll, OfferingSort::Newest, Some(8), 5).unwrap()
+    );
+    assert_eq!(
+        vec![2, 1],
+        page(deps.as_ref(), coll, OfferingSort::Newest, Some(3), 5).unwrap()
+    );
+    assert_eq!(
+        vec![10, 11, 12],
+        page(deps.as_ref(), coll, OfferingSort::Oldest, Some(9), 5).unwrap()
+    );
+    assert_eq!(
+        vec![3, 8, 5, 10],
+        page(deps.as_ref(), coll, OfferingSort::LowestPrice, None, 4).unwrap()
+    );
+    assert_eq!(
+        vec![1, 12, 7, 11],
+        page(deps.as_ref(), coll, OfferingSort::LowestPrice, Some(10), 4).unwrap()
+    );
+    assert_eq!(
+        vec![2, 6, 9],
+        page(deps.as_ref(), coll, OfferingSort::HighestPrice, None, 3).unwrap()
+    );
+    assert_eq!(
+        vec![4, 11, 7],
+        page(deps.as_ref(), coll, OfferingSort::HighestPrice, Some(9), 3).unwrap()
+    );
+    // across collections, equal prices in listing order
+    assert_eq!(
+        vec![3, 13, 8],
+        page(deps.as_ref(), None, OfferingSort::LowestPrice, None, 3).unwrap()
+    );
+    assert_eq!(
+        vec![8, 5],
+        page(deps.as_ref(), None, OfferingSort::LowestPrice, Some(13), 2).unwrap()
+    );
+    assert_eq!(
+        vec![2, 6],
+        page(deps.as_ref(), None, OfferingSort::HighestPrice, None, 2).unwrap()
+    );
+
+    // a new price moves the offering in the price index
+    let msg = ExecuteMsg::UpdateListingPrice {
+        offering_id: OfferingId(2),
+        new_price: Uint128::new(1_000_000),
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), msg).unwrap();
+    assert_eq!(
+        vec![2, 3, 8],
+        page(deps.as_ref(), coll, OfferingSort::LowestPrice, None, 3).unwrap()
+    );
+    assert_eq!(
+        vec![6, 9, 4],
+        page(deps.as_ref(), coll, OfferingSort::HighestPrice, None, 3).unwrap()
+    );
+
+    // the next page still follows once the offering it starts after is withdrawn
+    let msg = QueryMsg::GetOfferings {
+        filter_seller: None,
+        include_metadata: None,
+        collection: Some("collection".to_string()),
+        sort: Some(OfferingSort::LowestPrice),
+        start_after: None,
+        limit: Some(3),
+    };
+    let res: OfferingsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    let cursor = res.next_start_after.unwrap();
+    assert_eq!(OfferingId(8), cursor.offering_id);
+    let msg = ExecuteMsg::WithdrawNft {
+        offering_id: OfferingId(8),
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), msg).unwrap();
+    let msg = QueryMsg::GetOfferings {
+        filter_seller: None,
+        include_metadata: None,
+        collection: Some("collection".to_string()),
+        sort: Some(OfferingSort::LowestPrice),
+        start_after: Some(cursor),
+        limit: Some(3),
+    };
+    let res: OfferingsResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    let ids: Vec<_> = res.offerings.iter().map(|off| off.offering_id.0).collect();
+    assert_eq!(vec![5, 10, 1], ids);
+
+    let newest = &get_offerings(deps.as_ref()).offerings[10];
+    assert_eq!(OfferingId(12), newest.offering_id);
+    assert_eq!(mock_env().block.height + 11, newest.listed_height);
+    assert_eq!(mock_env().block.time.plus_seconds(660), newest.listed_at);
+}
+
+#[test]
+fn test_ics721_bridge() {
+    let mut deps = mock_deps();
+    let (denom, dao_address, _) = initialize_contract(deps.as_mut());
+    mock_ics721_bridge(&mut deps);
+
+    let callback = |deps: DepsMut, sender: &str, nft_contract: &str| {
+        let data = BridgedSellNft {
+            seller: "seller".to_string(),
+            list_price: Uint128::new(1_000_000),
+            usd_price: None,
+            expiration: Expiration::Never {},
+        };
+        let msg = ExecuteMsg::Ics721ReceiveCallback(Ics721ReceiveCallbackMsg {
+            original_packet: mock_ics721_packet(&["token1", "token2"], MOCK_CONTRACT_ADDR),
+            nft_contract: nft_contract.to_string(),
+            msg: to_binary(&data).unwrap(),
+        });
+        execute(deps, mock_env(), mock_info(sender, &[]), msg)
+    };
+    let err = callback(deps.as_mut(), "ics721", "bridged").unwrap_err();
+    assert!(matches!(err, ContractError::NoIcs721Bridge {}));
+
+    let msg = ExecuteMsg::UpdateIcs721Bridge {
+        address: Some("ics721".to_string()),
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info(&dao_address, &[]), msg).unwrap();
+    let attrs = &res.events[0].attributes;
+    assert!(!attrs.iter().any(|attr| attr.key == "old_address"));
+    assert!(attrs.contains(&attr("new_address", "ics721")));
+
+    let err = callback(deps.as_mut(), "anyone", "bridged").unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized { .. }));
+    // the class was minted on "bridged", not on the contract the callback names
+    let err = callback(deps.as_mut(), "ics721", "collection").unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized { .. }));
+
+    // the seller hasn't allowed the packet sender yet
+    let err = callback(deps.as_mut(), "ics721", "bridged").unwrap_err();
+    assert!(matches!(
+        err,
+        ContractError::BridgedSenderNotAllowed { seller, channel_id, sender }
+            if seller == "seller" && channel_id == "channel-0" && sender == "stars1sender"
+    ));
+    let msg = ExecuteMsg::AllowBridgedSender {
+        channel_id: "channel-0".to_string(),
+        sender: "stars1sender".to_string(),
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("seller", &[]), msg).unwrap();
+    assert_eq!("craft_marketplace.allow_bridged_sender", res.events[0].ty);
+    assert!(res.events[0]
+        .attributes
+        .contains(&attr("sender", "stars1sender")));
+
+    // every NFT of the packet is listed
+    let res = callback(deps.as_mut(), "ics721", "bridged").unwrap();
+    assert_eq!(2, res.events.len());
+    assert!(res.events[1]
+        .attributes
+        .contains(&attr("class_id", BRIDGED_CLASS)));
+    let offerings = get_offerings(deps.as_ref()).offerings;
+    assert_eq!(2, offerings.len());
+    assert_eq!("bridged", offerings[1].contract_addr.as_str());
+    assert_eq!("seller", offerings[1].seller.as_str());
+
+    // only NFTs bridged in can be sent back
+    receive_nft(
+        deps.as_mut(),
+        mock_info("collection", &[]),
+        1_000_000,
+        "token3".to_string(),
+    )
+    .unwrap();
+    let buy_over_ibc = |deps: DepsMut, offering_id: u64| {
+        let msg = ExecuteMsg::BuyNftOverIbc {
+            offering_id: OfferingId(offering_id),
+            receiver: "stars1buyer".to_string(),
+            referrer: None,
+        };
+        let info = mock_info("buyer", &coins(1_000_000, &denom));
+        execute(deps, mock_env(), info, msg)
+    };
+    let err = buy_over_ibc(deps.as_mut(), 3).unwrap_err();
+    assert!(matches!(err, ContractError::NotBridged { collection } if collection == "collection"));
+
+    // the NFT is sent to the bridge, back over the channel it came in on
+    let res = buy_over_ibc(deps.as_mut(), 1).unwrap();
+    let packet = mock_ics721_send(&res.messages[0].msg, "token1");
+    assert_eq!("stars1buyer", packet.receiver);
+    assert_eq!("channel-0", packet.channel_id);
+    assert_eq!(
+        Some(mock_env().block.time.plus_seconds(600)),
+        packet.timeout.timestamp
+    );
+    let memo: Ics721Memo = cosmwasm_std::from_slice(packet.memo.unwrap().as_bytes()).unwrap();
+    let callbacks = memo.callbacks.unwrap();
+    assert_eq!(
+        Some(MOCK_CONTRACT_ADDR.to_string()),
+        callbacks.ack_callback_addr
+    );
+    let data = IbcDeliveryCallback {
+        offering_id: 1,
+        recipient: Addr::unchecked("buyer"),
+    };
+    assert_eq!(Some(to_binary(&data).unwrap()), callbacks.ack_callback_data);
+    assert!(res.events[0]
+        .attributes
+        .contains(&attr("ibc_receiver", "stars1buyer")));
+
+    // the bridge took the NFT, so the seller is paid
+    let res = contract::reply(deps.as_mut(), mock_env(), transfer_ok(1)).unwrap();
+    assert_eq!(2, res.messages.len());
+
+    // a failed packet returns the NFT to the marketplace, it goes to the buyer on this chain
+    let ack = |deps: DepsMut, sender: &str, status: Ics721Status| {
+        let msg = ExecuteMsg::Ics721AckCallback(Ics721AckCallbackMsg {
+            status,
+            nft_contract: "bridged".to_string(),
+            original_packet: NonFungibleTokenPacketData {
+                sender: sender.to_string(),
+                receiver: "stars1buyer".to_string(),
+                ..mock_ics721_packet(&["token1"], MOCK_CONTRACT_ADDR)
+            },
+            msg: to_binary(&data).unwrap(),
+        });
+        execute(deps, mock_env(), mock_info("ics721", &[]), msg)
+    };
+    let err = ack(deps.as_mut(), "stars1sender", Ics721Status::Success).unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized { .. }));
+    let res = ack(deps.as_mut(), MOCK_CONTRACT_ADDR, Ics721Status::Success).unwrap();
+    assert!(res.messages.is_empty());
+    assert_eq!("craft_marketplace.ibc_delivered", res.events[0].ty);
+    let failed = Ics721Status::Failed("timeout".to_string());
+    let res = ack(deps.as_mut(), MOCK_CONTRACT_ADDR, failed).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "bridged".to_string(),
+            msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: "buyer".to_string(),
+                token_id: "token1".to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    );
+
+    // a delivery the bridge rejected is retried with a new timeout
+    buy_over_ibc(deps.as_mut(), 2).unwrap();
+    contract::reply(deps.as_mut(), mock_env(), transfer_err(2)).unwrap();
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(3600);
+    let msg = ExecuteMsg::RetryTransfer {
+        offering_id: OfferingId(2),
+    };
+    let res = execute(deps.as_mut(), env.clone(), mock_info("buyer", &[]), msg).unwrap();
+    let packet = mock_ics721_send(&res.messages[0].msg, "token2");
+    assert_eq!(
+        Some(env.block.time.plus_seconds(600)),
+        packet.timeout.timestamp
+    );
+}
+
+#[test]
+fn test_ics721_forged_seller() {
+    let mut deps = mock_deps();
+    let (denom, dao_address, _) = initialize_contract(deps.as_mut());
+    mock_ics721_bridge(&mut deps);
+    let msg = ExecuteMsg::UpdateIcs721Bridge {
+        address: Some("ics721".to_string()),
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(&dao_address, &[]), msg).unwrap();
+    let msg = ExecuteMsg::UpdateListingLimits {
+        deposit: Uint128::new(100_000),
+        max_per_seller: 1,
+    };
+    execute(deps.as_mut(), mock_env(), mock_info(&dao_address, &[]), msg).unwrap();
+
+    // the victim prepaid a deposit & allowed their own address on the other chain
+    let info = mock_info("victim", &coins(100_000, &denom));
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        ExecuteMsg::PrepayDeposit {},
+    )
+    .unwrap();
+    let msg = ExecuteMsg::AllowBridgedSender {
+        channel_id: "channel-0".to_string(),
+        sender: "stars1victim".to_string(),
+    };
+    execute(deps.as_mut(), mock_env(), mock_info("victim", &[]), msg).unwrap();
+
+    // someone else bridges an NFT in naming the victim as seller
+    let data = BridgedSellNft {
+        seller: "victim".to_string(),
+        list_price: Uint128::new(1_000_000),
+        usd_price: None,
+        expiration: Expiration::Never {},
+    };
+    let msg = ExecuteMsg::Ics721ReceiveCallback(Ics721ReceiveCallbackMsg {
+        original_packet: mock_ics721_packet(&["token1"], MOCK_CONTRACT_ADDR),
+        nft_contract: "bridged".to_string(),
+        msg: to_binary(&data).unwrap(),
+    });
+    let err = execute(deps.as_mut(), mock_env(), mock_info("ics721", &[]), msg).unwrap_err();
+    assert!(matches!(err, ContractError::BridgedSenderNotAllowed { .. }));
+
+    // the deposit is untouched & no listing takes up the victims slot
+    let msg = QueryMsg::GetPrepaidDeposit {
+        address: "victim".to_string(),
+    };
+    let res: PrepaidDepositResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(Uint128::new(100_000), res.amount.amount);
+    assert!(get_offerings(deps.as_ref()).offerings.is_empty());
+
+    // a revoked sender can't list any more
+    let msg = ExecuteMsg::RevokeBridgedSender {
+        channel_id: "channel-0".to_string(),
+        sender: "stars1victim".to_string(),
+    };
+    let res = execute(deps.as_mut(), mock_env(), mock_info("victim", &[]), msg).unwrap();
+    assert_eq!("craft_marketplace.revoke_bridged_sender", res.events[0].ty);
+}
+
+#[test]
+fn test_effective_owners() {
+    let mut deps = mock_deps();
+    initialize_contract(deps.as_mut());
+    // the marketplace holds the "token"s, the seller keeps "house" & approved the marketplace for it
+    deps.querier.update_wasm(|query| match query {
+        WasmQuery::Smart { msg, .. } => {
+            let res = match from_binary(msg).unwrap() {
+                Cw721BaseQueryMsg::OwnerOf { token_id, .. } if token_id == "burned" => {
+                    return SystemResult::Ok(ContractResult::Err("not found".to_string()));
+                }
+                Cw721BaseQueryMsg::OwnerOf { token_id, .. } => {
+                    let owner = match token_id.as_str() {
+                        "house" => "seller",
+                        "unlisted" => "player",
+                        _ => MOCK_CONTRACT_ADDR,
+                    };
+                    to_binary(&OwnerOfResponse {
+                        owner: owner.to_string(),
+                        approvals: vec![],
+                    })
+                }
+                Cw721BaseQueryMsg::Approval { .. } => to_binary(&ApprovalResponse {
+                    approval: Approval {
+                        spender: MOCK_CONTRACT_ADDR.to_string(),
+                        expires: Expiration::Never {},
+                    },
+                }),
+                _ => return SystemResult::Ok(ContractResult::Err("not found".to_string())),
+            };
+            SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+        }
+        other => panic!("unexpected wasm query {:?}", other),
+    });
+
+    receive_nft(
+        deps.as_mut(),
+        mock_info("collection", &[]),
+        1_000_000,
+        "token1".to_string(),
+    )
+    .unwrap();
+    list_nft(deps.as_mut(), "seller", "house").unwrap();
+    let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+        sender: "seller".to_string(),
+        token_id: "token2".to_string(),
+        msg: to_binary(&SellNft {
+            list_price: Uint128::new(1_000_000),
+            expiration: Expiration::AtHeight(mock_env().block.height + 1),
+            usd_price: None,
+        })
+        .unwrap(),
+    });
+    execute(deps.as_mut(), mock_env(), mock_info("collection", &[]), msg).unwrap();
+    // the transfer of a bought token fails, the marketplace keeps it for the buyer
+    receive_nft(
+        deps.as_mut(),
+        mock_info("collection", &[]),
+        1_000_000,
+        "token3".to_string(),
+    )
+    .unwrap();
+    buy_nft(
+        deps.as_mut(),
+        mock_info("buyer", &coins(1_000_000, DENOM)),
+        OfferingId(4),
+    )
+    .unwrap();
+    contract::reply(deps.as_mut(), mock_env(), transfer_err(4)).unwrap();
+
+    let msg = QueryMsg::GetEffectiveOwner {
+        collection: "collection".to_string(),
+        token_id: "token1".to_string(),
+    };
+    let res: EffectiveOwnerResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    assert_eq!(Some("seller".to_string()), res.owner);
+    assert_eq!(
+        Some(ListingState {
+            offering_id: OfferingId(1),
+            seller: Addr::unchecked("seller"),
+            list_price: Uint128::new(1_000_000),
+            list_denom: DENOM.to_string(),
+            escrowed: true,
+            expired: false,
+        }),
+        res.listing
+    );
+
+    let mut env = mock_env();
+    env.block.height += 5;
+    let token_ids = ["token1", "house", "unlisted", "token2", "token3", "burned"];
+    let msg = QueryMsg::GetEffectiveOwners {
+        collection: "collection".to_string(),
+        token_ids: token_ids.iter().map(|id| id.to_string()).collect(),
+    };
+    let res: EffectiveOwnersResponse =
+        from_binary(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+    let owners: Vec<_> = res
+        .owners
+        .iter()
+        .map(|owner| (owner.token_id.as_str(), owner.owner.as_deref()))
+        .collect();
+    assert_eq!(
+        vec![
+            ("token1", Some("seller")),
+            ("house", Some("seller")),
+            ("unlisted", Some("player")),
+            ("token2", Some("seller")),
+            ("token3", Some("buyer")),
+            ("burned", None)
+        ],
+        owners
+    );
+    let stuck = res.owners[4].stuck_transfer.as_ref().unwrap();
+    assert_eq!(OfferingId(4), stuck.offering_id);
+    assert!(res.owners[5].error.is_some());
+    // the approval listing is listed but not escrowed, the collection knows its owner
+    let house = res.owners[1].listing.as_ref().unwrap();
+    assert_eq!((OfferingId(2), false), (house.offering_id, house.escrowed));
+    assert_eq!(None, res.owners[2].listing);
+    assert!(res.owners[3].listing.as_ref().unwrap().expired);
+
+    let msg = QueryMsg::GetEffectiveOwners {
+        collection: "collection".to_string(),
+        token_ids: vec!["token1".to_string(); 101],
+    };
+    query(deps.as_ref(), env.clone(), msg).unwrap_err();
+
+    // pages of offerings, the approval listing is skipped
+    let escrowed = |start_after: Option<u64>| -> EscrowedTokensResponse {
+        let msg = QueryMsg::GetEscrowedTokens {
+            owner: "seller".to_string(),
+            start_after: start_after.map(OfferingId),
+            limit: Some(2),
+        };
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap()
+    };
+    let page = escrowed(None);
+    let tokens: Vec<_> = page.tokens.iter().map(|t| t.token_id.as_str()).collect();
+    assert_eq!(vec!["token1"], tokens);
+    assert_eq!(Some(OfferingId(2)), page.next_start_after);
+    let page = escrowed(page.next_start_after.map(|id| id.0));
+    let tokens: Vec<_> = page.tokens.iter().map(|t| t.token_id.as_str()).collect();
+    assert_eq!(vec!["token2"], tokens);
+    assert_eq!(None, page.next_start_after);
+
+    // the buyer is waiting for the token of the failed transfer
+    let msg = QueryMsg::GetEscrowedTokens {
+        owner: "buyer".to_string(),
+        start_after: None,
+        limit: None,
+    };
+    let page: EscrowedTokensResponse =
+        from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+    let tokens: Vec<_> = page.tokens.iter().map(|t| t.token_id.as_str()).collect();
+    assert_eq!(vec!["token3"], tokens);
+    assert_eq!(None, page.tokens[0].listing);
+    assert!(page.tokens[0].stuck_transfer.is_some());
+}
+
+// ==== TEST HELPERS ====
+// every collection says the marketplace owns the token, except for "unsent" tokens. The
+// ContractInfo name is the collection address & the NftInfo name is the token_id. The oracle
+// prices 1 CRAFT at 2 USD, updated at the mock_env block time
+fn mock_deps() -> OwnedDeps<MockStorage, MockApi, MockQuerier<CraftQuery>> {
+    let querier = MockQuerier::<CraftQuery>::new(&[]).with_custom_handler(|query| match query {
+        CraftQuery::UsdPrice { denom } => {
+            assert_eq!(DENOM, denom);
+            let res = UsdPriceResponse {
+                price: Decimal::from_atomics(2u128, 6).unwrap(),
+                updated_at: mock_env().block.time,
+            };
+            SystemResult::Ok(ContractResult::Ok(to_binary(&res).unwrap()))
+        }
+    });
+    let mut deps = OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier,
+        custom_query_type: PhantomData,
+    };
+    deps.querier.update_wasm(|query| match query {
+        WasmQuery::Smart { contract_addr, msg } => {
+            let res = match from_binary(msg).unwrap() {
+                Cw721QueryMsg::OwnerOf { token_id, .. } => {
+                    let owner = match token_id.starts_with("unsent") {
+                        true => "seller",
+                        false => MOCK_CONTRACT_ADDR,
+                    };
+                    to_binary(&OwnerOfResponse {
+                        owner: owner.to_string(),
+                        approvals: vec![],
+                    })
+                }
+                Cw721QueryMsg::ContractInfo {} => to_binary(&Cw721ContractInfo {
+                    name: contract_addr.to_string(),
+                    symbol: "NFT".to_string(),
+                }),
+                Cw721QueryMsg::NftInfo { token_id } => to_binary(&NftInfoResponse {
+                    token_uri: Some(format!("ipfs://{}", token_id)),
+                    extension: NftMetadata {
+                        name: Some(token_id),
+                        description: Some("a".repeat(1000)),
+                        ..NftMetadata::default()
+                    },
+                }),
+                other => panic!("unexpected cw721 query {:?}", other),
+            };
+            SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+        }
+        other => panic!("unexpected wasm query {:?}", other),
+    });
+    deps
+}
+
+// answers the cw721 queries of approval listings: `owner` owns every token, the marketplace is
+// approved for it with Approve when `approved` & with ApproveAll when `operator`
+fn mock_approvals(
+    deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier<CraftQuery>>,
+    owner: &'static str,
+    approved: bool,
+    operator: bool,
+) {
+    deps.querier.update_wasm(move |query| match query {
+        WasmQuery::Smart { msg, .. } => {
+            let marketplace = Approval {
+                spender: MOCK_CONTRACT_ADDR.to_string(),
+                expires: Expiration::Never {},
+            };
+            let res = match from_binary(msg).unwrap() {
+                Cw721BaseQueryMsg::OwnerOf { .. } => to_binary(&OwnerOfResponse {
+                    owner: owner.to_string(),
+                    approvals: vec![],
+                }),
+                Cw721BaseQueryMsg::Approval { .. } if approved => to_binary(&ApprovalResponse {
+                    approval: marketplace,
+                }),
+                Cw721BaseQueryMsg::AllOperators { .. } => to_binary(&OperatorsResponse {
+                    operators: if operator { vec![marketplace] } else { vec![] },
+                }),
+                _ => return SystemResult::Ok(ContractResult::Err("not found".to_string())),
+            };
+            SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+        }
+        other => panic!("unexpected wasm query {:?}", other),
+    });
+}
+
+// answers the cw20 queries of the loyalty token: a cap of 1_000_000 & `supply` minted so far
+fn mock_loyalty_token(
+    deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier<CraftQuery>>,
+    minter: &'static str,
+    supply: u128,
+) {
+    deps.querier.update_wasm(move |query| match query {
+        WasmQuery::Smart { contract_addr, msg } if contract_addr == "points" => {
+            let res = match from_binary(msg).unwrap() {
+                Cw20QueryMsg::Minter {} => to_binary(&Some(MinterResponse {
+                    minter: minter.to_string(),
+                    cap: Some(Uint128::new(1_000_000)),
+                })),
+                Cw20QueryMsg::TokenInfo {} => to_binary(&TokenInfoResponse {
+                    name: "Craft Points".to_string(),
+                    symbol: "POINTS".to_string(),
+                    decimals: 0,
+                    total_supply: Uint128::new(supply),
+                }),
+                other => panic!("unexpected cw20 query {:?}", other),
+            };
+            SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+        }
+        other => panic!("unexpected wasm query {:?}", other),
+    });
+}
+
+// the class the mock ICS-721 bridge minted on "bridged", it came in over channel-0
+const BRIDGED_CLASS: &str = "wasm.ics721/channel-0/stars1collection";
+
+// a mock ICS-721 bridge at "ics721" which only knows BRIDGED_CLASS. Every collection says the
+// marketplace owns the token
+fn mock_ics721_bridge(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier<CraftQuery>>) {
+    deps.querier.update_wasm(|query| match query {
+        WasmQuery::Smart { contract_addr, msg } if contract_addr == "ics721" => {
+            let res = match from_binary(msg).unwrap() {
+                Ics721QueryMsg::ClassId { contract } => {
+                    to_binary(&Some(BRIDGED_CLASS).filter(|_| contract == "bridged"))
+                }
+                Ics721QueryMsg::NftContract { class_id } => {
+                    to_binary(&Some("bridged").filter(|_| class_id == BRIDGED_CLASS))
+                }
+            };
+            SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+        }
+        WasmQuery::Smart { msg, .. } => match from_binary(msg).unwrap() {
+            Cw721QueryMsg::OwnerOf { .. } => {
+                let res = to_binary(&OwnerOfResponse {
+                    owner: MOCK_CONTRACT_ADDR.to_string(),
+                    approvals: vec![],
+                });
+                SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+            }
+            _ => SystemResult::Ok(ContractResult::Err("not found".to_string())),
+        },
+        other => panic!("unexpected wasm query {:?}", other),
+    });
+}
+
+// the packet of NFTs of BRIDGED_CLASS sent from the other chain to `receiver`
+fn mock_ics721_packet(token_ids: &[&str], receiver: &str) -> NonFungibleTokenPacketData {
+    NonFungibleTokenPacketData {
+        class_id: BRIDGED_CLASS.to_string(),
+        class_uri: None,
+        class_data: None,
+        token_ids: token_ids.iter().map(|id| id.to_string()).collect(),
+        token_uris: None,
+        token_data: None,
+        sender: "stars1sender".to_string(),
+        receiver: receiver.to_string(),
+        memo: None,
+    }
+}
+
+// the packet of a SendNft of `token_id` to the mock bridge
+fn mock_ics721_send(msg: &CosmosMsg, token_id: &str) -> IbcOutgoingMsg {
+    match msg {
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr, msg, ..
+        }) => {
+            assert_eq!("bridged", contract_addr);
+            match from_binary(msg).unwrap() {
+                Cw721ExecuteMsg::SendNft {
+                    contract,
+                    token_id: sent,
+                    msg,
+                } => {
+                    assert_eq!(("ics721", token_id), (contract.as_str(), sent.as_str()));
+                    from_binary(&msg).unwrap()
+                }
+                other => panic!("Unexpected cw721 message: {:?}", other),
+            }
+        }
+        msg => panic!("Unexpected message: {:?}", msg),
+    }
+}
+
+fn list_nft(deps: DepsMut, seller: &str, token_id: &str) -> Result<Response, ContractError> {
+    let msg = ExecuteMsg::List {
+        collection: "collection".to_string(),
+        token_id: token_id.to_string(),
+        price: Uint128::new(1_000_000),
+    };
+    execute(deps, mock_env(), mock_info(seller, &[]), msg)
+}
+
+fn initialize_contract(deps: DepsMut) -> (String, String, u128) {
+    let msg = InitMsg {
+        name: MP_NAME.to_string(),
+        denom: DENOM.to_string(),
         fee_receive_address: String::from("craftdaoaddr"),
         platform_fee: 5,
+        admin: None,
     };
 
-    let info = mock_info("creator", &coins(1000000, &DENOM.to_string()));
+    let info = mock_info("creator", &coins(1000000, DENOM));
     contract::instantiate(deps, mock_env(), info, msg.clone()).unwrap();
 
     (msg.denom, msg.fee_receive_address, msg.platform_fee)
 }
 
-fn buy_nft(deps: DepsMut, info: MessageInfo, offering_id: String) -> Result<(), ContractError> {
-    let res = execute(deps, mock_env(), info, ExecuteMsg::BuyNft { offering_id });
+fn buy_nft(deps: DepsMut, info: MessageInfo, offering_id: OfferingId) -> Result<(), ContractError> {
+    let msg = ExecuteMsg::BuyNft {
+        offering_id,
+        recipient: None,
+        referrer: None,
+    };
+    let res = execute(deps, mock_env(), info, msg);
     match res {
         Ok(_) => Ok(()),
         Err(e) => {
@@ -551,6 +3702,8 @@ fn sell_nft(deps: DepsMut, info: MessageInfo, token_id: String, amount: u128) ->
     // now test if you overpay (expect error) [can this be done in helper function?]
     let sell_msg = SellNft {
         list_price: Uint128::new(amount), // so DAO should get 50k @ 5%
+        expiration: Expiration::Never {},
+        usd_price: None,
     };
     let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
         sender: String::from(info.sender.clone()), // was "seller_contract"
@@ -570,6 +3723,8 @@ fn receive_nft(
 ) -> Result<(), ContractError> {
     let sell_msg = SellNft {
         list_price: Uint128::new(list_price),
+        expiration: Expiration::Never {},
+        usd_price: None,
     };
 
     let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
@@ -584,8 +3739,37 @@ fn receive_nft(
     }
 }
 
+fn get_stuck_transfers(deps: Deps, filter_address: Option<&str>) -> StuckTransfersResponse {
+    let res = query(
+        deps,
+        mock_env(),
+        QueryMsg::GetStuckTransfers {
+            filter_address: filter_address.map(String::from),
+        },
+    )
+    .unwrap();
+    from_binary(&res).unwrap()
+}
+
+fn transfer_ok(id: u64) -> Reply {
+    Reply {
+        id,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+        }),
+    }
+}
+
+fn transfer_err(id: u64) -> Reply {
+    Reply {
+        id,
+        result: SubMsgResult::Err("collection is frozen".to_string()),
+    }
+}
+
 fn get_offerings(deps: Deps) -> OfferingsResponse {
-    let res = query(deps, mock_env(), QueryMsg::GetOfferings { filter_seller: None }).unwrap();
+    let res = query(deps, mock_env(), QueryMsg::GetOfferings { filter_seller: None, include_metadata: None, collection: None, sort: None, start_after: None, limit: None }).unwrap();
     let value: OfferingsResponse = from_binary(&res).unwrap();
     value
 }
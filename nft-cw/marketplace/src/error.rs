@@ -1,4 +1,5 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Timestamp};
+use cw_utils::PaymentError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,6 +7,19 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
+    #[error("Semver parsing error: {0}")]
+    SemVer(String),
+
+    #[error("Cannot migrate {contract} from {from} to {to}")]
+    CannotMigrate {
+        contract: String,
+        from: String,
+        to: String,
+    },
+
     #[error("No data in ReceiveMsg")]
     NoData {},
 
@@ -15,8 +29,11 @@ pub enum ContractError {
     #[error("Insufficient funds. Needed: {needed:?}, Received: {received:?}")]
     InsufficientFundsSend { needed: String, received: String },
 
-    #[error("The ID {id} is not valid. Make sure to check getOfferings{}")]
-    NoMarketplaceOfferingWithGivenID { id: String },
+    #[error("The ID {id} is not valid. Make sure to check getOfferings")]
+    NoMarketplaceOfferingWithGivenID { id: u64 },
+
+    #[error("There is no stuck transfer for offering {id}")]
+    NoStuckTransferWithGivenID { id: u64 },
 
     // This may be removed in favor of just withdrawing the NFT back to themselves.
     #[error("Trying to purchase your own item")]
@@ -27,4 +44,107 @@ pub enum ContractError {
 
     #[error("You can't sell an NFT for less than 1craft (1_000_000ucraft)")]
     ListingPriceTooLow {},
+
+    #[error("The offering {id} has expired")]
+    OfferingExpired { id: u64 },
+
+    #[error("The expiration is already in the past")]
+    InvalidExpiration {},
+
+    #[error("There are no proceeds to claim")]
+    NoProceeds {},
+
+    #[error("The marketplace does not own token {token_id} of {collection}")]
+    NftNotReceived {
+        collection: String,
+        token_id: String,
+    },
+
+    #[error("The collection {collection} is not registered")]
+    CollectionNotRegistered { collection: String },
+
+    #[error("The ContractInfo of {collection} does not match the registry")]
+    CollectionInfoMismatch { collection: String },
+
+    #[error("The oracle price of {denom} was last updated at {updated_at}, it is too old to use")]
+    StalePrice { denom: String, updated_at: u64 },
+
+    #[error("The oracle has no price for {denom}")]
+    NoPrice { denom: String },
+
+    #[error("There are no featured slots")]
+    NoFeaturedSlots {},
+
+    #[error("The offering {id} is already promoted")]
+    AlreadyPromoted { id: u64 },
+
+    #[error("Every featured slot is taken, the bid must be more than {min}")]
+    PromotionBidTooLow { min: String },
+
+    #[error("A promotion can last between 1 and {max} seconds")]
+    InvalidPromotionDuration { max: u64 },
+
+    #[error("The marketplace is not approved to transfer token {token_id} of {collection}")]
+    MarketplaceNotApproved {
+        collection: String,
+        token_id: String,
+    },
+
+    #[error("The token is already listed as offering {id}")]
+    AlreadyListed { id: u64 },
+
+    #[error("The offering {id} is stale, the seller no longer owns or approved the token")]
+    StaleListing { id: u64 },
+
+    #[error("{address} is blocked from the marketplace. Reason: {reason}")]
+    Blocked { address: String, reason: String },
+
+    #[error("The referral fee can be at most {max} basis points")]
+    ReferralFeeTooHigh { max: u16 },
+
+    #[error("A buyer can not refer their own purchase")]
+    SelfReferral {},
+
+    #[error("A fee discount can be at most {max} basis points")]
+    FeeDiscountTooHigh { max: u16 },
+
+    #[error("The marketplace is not the minter of {token}")]
+    NotRebateMinter { token: String },
+
+    #[error("The sale looks like a wash trade: {reason}")]
+    WashTrade { reason: String },
+
+    #[error("The token was bought recently, it can be sold again at {until}")]
+    HoldingPeriod { until: Timestamp },
+
+    #[error("A seller can have at most {max} offerings listed")]
+    TooManyListings { max: u32 },
+
+    #[error("Listing requires a deposit of {deposit}, prepay it with PrepayDeposit")]
+    ListingDepositRequired { deposit: String },
+
+    #[error("The offering {id} has not expired yet")]
+    OfferingNotExpired { id: u64 },
+
+    #[error("There are no prepaid deposits to withdraw")]
+    NoPrepaidDeposit {},
+
+    #[error("No ICS-721 bridge is set")]
+    NoIcs721Bridge {},
+
+    #[error("{collection} was not bridged in over ICS-721, it can't be sent back")]
+    NotBridged { collection: String },
+
+    #[error("{seller} does not let {sender} list NFTs bridged in over {channel_id} for them")]
+    BridgedSenderNotAllowed {
+        seller: String,
+        channel_id: String,
+        sender: String,
+    },
+}
+
+impl From<semver::Error> for ContractError {
+    fn from(err: semver::Error) -> Self {
+        Self::SemVer(err.to_string())
+    }
 }
@@ -0,0 +1,793 @@
+use crate::state::{FeeTier, FeeTiers, RebateConfig, RebateRate, WashTrading};
+use cosmwasm_std::{Coin, Decimal, Event as WasmEvent, Response, Timestamp, Uint128};
+use cw_utils::Event;
+use cw_utils::Expiration;
+
+// Every action emits one `wasm-craft_marketplace.<action>` event. Indexers (nftsync.service, stats)
+// should key off these instead of the `action` attribute. Bump EVENT_SCHEMA_VERSION whenever an
+// attribute is renamed or removed, adding new attributes does not require a bump.
+pub const EVENT_SCHEMA_VERSION: &str = "1";
+const EVENT_PREFIX: &str = "craft_marketplace";
+
+fn marketplace_event(action: &str) -> WasmEvent {
+    WasmEvent::new(format!("{}.{}", EVENT_PREFIX, action))
+        .add_attribute("schema_version", EVENT_SCHEMA_VERSION)
+}
+
+pub struct ListEvent<'a> {
+    pub offering_id: u64,
+    pub collection: &'a str,
+    pub token_id: &'a str,
+    pub seller: &'a str,
+    pub amount: Uint128,
+    pub denom: &'a str,
+    /// set for listings priced in USD, amount is then the converted price at listing time
+    pub usd_price: Option<Decimal>,
+    pub non_custodial: bool,
+    /// the sellers staking discount at listing time, the one at purchase time applies
+    pub fee_discount_bps: u16,
+    /// the listing deposit held, in denom
+    pub deposit: Uint128,
+    /// the ICS-721 class of an NFT listed as it was bridged in
+    pub class_id: Option<&'a str>,
+}
+
+impl<'a> Event for ListEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        let mut event = marketplace_event("list")
+            .add_attribute("offering_id", self.offering_id.to_string())
+            .add_attribute("collection", self.collection)
+            .add_attribute("token_id", self.token_id)
+            .add_attribute("seller", self.seller)
+            .add_attribute("amount", self.amount)
+            .add_attribute("denom", self.denom)
+            .add_attribute("non_custodial", self.non_custodial.to_string())
+            .add_attribute("fee_discount_bps", self.fee_discount_bps.to_string());
+        if let Some(usd_price) = self.usd_price {
+            event = event.add_attribute("usd_price", usd_price.to_string());
+        }
+        if let Some(class_id) = self.class_id {
+            event = event.add_attribute("class_id", class_id);
+        }
+        if !self.deposit.is_zero() {
+            event = event.add_attribute("deposit", self.deposit);
+        }
+        response.events.push(event);
+    }
+}
+
+pub struct BuyEvent<'a> {
+    pub offering_id: u64,
+    pub collection: &'a str,
+    pub token_id: &'a str,
+    pub seller: &'a str,
+    pub buyer: &'a str,
+    /// who receives the NFT, the buyer unless it was bought as a gift
+    pub recipient: &'a str,
+    pub amount: Uint128,
+    pub denom: &'a str,
+    pub platform_fee: Uint128,
+    pub seller_amount: Uint128,
+    /// the sellers staking discount, already taken off platform_fee
+    pub fee_discount_bps: u16,
+    /// why the sale was flagged as a wash trade, it is then left out of the volume
+    pub wash_trade: Option<&'a str>,
+    /// the referrer & their share, which is not included in platform_fee
+    pub referral: Option<(&'a str, Uint128)>,
+    /// the channel & receiver the NFT is sent to over ICS-721, recipient then only gets it back
+    /// when the packet fails
+    pub ibc: Option<(&'a str, &'a str)>,
+}
+
+impl<'a> Event for BuyEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        let mut event = marketplace_event("buy")
+            .add_attribute("offering_id", self.offering_id.to_string())
+            .add_attribute("collection", self.collection)
+            .add_attribute("token_id", self.token_id)
+            .add_attribute("seller", self.seller)
+            .add_attribute("buyer", self.buyer)
+            .add_attribute("recipient", self.recipient)
+            .add_attribute("amount", self.amount)
+            .add_attribute("denom", self.denom)
+            .add_attribute("platform_fee", self.platform_fee)
+            .add_attribute("seller_amount", self.seller_amount)
+            .add_attribute("fee_discount_bps", self.fee_discount_bps.to_string());
+        if let Some(wash_trade) = self.wash_trade {
+            event = event.add_attribute("wash_trade", wash_trade);
+        }
+        if let Some((referrer, referral_fee)) = self.referral {
+            event = event
+                .add_attribute("referrer", referrer)
+                .add_attribute("referral_fee", referral_fee);
+        }
+        if let Some((channel_id, receiver)) = self.ibc {
+            event = event
+                .add_attribute("ibc_channel", channel_id)
+                .add_attribute("ibc_receiver", receiver);
+        }
+        response.events.push(event);
+    }
+}
+
+pub struct RefreshMetadataEvent<'a> {
+    pub offering_id: u64,
+    pub collection: &'a str,
+    pub token_id: &'a str,
+    /// false when the collection returned no NftInfo & the cached copy was dropped
+    pub found: bool,
+}
+
+impl<'a> Event for RefreshMetadataEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("refresh_metadata")
+                .add_attribute("offering_id", self.offering_id.to_string())
+                .add_attribute("collection", self.collection)
+                .add_attribute("token_id", self.token_id)
+                .add_attribute("found", self.found.to_string()),
+        );
+    }
+}
+
+pub struct WithdrawEvent<'a> {
+    pub offering_id: u64,
+    pub collection: &'a str,
+    pub token_id: &'a str,
+    pub seller: &'a str,
+    /// true when the admin or a governance proposal (sudo) pulled the listing with ForceWithdraw
+    pub forced: bool,
+}
+
+impl<'a> Event for WithdrawEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("withdraw")
+                .add_attribute("offering_id", self.offering_id.to_string())
+                .add_attribute("collection", self.collection)
+                .add_attribute("token_id", self.token_id)
+                .add_attribute("seller", self.seller)
+                .add_attribute("forced", self.forced.to_string()),
+        );
+    }
+}
+
+pub struct PruneEvent<'a> {
+    pub offering_id: u64,
+    pub collection: &'a str,
+    pub token_id: &'a str,
+    pub seller: &'a str,
+    pub reason: &'a str,
+}
+
+impl<'a> Event for PruneEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("prune")
+                .add_attribute("offering_id", self.offering_id.to_string())
+                .add_attribute("collection", self.collection)
+                .add_attribute("token_id", self.token_id)
+                .add_attribute("seller", self.seller)
+                .add_attribute("reason", self.reason),
+        );
+    }
+}
+
+pub struct UpdatePriceEvent<'a> {
+    pub offering_id: u64,
+    pub collection: &'a str,
+    pub token_id: &'a str,
+    pub old_amount: Uint128,
+    pub new_amount: Uint128,
+    pub denom: &'a str,
+}
+
+impl<'a> Event for UpdatePriceEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("update_price")
+                .add_attribute("offering_id", self.offering_id.to_string())
+                .add_attribute("collection", self.collection)
+                .add_attribute("token_id", self.token_id)
+                .add_attribute("old_amount", self.old_amount)
+                .add_attribute("new_amount", self.new_amount)
+                .add_attribute("denom", self.denom),
+        );
+    }
+}
+
+pub struct UpdateFeeReceiverEvent<'a> {
+    pub old_address: &'a str,
+    pub new_address: &'a str,
+}
+
+impl<'a> Event for UpdateFeeReceiverEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("update_fee_receiver")
+                .add_attribute("old_address", self.old_address)
+                .add_attribute("new_address", self.new_address),
+        );
+    }
+}
+
+pub struct UpdatePlatformFeeEvent {
+    pub old_fee: u128,
+    pub new_fee: u128,
+}
+
+impl Event for UpdatePlatformFeeEvent {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("update_platform_fee")
+                .add_attribute("old_fee", self.old_fee.to_string())
+                .add_attribute("new_fee", self.new_fee.to_string()),
+        );
+    }
+}
+
+pub struct UpdatePullPaymentsEvent {
+    pub old_enabled: bool,
+    pub new_enabled: bool,
+}
+
+impl Event for UpdatePullPaymentsEvent {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("update_pull_payments")
+                .add_attribute("old_enabled", self.old_enabled.to_string())
+                .add_attribute("new_enabled", self.new_enabled.to_string()),
+        );
+    }
+}
+
+pub struct RegisterCollectionEvent<'a> {
+    pub collection: &'a str,
+    pub name: &'a str,
+    pub symbol: &'a str,
+}
+
+impl<'a> Event for RegisterCollectionEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("register_collection")
+                .add_attribute("collection", self.collection)
+                .add_attribute("name", self.name)
+                .add_attribute("symbol", self.symbol),
+        );
+    }
+}
+
+pub struct UnregisterCollectionEvent<'a> {
+    pub collection: &'a str,
+}
+
+impl<'a> Event for UnregisterCollectionEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("unregister_collection").add_attribute("collection", self.collection),
+        );
+    }
+}
+
+pub struct UpdateVerifyCollectionsEvent {
+    pub old_enabled: bool,
+    pub new_enabled: bool,
+}
+
+impl Event for UpdateVerifyCollectionsEvent {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("update_verify_collections")
+                .add_attribute("old_enabled", self.old_enabled.to_string())
+                .add_attribute("new_enabled", self.new_enabled.to_string()),
+        );
+    }
+}
+
+pub struct TransferEvent<'a> {
+    pub offering_id: u64,
+    pub collection: &'a str,
+    pub token_id: &'a str,
+    pub recipient: &'a str,
+    /// set when the collection rejected the transfer & the item is now stuck
+    pub error: Option<&'a str>,
+}
+
+impl<'a> Event for TransferEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        let event = match self.error {
+            None => marketplace_event("transfer_complete"),
+            Some(error) => marketplace_event("transfer_stuck").add_attribute("error", error),
+        };
+        response.events.push(
+            event
+                .add_attribute("offering_id", self.offering_id.to_string())
+                .add_attribute("collection", self.collection)
+                .add_attribute("token_id", self.token_id)
+                .add_attribute("recipient", self.recipient),
+        );
+    }
+}
+
+pub struct RetryTransferEvent<'a> {
+    pub offering_id: u64,
+    pub sender: &'a str,
+}
+
+impl<'a> Event for RetryTransferEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("retry_transfer")
+                .add_attribute("offering_id", self.offering_id.to_string())
+                .add_attribute("sender", self.sender),
+        );
+    }
+}
+
+pub struct RefundEvent<'a> {
+    pub offering_id: u64,
+    pub collection: &'a str,
+    pub token_id: &'a str,
+    pub buyer: &'a str,
+    pub amount: Uint128,
+    pub denom: &'a str,
+}
+
+impl<'a> Event for RefundEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("refund")
+                .add_attribute("offering_id", self.offering_id.to_string())
+                .add_attribute("collection", self.collection)
+                .add_attribute("token_id", self.token_id)
+                .add_attribute("buyer", self.buyer)
+                .add_attribute("amount", self.amount)
+                .add_attribute("denom", self.denom),
+        );
+    }
+}
+
+pub struct CreditProceedsEvent<'a> {
+    pub offering_id: u64,
+    pub recipient: &'a str,
+    pub amount: Uint128,
+    pub denom: &'a str,
+}
+
+impl<'a> Event for CreditProceedsEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("credit_proceeds")
+                .add_attribute("offering_id", self.offering_id.to_string())
+                .add_attribute("recipient", self.recipient)
+                .add_attribute("amount", self.amount)
+                .add_attribute("denom", self.denom),
+        );
+    }
+}
+
+pub struct ClaimProceedsEvent<'a> {
+    pub recipient: &'a str,
+    pub amount: &'a [Coin],
+}
+
+impl<'a> Event for ClaimProceedsEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        let amount: Vec<String> = self.amount.iter().map(Coin::to_string).collect();
+        response.events.push(
+            marketplace_event("claim_proceeds")
+                .add_attribute("recipient", self.recipient)
+                .add_attribute("amount", amount.join(",")),
+        );
+    }
+}
+
+pub struct PromoteEvent<'a> {
+    pub offering_id: u64,
+    pub collection: &'a str,
+    pub seller: &'a str,
+    pub amount: Uint128,
+    pub denom: &'a str,
+    pub expires: Timestamp,
+    /// the offering which lost its slot to this one
+    pub outbid: Option<u64>,
+}
+
+impl<'a> Event for PromoteEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        let mut event = marketplace_event("promote")
+            .add_attribute("offering_id", self.offering_id.to_string())
+            .add_attribute("collection", self.collection)
+            .add_attribute("seller", self.seller)
+            .add_attribute("amount", self.amount)
+            .add_attribute("denom", self.denom)
+            .add_attribute("expires", self.expires.seconds().to_string());
+        if let Some(outbid) = self.outbid {
+            event = event.add_attribute("outbid", outbid.to_string());
+        }
+        response.events.push(event);
+    }
+}
+
+pub struct UpdateMaxPriceAgeEvent {
+    pub old_max_price_age: u64,
+    pub new_max_price_age: u64,
+}
+
+impl Event for UpdateMaxPriceAgeEvent {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("update_max_price_age")
+                .add_attribute("old_max_price_age", self.old_max_price_age.to_string())
+                .add_attribute("new_max_price_age", self.new_max_price_age.to_string()),
+        );
+    }
+}
+
+pub struct UpdateFeaturedSlotsEvent {
+    pub old_slots: u32,
+    pub new_slots: u32,
+}
+
+impl Event for UpdateFeaturedSlotsEvent {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("update_featured_slots")
+                .add_attribute("old_slots", self.old_slots.to_string())
+                .add_attribute("new_slots", self.new_slots.to_string()),
+        );
+    }
+}
+
+pub struct UpdateReferralFeeEvent {
+    pub old_bps: u16,
+    pub new_bps: u16,
+}
+
+impl Event for UpdateReferralFeeEvent {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("update_referral_fee")
+                .add_attribute("old_bps", self.old_bps.to_string())
+                .add_attribute("new_bps", self.new_bps.to_string()),
+        );
+    }
+}
+
+pub struct RebateEvent<'a> {
+    pub offering_id: u64,
+    pub token: &'a str,
+    pub buyer: &'a str,
+    pub buyer_amount: Uint128,
+    pub seller: &'a str,
+    pub seller_amount: Uint128,
+    /// set when the cap of the token cut the rebates short
+    pub capped: bool,
+}
+
+impl<'a> Event for RebateEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("rebate")
+                .add_attribute("offering_id", self.offering_id.to_string())
+                .add_attribute("token", self.token)
+                .add_attribute("buyer", self.buyer)
+                .add_attribute("buyer_amount", self.buyer_amount)
+                .add_attribute("seller", self.seller)
+                .add_attribute("seller_amount", self.seller_amount)
+                .add_attribute("capped", self.capped.to_string()),
+        );
+    }
+}
+
+pub struct RebateFailedEvent<'a> {
+    pub offering_id: u64,
+    pub token: &'a str,
+    /// set when the token failed the mint, unset when it couldn't be queried
+    pub recipient: Option<&'a str>,
+    pub amount: Option<Uint128>,
+    pub error: &'a str,
+}
+
+impl<'a> Event for RebateFailedEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        let mut event = marketplace_event("rebate_failed")
+            .add_attribute("offering_id", self.offering_id.to_string())
+            .add_attribute("token", self.token);
+        if let Some(recipient) = self.recipient {
+            event = event.add_attribute("recipient", recipient);
+        }
+        if let Some(amount) = self.amount {
+            event = event.add_attribute("amount", amount);
+        }
+        response
+            .events
+            .push(event.add_attribute("error", self.error));
+    }
+}
+
+pub struct ReclaimEvent<'a> {
+    pub offering_id: u64,
+    pub collection: &'a str,
+    pub token_id: &'a str,
+    pub seller: &'a str,
+    pub reclaimer: &'a str,
+    /// who the listing deposit went to, the fee receiver unless the seller reclaimed it
+    pub deposit_recipient: &'a str,
+    pub deposit: Uint128,
+}
+
+impl<'a> Event for ReclaimEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("reclaim")
+                .add_attribute("offering_id", self.offering_id.to_string())
+                .add_attribute("collection", self.collection)
+                .add_attribute("token_id", self.token_id)
+                .add_attribute("seller", self.seller)
+                .add_attribute("reclaimer", self.reclaimer)
+                .add_attribute("deposit_recipient", self.deposit_recipient)
+                .add_attribute("deposit", self.deposit),
+        );
+    }
+}
+
+pub struct PrepayDepositEvent<'a> {
+    pub seller: &'a str,
+    pub amount: Uint128,
+    /// the prepaid deposits of the seller, this one included
+    pub balance: Uint128,
+    pub denom: &'a str,
+}
+
+impl<'a> Event for PrepayDepositEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("prepay_deposit")
+                .add_attribute("seller", self.seller)
+                .add_attribute("amount", self.amount)
+                .add_attribute("balance", self.balance)
+                .add_attribute("denom", self.denom),
+        );
+    }
+}
+
+pub struct WithdrawPrepaidDepositEvent<'a> {
+    pub seller: &'a str,
+    pub amount: Uint128,
+    pub denom: &'a str,
+}
+
+impl<'a> Event for WithdrawPrepaidDepositEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("withdraw_prepaid_deposit")
+                .add_attribute("seller", self.seller)
+                .add_attribute("amount", self.amount)
+                .add_attribute("denom", self.denom),
+        );
+    }
+}
+
+pub struct UpdateListingLimitsEvent {
+    pub old_deposit: Uint128,
+    pub new_deposit: Uint128,
+    pub old_max_per_seller: u32,
+    pub new_max_per_seller: u32,
+}
+
+impl Event for UpdateListingLimitsEvent {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            marketplace_event("update_listing_limits")
+                .add_attribute("old_deposit", self.old_deposit)
+                .add_attribute("new_deposit", self.new_deposit)
+                .add_attribute("old_max_per_seller", self.old_max_per_seller.to_string())
+                .add_attribute("new_max_per_seller", self.new_max_per_seller.to_string()),
+        );
+    }
+}
+
+pub struct UpdateWashTradingEvent<'a> {
+    pub old: &'a WashTrading,
+    pub new: &'a WashTrading,
+}
+
+impl<'a> Event for UpdateWashTradingEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        let (old, new) = (self.old, self.new);
+        response.events.push(
+            marketplace_event("update_wash_trading")
+                .add_attribute("old_holding_period", old.holding_period.to_string())
+                .add_attribute("new_holding_period", new.holding_period.to_string())
+                .add_attribute("old_pair_window", old.pair_window.to_string())
+                .add_attribute("new_pair_window", new.pair_window.to_string())
+                .add_attribute("old_reject", old.reject.to_string())
+                .add_attribute("new_reject", new.reject.to_string()),
+        );
+    }
+}
+
+pub struct UpdateRebatesEvent<'a> {
+    /// unset until the rebates are configured for the first time
+    pub old: Option<&'a RebateConfig>,
+    pub new_token: &'a str,
+    pub new_rates: &'a [RebateRate],
+}
+
+impl<'a> Event for UpdateRebatesEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        // denom:buyer_rate:seller_rate
+        let rates = |rates: &[RebateRate]| -> String {
+            rates
+                .iter()
+                .map(|rate| format!("{}:{}:{}", rate.denom, rate.buyer, rate.seller))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let mut event = marketplace_event("update_rebates");
+        if let Some(old) = self.old {
+            event = event
+                .add_attribute("old_token", &old.token)
+                .add_attribute("old_rates", rates(&old.rates));
+        }
+        response.events.push(
+            event
+                .add_attribute("new_token", self.new_token)
+                .add_attribute("new_rates", rates(self.new_rates)),
+        );
+    }
+}
+
+pub struct UpdateFeeTiersEvent<'a> {
+    /// unset until the tiers are configured for the first time
+    pub old: Option<&'a FeeTiers>,
+    pub new_denom: &'a str,
+    pub new_tiers: &'a [FeeTier],
+}
+
+impl<'a> Event for UpdateFeeTiersEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        // min_stake:discount_bps pairs, lowest tier first
+        let tiers = |tiers: &[FeeTier]| -> String {
+            tiers
+                .iter()
+                .map(|tier| format!("{}:{}", tier.min_stake, tier.discount_bps))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let mut event = marketplace_event("update_fee_tiers");
+        if let Some(old) = self.old {
+            event = event
+                .add_attribute("old_denom", &old.denom)
+                .add_attribute("old_tiers", tiers(&old.tiers));
+        }
+        response.events.push(
+            event
+                .add_attribute("new_denom", self.new_denom)
+                .add_attribute("new_tiers", tiers(self.new_tiers)),
+        );
+    }
+}
+
+pub struct SetReferrerLimitEvent<'a> {
+    pub referrer: &'a str,
+    /// unset when the referrer had no limit, or has none any more
+    pub old_max_bps: Option<u16>,
+    pub new_max_bps: Option<u16>,
+}
+
+impl<'a> Event for SetReferrerLimitEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        let mut event =
+            marketplace_event("set_referrer_limit").add_attribute("referrer", self.referrer);
+        if let Some(max_bps) = self.old_max_bps {
+            event = event.add_attribute("old_max_bps", max_bps.to_string());
+        }
+        if let Some(max_bps) = self.new_max_bps {
+            event = event.add_attribute("new_max_bps", max_bps.to_string());
+        }
+        response.events.push(event);
+    }
+}
+
+pub struct BlockEvent<'a> {
+    pub address: &'a str,
+    pub reason: Option<&'a str>,
+    pub expires: &'a Expiration,
+}
+
+impl<'a> Event for BlockEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        let mut event = marketplace_event("block")
+            .add_attribute("address", self.address)
+            .add_attribute("expires", self.expires.to_string());
+        if let Some(reason) = self.reason {
+            event = event.add_attribute("reason", reason);
+        }
+        response.events.push(event);
+    }
+}
+
+pub struct UnblockEvent<'a> {
+    pub address: &'a str,
+}
+
+impl<'a> Event for UnblockEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response
+            .events
+            .push(marketplace_event("unblock").add_attribute("address", self.address));
+    }
+}
+
+pub struct IbcDeliveryEvent<'a> {
+    pub offering_id: u64,
+    pub collection: &'a str,
+    pub token_id: &'a str,
+    /// the address on the other chain
+    pub receiver: &'a str,
+    /// set when the packet failed, the NFT is then sent to recipient
+    pub error: Option<&'a str>,
+    pub recipient: &'a str,
+}
+
+impl<'a> Event for IbcDeliveryEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        let event = match self.error {
+            None => marketplace_event("ibc_delivered"),
+            Some(error) => marketplace_event("ibc_failed")
+                .add_attribute("error", error)
+                .add_attribute("recipient", self.recipient),
+        };
+        response.events.push(
+            event
+                .add_attribute("offering_id", self.offering_id.to_string())
+                .add_attribute("collection", self.collection)
+                .add_attribute("token_id", self.token_id)
+                .add_attribute("receiver", self.receiver),
+        );
+    }
+}
+
+pub struct UpdateIcs721BridgeEvent<'a> {
+    pub old_address: Option<&'a str>,
+    pub new_address: Option<&'a str>,
+}
+
+impl<'a> Event for UpdateIcs721BridgeEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        let mut event = marketplace_event("update_ics721_bridge");
+        if let Some(address) = self.old_address {
+            event = event.add_attribute("old_address", address);
+        }
+        if let Some(address) = self.new_address {
+            event = event.add_attribute("new_address", address);
+        }
+        response.events.push(event);
+    }
+}
+
+pub struct BridgedSenderEvent<'a> {
+    pub seller: &'a str,
+    pub channel_id: &'a str,
+    pub sender: &'a str,
+    /// false when the seller revoked the sender
+    pub allowed: bool,
+}
+
+impl<'a> Event for BridgedSenderEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        let action = match self.allowed {
+            true => "allow_bridged_sender",
+            false => "revoke_bridged_sender",
+        };
+        response.events.push(
+            marketplace_event(action)
+                .add_attribute("seller", self.seller)
+                .add_attribute("channel_id", self.channel_id)
+                .add_attribute("sender", self.sender),
+        );
+    }
+}
@@ -1,200 +1,1065 @@
-use crate::coin_helpers::assert_sent_exact_coin;
-use crate::queries;
-use cosmwasm_std::{BankMsg, Deps, StdResult, Uint128};
-use cw721::{Cw721ExecuteMsg, Cw721ReceiveMsg};
+use crate::coin_helpers::take_payment;
+use cosmwasm_std::{
+    Addr, BankMsg, BlockInfo, Deps, Empty, Env, Order, Reply, StdError, StdResult, Storage,
+    SubMsgResult, Uint128,
+};
+use cw20::{Cw20ExecuteMsg, Cw20QueryMsg, MinterResponse, TokenInfoResponse};
+use cw721::{
+    ContractInfoResponse as Cw721ContractInfo, Cw721ExecuteMsg, Cw721ReceiveMsg, NftInfoResponse,
+};
+use cw721_base::helpers::Cw721Contract;
+use cw_storage_plus::Bound;
 
 // use crate::package::{ContractInfoResponse};
-use crate::state::{increment_offerings, Offering, COLLECTION_VOLUME, CONTRACT_INFO, OFFERINGS};
+use crate::state::{
+    active_block, increment_offerings, offerings, remove_offering, BlockEntry, FeeTier, FeeTiers,
+    IbcDelivery, LastSale, NftMetadata, NftTransfer, Offering, Payout, Promotion, RebateConfig,
+    RebateMint, RebateRate, Trait, TransferKind, WashTrading, APPROVAL_LISTINGS, BLOCKLIST,
+    BRIDGED_SENDERS, COLLECTIONS, COLLECTION_VOLUME, CONTRACT_INFO, FEE_TIERS, LAST_SALES,
+    OFFERING_METADATA, PAIR_TRADES, PENDING_TRANSFERS, PREPAID_DEPOSITS, PROCEEDS, PROMOTIONS,
+    REBATES, REBATE_CONFIG, REBATE_MINTS, REFERRAL_TOTALS, REFERRER_LIMITS, STUCK_TRANSFERS,
+    WASH_TRADING,
+};
 use cosmwasm_std::{
-    from_binary, to_binary, Coin, CosmosMsg, DepsMut, MessageInfo, Response, SubMsg, WasmMsg,
+    from_binary, to_binary, to_vec, Coin, DepsMut, MessageInfo, Response, SubMsg, WasmMsg,
 };
 
 use crate::error::ContractError;
-use crate::msg::SellNft;
+use crate::events::{
+    BlockEvent, BridgedSenderEvent, BuyEvent, ClaimProceedsEvent, CreditProceedsEvent,
+    IbcDeliveryEvent, ListEvent, PrepayDepositEvent, PromoteEvent, PruneEvent, RebateEvent,
+    RebateFailedEvent, ReclaimEvent, RefreshMetadataEvent, RefundEvent, RegisterCollectionEvent,
+    RetryTransferEvent, SetReferrerLimitEvent, TransferEvent, UnblockEvent,
+    UnregisterCollectionEvent, UpdateFeaturedSlotsEvent, UpdateFeeReceiverEvent,
+    UpdateFeeTiersEvent, UpdateIcs721BridgeEvent, UpdateListingLimitsEvent, UpdateMaxPriceAgeEvent,
+    UpdatePlatformFeeEvent, UpdatePriceEvent, UpdatePullPaymentsEvent, UpdateRebatesEvent,
+    UpdateReferralFeeEvent, UpdateVerifyCollectionsEvent, UpdateWashTradingEvent, WithdrawEvent,
+    WithdrawPrepaidDepositEvent,
+};
+use crate::ics721::{
+    query_class_id, query_nft_contract, source_channel, IbcDeliveryCallback, IbcOutgoingMsg,
+    IbcTimeout, Ics721AckCallbackMsg, Ics721Callbacks, Ics721Memo, Ics721ReceiveCallbackMsg,
+    Ics721Status,
+};
+use crate::msg::{
+    BridgedSellNft, ForceWithdrawResponse, OfferingId, PurchaseFailure, SellNft, StaleListing,
+    StaleListingsResponse,
+};
+use crate::oracle::query_usd_price;
+use cw_utils::{must_pay, nonpayable, Event, Expiration, NativeBalance};
+
+// how many offerings a single ForceWithdraw call returns, keeps the tx under the block gas limit
+const DEFAULT_FORCE_WITHDRAW_LIMIT: u32 = 30;
+const MAX_FORCE_WITHDRAW_LIMIT: u32 = 100;
+
+// every approval listing checked costs a few queries, so pruning pages are smaller
+const DEFAULT_PRUNE_LIMIT: u32 = 10;
+const MAX_PRUNE_LIMIT: u32 = 30;
+
+// page size used to look for the marketplace in a sellers cw721 operators
+const OPERATORS_PAGE: u32 = 30;
+
+const MAX_PROMOTION_DURATION: u64 = 30 * 24 * 60 * 60;
+
+// referral fees & staking discounts are basis points of the platform fee
+const MAX_BPS: u16 = 10_000;
+
+// bounds of the cached NftInfo, longer strings are cut & extra attributes dropped
+const MAX_METADATA_LEN: usize = 512;
+const MAX_METADATA_TRAITS: usize = 32;
+
+// seconds the ICS-721 packet of BuyNftOverIbc has to reach the other chain
+const ICS721_TIMEOUT: u64 = 10 * 60;
 
 // receive funds & buy NFT if funds are enough
 pub fn buy_nft(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
-    offering_id: String,
+    offering_id: u64,
+    recipient: Option<String>,
+    referrer: Option<String>,
+    ibc_receiver: Option<String>,
 ) -> Result<Response, ContractError> {
+    let recipient = match recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => info.sender.clone(),
+    };
+    let referrer = referrer
+        .map(|referrer| deps.api.addr_validate(&referrer))
+        .transpose()?;
+    if referrer.as_ref() == Some(&info.sender) {
+        return Err(ContractError::SelfReferral {});
+    }
+
     // load offering from storage if a given offering_id exist, if not, return NoMarketplaceOfferingWithGivenID
-    let off = OFFERINGS.may_load(deps.storage, &offering_id)?;
+    let off = offerings().may_load(deps.storage, offering_id)?;
     if off.is_none() {
         return Err(ContractError::NoMarketplaceOfferingWithGivenID { id: offering_id });
     }
 
     let off = off.unwrap();
-    if off.seller == info.sender {
-        return Err(ContractError::UnableToPurchaseMarketplaceItemYouSold {});
+    // the seller is checked too, a blocked wallet is not paid out of new sales
+    for address in [&info.sender, &recipient, &off.seller]
+        .iter()
+        .copied()
+        .chain(referrer.as_ref())
+    {
+        check_not_blocked(deps.as_ref(), &env.block, address)?;
     }
+    let ibc = ibc_receiver
+        .map(|receiver| ibc_delivery(deps.as_ref(), &env, &off, offering_id, &recipient, receiver))
+        .transpose()?;
 
-    let denom = CONTRACT_INFO.load(deps.storage)?.denom;
+    // an approval listing is only valid while the seller still owns & approved the token
+    let failure = purchase_failure(&off, &info.sender, &env.block)
+        .or_else(|| stale_listing(deps.as_ref(), &env, &off));
+    match failure {
+        Some(PurchaseFailure::BuyerIsSeller) => {
+            return Err(ContractError::UnableToPurchaseMarketplaceItemYouSold {})
+        }
+        Some(PurchaseFailure::Expired) => {
+            return Err(ContractError::OfferingExpired { id: offering_id })
+        }
+        Some(PurchaseFailure::OwnerChanged) | Some(PurchaseFailure::NotApproved) => {
+            return Err(ContractError::StaleListing { id: offering_id })
+        }
+        // never returned by these checks, blocked addresses were already rejected above, wash
+        // trades are checked below & the price when it is paid
+        Some(PurchaseFailure::Blocked)
+        | Some(PurchaseFailure::HoldingPeriod)
+        | Some(PurchaseFailure::PingPong)
+        | Some(PurchaseFailure::PriceUnavailable)
+        | None => {}
+    }
+
+    let wash_trade = wash_trade(deps.storage, &env.block, &off, &info.sender)?;
+    if let Some(reason) = &wash_trade {
+        if WASH_TRADING
+            .may_load(deps.storage)?
+            .unwrap_or_default()
+            .reject
+        {
+            return Err(ContractError::WashTrade {
+                reason: reason.as_str().to_string(),
+            });
+        }
+    }
+    record_sale(deps.storage, &env.block, &off, &info.sender)?;
+
+    // from here on list_price is what the buyer pays, USD listings are converted at this block
+    let off = Offering {
+        list_price: current_price(deps.as_ref(), &env, &off)?,
+        ..off
+    };
+    let denom = off.list_denom.clone();
 
     // check for enough coins (>= the listing price with the same denom)
-    assert_sent_exact_coin(
-        &info.funds,
-        Some(Coin::new(off.list_price.clone().u128(), &denom)),
-    )?;
+    let price = Coin::new(off.list_price.u128(), &denom);
+    let change = take_payment(&info, &price)?;
 
-    // DAO TAX AND PAYMENTS
-    let tax_rate = CONTRACT_INFO.load(deps.storage)?.platform_fee; // 5 = 5%
-    let dao_addr = CONTRACT_INFO.load(deps.storage)?.fee_receive_address;
+    // == PAYOUTS ==
+    // the ucraft -> the off.seller & the DAOs contract address. These are held until the
+    // cw721 contract accepts the transfer to the buyer, see transfer_reply
+    let split = purchase_payouts(deps.as_ref(), &off, referrer.as_ref())?;
+    let fee_discount_bps = split.fee_discount_bps;
+    let dao_tax_payment = split.platform_fee.amount.amount;
+    let seller_payment = split.seller.amount.amount;
+    let referral_payment = split
+        .referral
+        .as_ref()
+        .map(|referral| referral.amount.amount)
+        .unwrap_or_default();
+    let payouts = vec![split.seller, split.platform_fee];
 
-    // 1_000_000ucraft * 0.05 = 50000ucraft -> DAO [5 = 5/100 = 5%]
-    let mut dao_tax_payment = (off.list_price.clone().u128() / 100) * &tax_rate;
-    println!("dao_tax_payment: {}", &dao_tax_payment);
-    // 1_000_000ucraft - 50000 = 950_000ucraft -> seller
-    let mut seller_payment: u128 = off.list_price.clone().u128() - &dao_tax_payment;
+    //delete offering, the sale returns the listing deposit
+    let promotion_payment = remove_offering(deps.storage, offering_id, &off)?;
+    let deposit_refund = release_deposit(&off, off.seller.as_str());
 
-    // if the offering price is <100, we dont payt any tax on it bc it is too small.
-    // doing so errors out as 0ucraft send to seller, which doesn't work.
-    if off.list_price.clone().u128() < 100 {
-        dao_tax_payment = 0;
-        seller_payment = off.list_price.clone().u128();
+    // transfer nft to the buyer, or whoever they bought it for
+    let transfer = NftTransfer {
+        kind: TransferKind::Purchase,
+        offering: off.clone(),
+        recipient: recipient.clone(),
+        buyer: Some(info.sender.clone()).filter(|buyer| *buyer != recipient),
+        paid: vec![price],
+        payouts,
+        referral: split.referral,
+        wash_trade: wash_trade.is_some(),
+        ibc: ibc.clone(),
+        error: None,
+    };
+    let cw721_submsg = send_nft_transfer(deps.storage, offering_id, transfer)?;
+
+    // wash trades would only inflate the volume
+    if wash_trade.is_none() {
+        COLLECTION_VOLUME.update(
+            deps.storage,
+            off.contract_addr.as_str(),
+            |value| -> StdResult<Uint128> {
+                Ok(value.unwrap_or(Uint128::from(0u128))
+                    + Uint128::from(off.list_price.clone().u128()))
+            },
+        )?;
     }
 
-    // if the user sends more funds then the list price, return those to them on success (if any)
+    let mut res = Response::new()
+        .add_attribute("action", "buy_nft")
+        .add_submessage(cw721_submsg)
+        .add_messages(promotion_payment)
+        .add_messages(deposit_refund);
+
+    // if the user sends more funds then the list price, return those to them (if any)
+    if !change.is_empty() {
+        res = res.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: change,
+        });
+    }
+    BuyEvent {
+        offering_id,
+        collection: off.contract_addr.as_str(),
+        token_id: &off.token_id,
+        seller: off.seller.as_str(),
+        buyer: info.sender.as_str(),
+        recipient: recipient.as_str(),
+        amount: off.list_price,
+        denom: &denom,
+        platform_fee: dao_tax_payment,
+        seller_amount: seller_payment,
+        fee_discount_bps,
+        wash_trade: wash_trade.as_ref().map(PurchaseFailure::as_str),
+        referral: referrer
+            .as_ref()
+            .map(|referrer| (referrer.as_str(), referral_payment)),
+        ibc: ibc
+            .as_ref()
+            .map(|ibc| (ibc.msg.channel_id.as_str(), ibc.msg.receiver.as_str())),
+    }
+    .add_attributes(&mut res);
 
-    // PAYMENT COINS
-    // convert off.list_price to a vector of coins
-    let sellers_token_payment = vec![Coin::new(seller_payment, &denom)];
-    let daos_token_tax = vec![Coin::new(dao_tax_payment as u128, &denom)];
+    Ok(res)
+}
 
-    // == TRANSFERS ==
-    // send the ucraft -> the off.seller using BankMsg & the DAOs contract address
-    let transfer_seller_tokens = BankMsg::Send {
-        to_address: off.seller.to_string(),
-        amount: sellers_token_payment.clone(),
-    };
-    let transfer_daos_tokens = BankMsg::Send {
-        to_address: dao_addr.clone(),
-        amount: daos_token_tax.clone(),
+// why a BuyNft for `off` would currently fail, if it would. Shared with the SimulateBuy query
+pub fn purchase_failure(
+    off: &Offering,
+    buyer: &Addr,
+    block: &BlockInfo,
+) -> Option<PurchaseFailure> {
+    if off.seller == *buyer {
+        return Some(PurchaseFailure::BuyerIsSeller);
+    }
+    if off.expiration.is_expired(block) {
+        return Some(PurchaseFailure::Expired);
+    }
+    None
+}
+
+// why a sale of `off` to `buyer` looks like a wash trade, if it does: the seller bought the token
+// within the holding period, or the two already traded with each other within the pair window
+pub fn wash_trade(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    off: &Offering,
+    buyer: &Addr,
+) -> StdResult<Option<PurchaseFailure>> {
+    let config = WASH_TRADING.may_load(storage)?.unwrap_or_default();
+
+    if let Some(last_sale) = LAST_SALES.may_load(storage, (&off.contract_addr, &off.token_id))? {
+        if last_sale.buyer == off.seller
+            && block.time < last_sale.time.plus_seconds(config.holding_period)
+        {
+            return Ok(Some(PurchaseFailure::HoldingPeriod));
+        }
+    }
+    if let Some(traded) = PAIR_TRADES.may_load(storage, pair_key(&off.seller, buyer))? {
+        if block.time < traded.plus_seconds(config.pair_window) {
+            return Ok(Some(PurchaseFailure::PingPong));
+        }
+    }
+    Ok(None)
+}
+
+// the wash trade a BuyNft would be rejected for, when the safeguards reject them. For SimulateBuy
+pub fn rejected_wash_trade(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    off: &Offering,
+    buyer: &Addr,
+) -> StdResult<Option<PurchaseFailure>> {
+    match WASH_TRADING.may_load(storage)?.unwrap_or_default().reject {
+        true => wash_trade(storage, block, off, buyer),
+        false => Ok(None),
+    }
+}
+
+fn pair_key<'a>(a: &'a Addr, b: &'a Addr) -> (&'a Addr, &'a Addr) {
+    match a < b {
+        true => (a, b),
+        false => (b, a),
+    }
+}
+
+// remembered for the wash trade checks of later sales
+fn record_sale(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    off: &Offering,
+    buyer: &Addr,
+) -> StdResult<()> {
+    LAST_SALES.save(
+        storage,
+        (&off.contract_addr, &off.token_id),
+        &LastSale {
+            buyer: buyer.clone(),
+            time: block.time,
+        },
+    )?;
+    PAIR_TRADES.save(storage, pair_key(&off.seller, buyer), &block.time)
+}
+
+// drops what record_sale remembered of a refunded purchase, so it does not flag later trades. The
+// pair is only dropped while no later trade between them replaced the time
+fn forget_sale(storage: &mut dyn Storage, transfer: &NftTransfer) -> StdResult<()> {
+    let off = &transfer.offering;
+    let key = (&off.contract_addr, off.token_id.as_str());
+    let last_sale = match LAST_SALES.may_load(storage, key)? {
+        Some(last_sale) if last_sale.buyer == *transfer.payer() => last_sale,
+        _ => return Ok(()),
     };
+    LAST_SALES.remove(storage, key);
+
+    let pair = pair_key(&off.seller, transfer.payer());
+    if PAIR_TRADES.may_load(storage, pair)? == Some(last_sale.time) {
+        PAIR_TRADES.remove(storage, pair);
+    }
+    Ok(())
+}
+
+// a bought token can't be listed again by its buyer within the holding period
+fn check_holding_period(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    collection: &Addr,
+    token_id: &str,
+    seller: &Addr,
+) -> Result<(), ContractError> {
+    let config = WASH_TRADING.may_load(storage)?.unwrap_or_default();
+    if config.holding_period == 0 {
+        return Ok(());
+    }
+    if let Some(last_sale) = LAST_SALES.may_load(storage, (collection, token_id))? {
+        let until = last_sale.time.plus_seconds(config.holding_period);
+        if last_sale.buyer == *seller && block.time < until {
+            return Err(ContractError::HoldingPeriod { until });
+        }
+    }
+    Ok(())
+}
+
+// why an approval listing can no longer be bought, if it can't. NFTs held by the marketplace are
+// never stale. Shared with SimulateBuy & PruneListings
+pub fn stale_listing(deps: Deps, env: &Env, off: &Offering) -> Option<PurchaseFailure> {
+    if !off.non_custodial {
+        return None;
+    }
+
+    let collection = Cw721Contract(off.contract_addr.clone());
+    // a burned token can't be queried anymore, that makes the listing stale as well
+    match collection.owner_of(&deps.querier, &off.token_id, false) {
+        Ok(owner) if owner.owner == off.seller.as_str() => {}
+        _ => return Some(PurchaseFailure::OwnerChanged),
+    }
+
+    let spender = env.contract.address.to_string();
+    let approved = collection
+        .approval(&deps.querier, off.token_id.clone(), spender.clone(), None)
+        .is_ok();
+    if approved || is_operator(deps, &collection, &off.seller, &spender).unwrap_or(false) {
+        return None;
+    }
+    Some(PurchaseFailure::NotApproved)
+}
+
+// ApproveAll is not covered by the Approval query, so page through the owners operators
+fn is_operator(
+    deps: Deps,
+    collection: &Cw721Contract,
+    owner: &Addr,
+    operator: &str,
+) -> StdResult<bool> {
+    let mut start_after = None;
+    loop {
+        let page = collection.all_operators(
+            &deps.querier,
+            owner.as_str(),
+            false,
+            start_after,
+            Some(OPERATORS_PAGE),
+        )?;
+        if page.iter().any(|approval| approval.spender == operator) {
+            return Ok(true);
+        }
+        if page.len() < OPERATORS_PAGE as usize {
+            return Ok(false);
+        }
+        start_after = page.last().map(|approval| approval.spender.clone());
+    }
+}
 
-    // create transfer cw721 msg
-    let transfer_cw721_msg = Cw721ExecuteMsg::TransferNft {
-        recipient: info.sender.to_string(),
-        token_id: off.token_id.clone(),
+// the stale approval listings in one page of offerings, & where the next page starts
+#[allow(clippy::type_complexity)]
+pub fn stale_listings(
+    deps: Deps,
+    env: &Env,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<(Vec<(u64, Offering, PurchaseFailure)>, Option<u64>)> {
+    let limit = limit.unwrap_or(DEFAULT_PRUNE_LIMIT).min(MAX_PRUNE_LIMIT) as usize;
+    let page: Vec<(u64, Offering)> = offerings()
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit + 1)
+        .collect::<StdResult<_>>()?;
+
+    let next_start_after = match page.len() > limit {
+        true => page.get(limit - 1).map(|(id, _)| *id),
+        false => None,
     };
-    let exec_cw721_transfer = WasmMsg::Execute {
-        contract_addr: off.contract_addr.clone().into_string(),
-        msg: to_binary(&transfer_cw721_msg)?,
-        funds: vec![],
+    let stale = page
+        .into_iter()
+        .take(limit)
+        .filter_map(|(id, off)| stale_listing(deps, env, &off).map(|reason| (id, off, reason)))
+        .collect();
+    Ok((stale, next_start_after))
+}
+
+// the amount of the list denom the offering costs at this block. USD listings are converted with
+// the oracle price, rounded up so the seller gets at least their asking price
+pub fn current_price(deps: Deps, env: &Env, off: &Offering) -> Result<Uint128, ContractError> {
+    let usd_price = match off.usd_price {
+        Some(usd_price) => usd_price,
+        None => return Ok(off.list_price),
     };
 
-    // == SUBMIT TRANSFERS ==
-    // if everything is fine transfer ucraft to seller
-    let transfer_cosmos_msg_seller: CosmosMsg = transfer_seller_tokens.into();
-    let transfer_cosmos_msg_dao: CosmosMsg = transfer_daos_tokens.into();
-    // transfer nft to buyer
-    let cw721_transfer_cosmos_msg: CosmosMsg = exec_cw721_transfer.into();
+    let quote = query_usd_price(&deps.querier, &off.list_denom)?;
+    let max_price_age = CONTRACT_INFO.load(deps.storage)?.max_price_age;
+    if quote.updated_at.plus_seconds(max_price_age) < env.block.time {
+        return Err(ContractError::StalePrice {
+            denom: off.list_denom.clone(),
+            updated_at: quote.updated_at.seconds(),
+        });
+    }
+    if quote.price.is_zero() {
+        return Err(ContractError::NoPrice {
+            denom: off.list_denom.clone(),
+        });
+    }
 
-    let cosmos_msgs = vec![
-        SubMsg::new(transfer_cosmos_msg_seller),
-        SubMsg::new(transfer_cosmos_msg_dao),
-        SubMsg::new(cw721_transfer_cosmos_msg),
-    ];
+    // both are 18 decimal fixed point, so the atomics divide into a plain amount
+    let unit_price = quote.price.atomics();
+    let amount = usd_price
+        .atomics()
+        .checked_add(unit_price - Uint128::new(1))
+        .map_err(StdError::from)?
+        / unit_price;
+    Ok(amount)
+}
 
-    //delete offering
-    OFFERINGS.remove(deps.storage, &offering_id);
+pub struct PurchasePayouts {
+    pub seller: Payout,
+    pub platform_fee: Payout,
+    pub referral: Option<Payout>,
+    pub fee_discount_bps: u16,
+}
 
-    let price_string = format!("{} {}", off.list_price.clone().u128(), info.sender);
+// splits the list price of `off` between the seller, the DAO & the referrer. This is the only place
+// the fee is worked out, buy_nft & the SimulateBuy query both use it
+pub fn purchase_payouts(
+    deps: Deps,
+    off: &Offering,
+    referrer: Option<&Addr>,
+) -> StdResult<PurchasePayouts> {
+    let config = CONTRACT_INFO.load(deps.storage)?;
+    let tax_rate = config.platform_fee; // 5 = 5%
+    let list_price = off.list_price.u128();
 
-    COLLECTION_VOLUME.update(
-        deps.storage,
-        &off.contract_addr.to_string(),
-        |value| -> StdResult<Uint128> {
-            Ok(
-                value.unwrap_or(Uint128::from(0u128))
-                    + Uint128::from(off.list_price.clone().u128()),
-            )
+    // 1_000_000ucraft * 0.05 = 50000ucraft -> DAO [5 = 5/100 = 5%]
+    let mut dao_tax_payment = (list_price / 100) * tax_rate;
+    // 1_000_000ucraft - 50000 = 950_000ucraft -> seller
+    let mut seller_payment: u128 = list_price - dao_tax_payment;
+
+    // if the offering price is <100, we dont payt any tax on it bc it is too small.
+    // doing so errors out as 0ucraft send to seller, which doesn't work.
+    if list_price < 100 {
+        dao_tax_payment = 0;
+        seller_payment = list_price;
+    }
+
+    // the fee comes out of the sellers proceeds, so their stake is what discounts it
+    let fee_discount_bps = fee_discount(deps, &off.seller)?;
+    let discount = Uint128::new(dao_tax_payment)
+        .multiply_ratio(fee_discount_bps, MAX_BPS)
+        .u128();
+    dao_tax_payment -= discount;
+    seller_payment += discount;
+
+    // the referrer is paid out of the platform fee, the seller gets the same either way
+    let referral = match referrer {
+        Some(referrer) => {
+            let share = referral_share(deps.storage, referrer)?;
+            let amount = Uint128::new(dao_tax_payment).multiply_ratio(share, MAX_BPS);
+            dao_tax_payment -= amount.u128();
+            Some(Payout {
+                recipient: referrer.clone(),
+                amount: Coin::new(amount.u128(), &off.list_denom),
+            })
+        }
+        None => None,
+    };
+
+    Ok(PurchasePayouts {
+        seller: Payout {
+            recipient: off.seller.clone(),
+            amount: Coin::new(seller_payment, &off.list_denom),
         },
-    )?;
+        platform_fee: Payout {
+            recipient: deps.api.addr_validate(&config.fee_receive_address)?,
+            amount: Coin::new(dao_tax_payment, &off.list_denom),
+        },
+        referral,
+        fee_discount_bps,
+    })
+}
 
-    Ok(Response::new()
-        .add_attribute("action", "buy_nft")
-        .add_attribute("buyer", info.sender.to_string())
-        .add_attribute("seller", off.seller)
-        .add_attribute("total_paid_price", price_string)
-        .add_attribute("tax_paid", dao_tax_payment.to_string())
-        .add_attribute("seller_receive", seller_payment.to_string())
-        .add_attribute("token_id", off.token_id)
-        .add_attribute("contract_addr", off.contract_addr)
-        .add_submessages(cosmos_msgs))
+// the platform fee discount of `trader` in basis points, from the highest staking tier their
+// delegations reach. No tiers means no discount & no staking query
+pub fn fee_discount(deps: Deps, trader: &Addr) -> StdResult<u16> {
+    let fee_tiers = match FEE_TIERS.may_load(deps.storage)? {
+        Some(fee_tiers) if !fee_tiers.tiers.is_empty() => fee_tiers,
+        _ => return Ok(0),
+    };
+
+    let staked: Uint128 = deps
+        .querier
+        .query_all_delegations(trader)?
+        .into_iter()
+        .filter(|delegation| delegation.amount.denom == fee_tiers.denom)
+        .map(|delegation| delegation.amount.amount)
+        .sum();
+
+    Ok(fee_tiers
+        .tiers
+        .iter()
+        .rev()
+        .find(|tier| staked >= tier.min_stake)
+        .map(|tier| tier.discount_bps)
+        .unwrap_or_default())
+}
+
+// the referral fee share the referrer gets, in basis points of the platform fee
+pub fn referral_share(storage: &dyn Storage, referrer: &Addr) -> StdResult<u16> {
+    let share = CONTRACT_INFO.load(storage)?.referral_fee_bps;
+    Ok(match REFERRER_LIMITS.may_load(storage, referrer)? {
+        Some(limit) => share.min(limit),
+        None => share,
+    })
 }
 
 // gets NFT from a 721 contract
 pub fn receive_nft(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     rcv_msg: Cw721ReceiveMsg,
 ) -> Result<Response, ContractError> {
     let msg: SellNft = from_binary(&rcv_msg.msg)?;
+    let res = list_received(
+        deps,
+        &env,
+        info.sender,
+        rcv_msg.token_id,
+        &rcv_msg.sender,
+        msg,
+        None,
+    )?;
+
+    Ok(res.add_attribute("action", "sell_nft"))
+}
+
+// lists the NFTs the ICS-721 bridge minted to the marketplace. The packet fails & the NFTs go back
+// to the other chain if any of them can't be listed
+pub fn ics721_receive_callback(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: Ics721ReceiveCallbackMsg,
+) -> Result<Response, ContractError> {
+    let bridge = check_ics721_bridge(deps.as_ref(), &info.sender)?;
+    let (seller, sell_msg) = from_binary::<BridgedSellNft>(&msg.msg)?.into_parts();
+    let packet = msg.original_packet;
+
+    // make sure the bridge minted the class on the contract it named
+    let collection = match query_nft_contract(&deps.querier, &bridge, &packet.class_id)? {
+        Some(collection) if collection == msg.nft_contract => collection,
+        _ => {
+            return Err(ContractError::Unauthorized {
+                msg: format!(
+                    "{} is not the NFT contract of class {}",
+                    msg.nft_contract, packet.class_id
+                ),
+            })
+        }
+    };
+
+    // anyone on the other chain can name any seller, so they must have allowed the sender
+    let seller = deps.api.addr_validate(&seller)?;
+    let channel_id = source_channel(&bridge, &packet.class_id).unwrap_or_default();
+    if !BRIDGED_SENDERS.has(deps.storage, (&seller, channel_id, &packet.sender)) {
+        return Err(ContractError::BridgedSenderNotAllowed {
+            seller: seller.into_string(),
+            channel_id: channel_id.to_string(),
+            sender: packet.sender,
+        });
+    }
+
+    let mut res = Response::new().add_attribute("action", "sell_nft");
+    for token_id in packet.token_ids {
+        let listed = list_received(
+            deps.branch(),
+            &env,
+            collection.clone(),
+            token_id,
+            seller.as_str(),
+            sell_msg.clone(),
+            Some(&packet.class_id),
+        )?;
+        res = res.add_events(listed.events);
+    }
+
+    Ok(res)
+}
+
+pub fn allow_bridged_sender(
+    deps: DepsMut,
+    info: MessageInfo,
+    channel_id: String,
+    sender: String,
+    allowed: bool,
+) -> Result<Response, ContractError> {
+    let key = (&info.sender, channel_id.as_str(), sender.as_str());
+    let action = if allowed {
+        BRIDGED_SENDERS.save(deps.storage, key, &Empty {})?;
+        "allow_bridged_sender"
+    } else {
+        BRIDGED_SENDERS.remove(deps.storage, key);
+        "revoke_bridged_sender"
+    };
+
+    let mut res = Response::new().add_attribute("action", action);
+    BridgedSenderEvent {
+        seller: info.sender.as_str(),
+        channel_id: &channel_id,
+        sender: &sender,
+        allowed,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+// lists a token the marketplace was sent, the response only carries the ListEvent
+fn list_received(
+    deps: DepsMut,
+    env: &Env,
+    collection: Addr,
+    token_id: String,
+    seller: &str,
+    msg: SellNft,
+    class_id: Option<&str>,
+) -> Result<Response, ContractError> {
+    // anyone can send a ReceiveNft, so ask the sending collection if we really hold the token now
+    let collection = Cw721Contract(collection);
+    let owner = collection.owner_of(&deps.querier, &token_id, false)?;
+    if owner.owner != env.contract.address {
+        return Err(ContractError::NftNotReceived {
+            collection: collection.addr().into_string(),
+            token_id,
+        });
+    }
+    let seller = deps.api.addr_validate(seller)?;
+    check_not_blocked(deps.as_ref(), &env.block, &seller)?;
+    check_holding_period(
+        deps.storage,
+        &env.block,
+        &collection.addr(),
+        &token_id,
+        &seller,
+    )?;
+    verify_collection(deps.as_ref(), &collection)?;
+    let config = CONTRACT_INFO.load(deps.storage)?;
+    check_listing_cap(deps.as_ref(), &seller, config.max_listings_per_seller)?;
+
+    // SendNft can't carry funds, so the deposit comes out of the sellers prepaid deposits
+    if !config.listing_deposit.is_zero() {
+        let prepaid = PREPAID_DEPOSITS
+            .may_load(deps.storage, &seller)?
+            .unwrap_or_default();
+        let left = prepaid.checked_sub(config.listing_deposit).map_err(|_| {
+            ContractError::ListingDepositRequired {
+                deposit: Coin::new(config.listing_deposit.u128(), &config.denom).to_string(),
+            }
+        })?;
+        PREPAID_DEPOSITS.save(deps.storage, &seller, &left)?;
+    }
+    let metadata = fetch_metadata(deps.as_ref(), &collection, &token_id);
 
     // check if same token Id form same original contract is already on sale
     // get OFFERING_COUNT
-    let id = increment_offerings(deps.storage)?.to_string();
+    let id = increment_offerings(deps.storage)?;
 
     // save Offering
-    let denom = CONTRACT_INFO.load(deps.storage)?.denom;
+    let denom = config.denom;
+
+    if msg.expiration.is_expired(&env.block) {
+        return Err(ContractError::InvalidExpiration {});
+    }
+
+    let mut off = Offering {
+        contract_addr: collection.addr(),
+        list_denom: denom,
+        token_id,
+        seller,
+        list_price: msg.list_price,
+        expiration: msg.expiration,
+        usd_price: msg.usd_price,
+        non_custodial: false,
+        deposit: config.listing_deposit,
+        listed_height: env.block.height,
+        listed_at: env.block.time,
+    };
+    if off.usd_price.is_some() {
+        off.list_price = current_price(deps.as_ref(), env, &off)?;
+    }
 
     // done here & in the update_listing_price method. Fixes issue with tax rates if price is too low
-    if &msg.list_price < &Uint128::from(1_000_000u128) {
+    if off.list_price < Uint128::from(1_000_000u128) {
+        return Err(ContractError::ListingPriceTooLow {});
+    }
+
+    offerings().save(deps.storage, id, &off)?;
+    if let Some(metadata) = metadata {
+        OFFERING_METADATA.save(deps.storage, id, &metadata)?;
+    }
+
+    let mut res = Response::new();
+    ListEvent {
+        offering_id: id,
+        collection: off.contract_addr.as_str(),
+        token_id: &off.token_id,
+        seller: off.seller.as_str(),
+        amount: off.list_price,
+        denom: &off.list_denom,
+        usd_price: off.usd_price,
+        non_custodial: false,
+        fee_discount_bps: fee_discount(deps.as_ref(), &off.seller)?,
+        deposit: off.deposit,
+        class_id,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+// lists a token which stays with the seller. They must have approved the marketplace for it with
+// Approve or ApproveAll, buy_nft checks this again before moving the token
+pub fn list(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    collection: String,
+    token_id: String,
+    price: Uint128,
+) -> Result<Response, ContractError> {
+    let collection = Cw721Contract(deps.api.addr_validate(&collection)?);
+    check_not_blocked(deps.as_ref(), &env.block, &info.sender)?;
+    check_holding_period(
+        deps.storage,
+        &env.block,
+        &collection.addr(),
+        &token_id,
+        &info.sender,
+    )?;
+    verify_collection(deps.as_ref(), &collection)?;
+
+    // same minimum as receive_nft
+    if price < Uint128::from(1_000_000u128) {
         return Err(ContractError::ListingPriceTooLow {});
     }
 
+    let config = CONTRACT_INFO.load(deps.storage)?;
+    check_listing_cap(deps.as_ref(), &info.sender, config.max_listings_per_seller)?;
+    // approval listings pay their deposit with the List message
+    let change = if config.listing_deposit.is_zero() {
+        nonpayable(&info)?;
+        vec![]
+    } else {
+        take_payment(
+            &info,
+            &Coin::new(config.listing_deposit.u128(), &config.denom),
+        )?
+    };
+
     let off = Offering {
-        contract_addr: info.sender.clone(),
-        list_denom: denom,
-        token_id: rcv_msg.token_id,
-        seller: deps.api.addr_validate(&rcv_msg.sender)?,
-        list_price: msg.list_price,
+        token_id,
+        list_denom: config.denom,
+        contract_addr: collection.addr(),
+        seller: info.sender,
+        list_price: price,
+        expiration: Expiration::Never {},
+        usd_price: None,
+        non_custodial: true,
+        deposit: config.listing_deposit,
+        listed_height: env.block.height,
+        listed_at: env.block.time,
+    };
+    match stale_listing(deps.as_ref(), &env, &off) {
+        Some(PurchaseFailure::NotApproved) => {
+            return Err(ContractError::MarketplaceNotApproved {
+                collection: off.contract_addr.into_string(),
+                token_id: off.token_id,
+            })
+        }
+        Some(_) => {
+            return Err(ContractError::Unauthorized {
+                msg: "You do not own this token, so you can not list it.".to_string(),
+            })
+        }
+        None => {}
+    }
+
+    let mut res = Response::new().add_attribute("action", "list");
+    if !change.is_empty() {
+        res = res.add_message(BankMsg::Send {
+            to_address: off.seller.to_string(),
+            amount: change,
+        });
+    }
+
+    // a token is only listed once. A listing by a previous owner is stale by now & replaced
+    let key = (&off.contract_addr, off.token_id.as_str());
+    if let Some(old_id) = APPROVAL_LISTINGS.may_load(deps.storage, key)? {
+        let old = offerings().load(deps.storage, old_id)?;
+        if old.seller == off.seller {
+            return Err(ContractError::AlreadyListed { id: old_id });
+        }
+        res = prune_listing(
+            deps.storage,
+            res,
+            old_id,
+            &old,
+            PurchaseFailure::OwnerChanged,
+        )?;
+    }
+
+    let metadata = fetch_metadata(deps.as_ref(), &collection, &off.token_id);
+    let id = increment_offerings(deps.storage)?;
+    offerings().save(deps.storage, id, &off)?;
+    APPROVAL_LISTINGS.save(deps.storage, key, &id)?;
+    if let Some(metadata) = metadata {
+        OFFERING_METADATA.save(deps.storage, id, &metadata)?;
+    }
+
+    ListEvent {
+        offering_id: id,
+        collection: off.contract_addr.as_str(),
+        token_id: &off.token_id,
+        seller: off.seller.as_str(),
+        amount: off.list_price,
+        denom: &off.list_denom,
+        usd_price: None,
+        non_custodial: true,
+        fee_discount_bps: fee_discount(deps.as_ref(), &off.seller)?,
+        deposit: off.deposit,
+        class_id: None,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+pub fn prune_listings(
+    deps: DepsMut,
+    env: Env,
+    limit: Option<u32>,
+    start_after: Option<u64>,
+) -> Result<Response, ContractError> {
+    let (stale, next_start_after) = stale_listings(deps.as_ref(), &env, start_after, limit)?;
+
+    let mut res = Response::new().add_attribute("action", "prune_listings");
+    let mut listings = vec![];
+    for (offering_id, off, reason) in stale {
+        res = prune_listing(deps.storage, res, offering_id, &off, reason.clone())?;
+        listings.push(StaleListing {
+            offering_id: offering_id.into(),
+            reason,
+        });
+    }
+
+    let page = StaleListingsResponse {
+        listings,
+        next_start_after: next_start_after.map(OfferingId),
     };
+    Ok(res
+        .add_attribute("pruned", page.listings.len().to_string())
+        .add_attribute(
+            "next_start_after",
+            page.next_start_after
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+        )
+        .set_data(to_binary(&page)?))
+}
+
+// removes an approval listing which can no longer be bought. Its promotion, if any, goes to the
+// fee receiver as it does for any other offering leaving the marketplace. So does the listing
+// deposit, the seller left the listing behind
+fn prune_listing(
+    storage: &mut dyn Storage,
+    res: Response,
+    offering_id: u64,
+    off: &Offering,
+    reason: PurchaseFailure,
+) -> Result<Response, ContractError> {
+    let fee_receiver = CONTRACT_INFO.load(storage)?.fee_receive_address;
+    let mut res = res
+        .add_messages(remove_offering(storage, offering_id, off)?)
+        .add_messages(release_deposit(off, &fee_receiver));
+    PruneEvent {
+        offering_id,
+        collection: off.contract_addr.as_str(),
+        token_id: &off.token_id,
+        seller: off.seller.as_str(),
+        reason: reason.as_str(),
+    }
+    .add_attributes(&mut res);
+    Ok(res)
+}
+
+// the NftInfo of the token, cut down to the metadata bounds. None when the collection does not
+// support the metadata extension, which should not stop it from being listed
+fn fetch_metadata(deps: Deps, collection: &Cw721Contract, token_id: &str) -> Option<NftMetadata> {
+    let info: NftInfoResponse<Option<NftMetadata>> =
+        collection.nft_info(&deps.querier, token_id).ok()?;
+    let extension = info.extension.unwrap_or_default();
+
+    let bound = |value: Option<String>| value.map(|value| truncate(value, MAX_METADATA_LEN));
+    Some(NftMetadata {
+        token_uri: bound(info.token_uri),
+        name: bound(extension.name),
+        description: bound(extension.description),
+        image: bound(extension.image),
+        external_url: bound(extension.external_url),
+        animation_url: bound(extension.animation_url),
+        attributes: extension.attributes.map(|attributes| {
+            attributes
+                .into_iter()
+                .take(MAX_METADATA_TRAITS)
+                .map(|attribute| Trait {
+                    display_type: bound(attribute.display_type),
+                    trait_type: truncate(attribute.trait_type, MAX_METADATA_LEN),
+                    value: truncate(attribute.value, MAX_METADATA_LEN),
+                })
+                .collect()
+        }),
+    })
+}
+
+fn truncate(mut value: String, max: usize) -> String {
+    if value.len() > max {
+        let mut end = max;
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        value.truncate(end);
+    }
+    value
+}
+
+pub fn refresh_metadata(deps: DepsMut, offering_id: u64) -> Result<Response, ContractError> {
+    let off = offerings().load(deps.storage, offering_id)?;
+    let collection = Cw721Contract(off.contract_addr.clone());
+
+    let metadata = fetch_metadata(deps.as_ref(), &collection, &off.token_id);
+    match &metadata {
+        Some(metadata) => OFFERING_METADATA.save(deps.storage, offering_id, metadata)?,
+        None => OFFERING_METADATA.remove(deps.storage, offering_id),
+    }
+
+    let mut res = Response::new().add_attribute("action", "refresh_metadata");
+    RefreshMetadataEvent {
+        offering_id,
+        collection: off.contract_addr.as_str(),
+        token_id: &off.token_id,
+        found: metadata.is_some(),
+    }
+    .add_attributes(&mut res);
 
-    OFFERINGS.save(deps.storage, &id, &off)?;
+    Ok(res)
+}
 
-    let price_string = format!("{} {}", msg.list_price, "ucraft");
+// while verify_collections is set the collection must be registered & report the same ContractInfo
+fn verify_collection(deps: Deps, collection: &Cw721Contract) -> Result<(), ContractError> {
+    if !CONTRACT_INFO.load(deps.storage)?.verify_collections {
+        return Ok(());
+    }
 
-    Ok(Response::new()
-        .add_attribute("action", "sell_nft")
-        .add_attribute("original_contract", info.sender)
-        .add_attribute("seller", off.seller)
-        .add_attribute("list_price", price_string)
-        .add_attribute("token_id", off.token_id))
+    let registered = COLLECTIONS
+        .may_load(deps.storage, &collection.0)?
+        .ok_or_else(|| ContractError::CollectionNotRegistered {
+            collection: collection.0.to_string(),
+        })?;
+    if collection.contract_info(&deps.querier)? != registered {
+        return Err(ContractError::CollectionInfoMismatch {
+            collection: collection.0.to_string(),
+        });
+    }
+    Ok(())
 }
 
 pub fn withdraw_offering(
     deps: DepsMut,
     info: MessageInfo,
-    offering_id: String,
+    offering_id: u64,
 ) -> Result<Response, ContractError> {
     // check if token_id is currently sold by the requesting address
-    let off = OFFERINGS.load(deps.storage, &offering_id)?;
+    let off = offerings().load(deps.storage, offering_id)?;
     if off.seller == info.sender {
-        // transfer token back to original owner
-        let transfer_cw721_msg = Cw721ExecuteMsg::TransferNft {
-            recipient: off.seller.clone().into_string(),
-            token_id: off.token_id.clone(),
-        };
-
-        let exec_cw721_transfer = WasmMsg::Execute {
-            contract_addr: off.contract_addr.into_string(),
-            msg: to_binary(&transfer_cw721_msg)?,
-            funds: vec![],
-        };
-
-        let cw721_transfer_cosmos_msg: CosmosMsg = exec_cw721_transfer.into();
-        let cw721_submsg = SubMsg::new(cw721_transfer_cosmos_msg);
-
         // remove offering
-        OFFERINGS.remove(deps.storage, &offering_id);
+        let promotion_payment = remove_offering(deps.storage, offering_id, &off)?;
 
-        return Ok(Response::new()
+        let mut res = Response::new()
             .add_attribute("action", "withdraw_nft")
-            .add_attribute("seller", info.sender)
-            .add_attribute("offering_id", offering_id)
-            .add_submessage(cw721_submsg));
+            .add_messages(promotion_payment)
+            .add_messages(release_deposit(&off, off.seller.as_str()));
+        WithdrawEvent {
+            offering_id,
+            collection: off.contract_addr.as_str(),
+            token_id: &off.token_id,
+            seller: off.seller.as_str(),
+            forced: false,
+        }
+        .add_attributes(&mut res);
+
+        // transfer token back to original owner
+        let cw721_submsg = return_nft(deps.storage, offering_id, off)?;
+
+        return Ok(res.add_submessages(cw721_submsg));
     }
     Err(ContractError::Unauthorized {
         msg: "You are not the seller of this token, so you can not withdraw it.".to_string(),
@@ -204,11 +1069,11 @@ pub fn withdraw_offering(
 pub fn update_listing_price(
     deps: DepsMut,
     info: MessageInfo,
-    offering_id: String,
+    offering_id: u64,
     new_price: Uint128,
 ) -> Result<Response, ContractError> {
     // check if offering_id exist & they are the seller of it
-    let off = OFFERINGS.load(deps.storage, &offering_id)?;
+    let off = offerings().load(deps.storage, offering_id)?;
     if off.seller != info.sender {
         // println!("{}, {}", off.seller, info.sender);
         return Err(ContractError::Unauthorized {
@@ -223,53 +1088,229 @@ pub fn update_listing_price(
         return Err(ContractError::ListingPriceTooLow {});
     }
 
-    // update offering
+    // update offering, a USD listing becomes a fixed price one
     let updated_offering = Offering {
         contract_addr: off.contract_addr,
         list_denom: off.list_denom,
         token_id: off.token_id,
         seller: off.seller,
         list_price: new_price,
+        expiration: off.expiration,
+        usd_price: None,
+        non_custodial: off.non_custodial,
+        deposit: off.deposit,
+        listed_height: off.listed_height,
+        listed_at: off.listed_at,
     };
 
-    OFFERINGS.save(deps.storage, &offering_id, &updated_offering)?;
+    offerings().save(deps.storage, offering_id, &updated_offering)?;
+
+    let mut res = Response::new().add_attribute("action", "update_listing_price");
+    UpdatePriceEvent {
+        offering_id,
+        collection: updated_offering.contract_addr.as_str(),
+        token_id: &updated_offering.token_id,
+        old_amount: old_price,
+        new_amount: new_price,
+        denom: &updated_offering.list_denom,
+    }
+    .add_attributes(&mut res);
 
-    Ok(Response::new()
-        .add_attribute("action", "update_listing_price")
-        .add_attribute("old_price", old_price.to_string())
-        .add_attribute("new_price", new_price.to_string()))
+    Ok(res)
 }
 
-pub fn update_fee_receiver_address(
+// takes down an expired offering, anyone can. The seller gets the NFT back either way, the listing
+// deposit only when they reclaim it themselves. Otherwise it goes to the fee receiver
+pub fn reclaim_expired(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
-    new_address: String,
+    offering_id: u64,
 ) -> Result<Response, ContractError> {
-    check_executer_is_authorized_fee_receiver(deps.as_ref(), info.sender.to_string())?;
+    let off = match offerings().may_load(deps.storage, offering_id)? {
+        Some(off) => off,
+        None => return Err(ContractError::NoMarketplaceOfferingWithGivenID { id: offering_id }),
+    };
+    if !off.expiration.is_expired(&env.block) {
+        return Err(ContractError::OfferingNotExpired { id: offering_id });
+    }
 
-    // update the contract fee in memory
-    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
-    contract_info.fee_receive_address = new_address.clone();
+    let deposit_recipient = if info.sender == off.seller {
+        off.seller.to_string()
+    } else {
+        CONTRACT_INFO.load(deps.storage)?.fee_receive_address
+    };
+    let promotion_payment = remove_offering(deps.storage, offering_id, &off)?;
 
-    // save to state
-    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+    let mut res = Response::new()
+        .add_attribute("action", "reclaim_expired")
+        .add_messages(promotion_payment)
+        .add_messages(release_deposit(&off, &deposit_recipient));
+    ReclaimEvent {
+        offering_id,
+        collection: off.contract_addr.as_str(),
+        token_id: &off.token_id,
+        seller: off.seller.as_str(),
+        reclaimer: info.sender.as_str(),
+        deposit_recipient: &deposit_recipient,
+        deposit: off.deposit,
+    }
+    .add_attributes(&mut res);
+
+    let cw721_submsg = return_nft(deps.storage, offering_id, off)?;
+    Ok(res.add_submessages(cw721_submsg))
+}
+
+pub fn prepay_deposit(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let denom = CONTRACT_INFO.load(deps.storage)?.denom;
+    let amount = must_pay(&info, &denom)?;
+    let balance = PREPAID_DEPOSITS.update(
+        deps.storage,
+        &info.sender,
+        |balance| -> StdResult<Uint128> { Ok(balance.unwrap_or_default() + amount) },
+    )?;
+
+    let mut res = Response::new().add_attribute("action", "prepay_deposit");
+    PrepayDepositEvent {
+        seller: info.sender.as_str(),
+        amount,
+        balance,
+        denom: &denom,
+    }
+    .add_attributes(&mut res);
 
-    Ok(Response::new()
-        .add_attribute("action", "update_fee_receiver_address")
-        .add_attribute("new_address", new_address)
-        // since you have to run this as the fee receiver, you can use your own address as the old address, this is the old address
-        .add_attribute("old_address", info.sender))
+    Ok(res)
 }
 
-pub fn update_platform_fee(
+pub fn withdraw_prepaid_deposit(
     deps: DepsMut,
     info: MessageInfo,
-    new_fee: u128,
 ) -> Result<Response, ContractError> {
-    check_executer_is_authorized_fee_receiver(deps.as_ref(), info.sender.to_string())?;
+    let amount = PREPAID_DEPOSITS
+        .may_load(deps.storage, &info.sender)?
+        .unwrap_or_default();
+    if amount.is_zero() {
+        return Err(ContractError::NoPrepaidDeposit {});
+    }
+    PREPAID_DEPOSITS.remove(deps.storage, &info.sender);
+    let denom = CONTRACT_INFO.load(deps.storage)?.denom;
 
-    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
-    let current_platform_fee = contract_info.platform_fee;
+    let mut res = Response::new().add_attribute("action", "withdraw_prepaid_deposit");
+    WithdrawPrepaidDepositEvent {
+        seller: info.sender.as_str(),
+        amount,
+        denom: &denom,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res.add_message(BankMsg::Send {
+        to_address: info.sender.into_string(),
+        amount: vec![Coin::new(amount.u128(), denom)],
+    }))
+}
+
+// fails once the seller has max listings, counted through the seller index. Zero is no limit
+fn check_listing_cap(deps: Deps, seller: &Addr, max: u32) -> Result<(), ContractError> {
+    if max == 0 {
+        return Ok(());
+    }
+    let listed = offerings()
+        .idx
+        .seller
+        .prefix(seller.clone())
+        .keys(deps.storage, None, None, Order::Ascending)
+        .take(max as usize)
+        .count();
+    if listed >= max as usize {
+        return Err(ContractError::TooManyListings { max });
+    }
+    Ok(())
+}
+
+// pays out the listing deposit of an offering which is leaving the marketplace, if it holds one
+fn release_deposit(off: &Offering, recipient: &str) -> Option<BankMsg> {
+    if off.deposit.is_zero() {
+        return None;
+    }
+    Some(BankMsg::Send {
+        to_address: recipient.to_string(),
+        amount: vec![Coin::new(off.deposit.u128(), &off.list_denom)],
+    })
+}
+
+pub fn update_listing_limits(
+    deps: DepsMut,
+    deposit: Uint128,
+    max_per_seller: u32,
+) -> Result<Response, ContractError> {
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let old_deposit = contract_info.listing_deposit;
+    let old_max_per_seller = contract_info.max_listings_per_seller;
+    contract_info.listing_deposit = deposit;
+    contract_info.max_listings_per_seller = max_per_seller;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    let mut res = Response::new().add_attribute("action", "update_listing_limits");
+    UpdateListingLimitsEvent {
+        old_deposit,
+        new_deposit: deposit,
+        old_max_per_seller,
+        new_max_per_seller: max_per_seller,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+pub fn update_ics721_bridge(
+    deps: DepsMut,
+    address: Option<String>,
+) -> Result<Response, ContractError> {
+    let address = address
+        .map(|address| deps.api.addr_validate(&address))
+        .transpose()?;
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let old_address = contract_info.ics721_bridge;
+    contract_info.ics721_bridge = address.as_ref().map(Addr::to_string);
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    let mut res = Response::new().add_attribute("action", "update_ics721_bridge");
+    UpdateIcs721BridgeEvent {
+        old_address: old_address.as_deref(),
+        new_address: address.as_ref().map(Addr::as_str),
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+pub fn update_fee_receiver_address(
+    deps: DepsMut,
+    new_address: String,
+) -> Result<Response, ContractError> {
+    let new_address = deps.api.addr_validate(&new_address)?.to_string();
+
+    // update the contract fee in memory
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let old_address = contract_info.fee_receive_address;
+    contract_info.fee_receive_address = new_address.clone();
+
+    // save to state
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    let mut res = Response::new().add_attribute("action", "update_fee_receiver_address");
+    UpdateFeeReceiverEvent {
+        old_address: &old_address,
+        new_address: &new_address,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+pub fn update_platform_fee(deps: DepsMut, new_fee: u128) -> Result<Response, ContractError> {
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let current_platform_fee = contract_info.platform_fee;
 
     if new_fee > 100 {
         return Err(ContractError::PlatformFeeToHigh {});
@@ -278,53 +1319,1044 @@ pub fn update_platform_fee(
     contract_info.platform_fee = new_fee;
     CONTRACT_INFO.save(deps.storage, &contract_info)?;
 
-    Ok(Response::new()
-        .add_attribute("action", "update_fee_receiver_address")
-        .add_attribute("new_fee", new_fee.to_string())
-        .add_attribute("old_fee", current_platform_fee.to_string()))
+    let mut res = Response::new().add_attribute("action", "update_platform_fee");
+    UpdatePlatformFeeEvent {
+        old_fee: current_platform_fee,
+        new_fee,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+pub fn register_collection(
+    deps: DepsMut,
+    address: String,
+    name: String,
+    symbol: String,
+) -> Result<Response, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    COLLECTIONS.save(
+        deps.storage,
+        &address,
+        &Cw721ContractInfo {
+            name: name.clone(),
+            symbol: symbol.clone(),
+        },
+    )?;
+
+    let mut res = Response::new().add_attribute("action", "register_collection");
+    RegisterCollectionEvent {
+        collection: address.as_str(),
+        name: &name,
+        symbol: &symbol,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+// existing listings of the collection stay up, only new ones are rejected
+pub fn unregister_collection(deps: DepsMut, address: String) -> Result<Response, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    COLLECTIONS.remove(deps.storage, &address);
+
+    let mut res = Response::new().add_attribute("action", "unregister_collection");
+    UnregisterCollectionEvent {
+        collection: address.as_str(),
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+pub fn update_verify_collections(deps: DepsMut, enabled: bool) -> Result<Response, ContractError> {
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let old_enabled = contract_info.verify_collections;
+    contract_info.verify_collections = enabled;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    let mut res = Response::new().add_attribute("action", "update_verify_collections");
+    UpdateVerifyCollectionsEvent {
+        old_enabled,
+        new_enabled: enabled,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+pub fn block(
+    deps: DepsMut,
+    env: Env,
+    address: String,
+    reason: Option<String>,
+    expires: Expiration,
+) -> Result<Response, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::InvalidExpiration {});
+    }
+    BLOCKLIST.save(
+        deps.storage,
+        &address,
+        &BlockEntry {
+            reason: reason.clone(),
+            expires,
+        },
+    )?;
+
+    let mut res = Response::new().add_attribute("action", "block");
+    BlockEvent {
+        address: address.as_str(),
+        reason: reason.as_deref(),
+        expires: &expires,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+pub fn unblock(deps: DepsMut, address: String) -> Result<Response, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    BLOCKLIST.remove(deps.storage, &address);
+
+    let mut res = Response::new().add_attribute("action", "unblock");
+    UnblockEvent {
+        address: address.as_str(),
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+pub fn update_referral_fee(deps: DepsMut, bps: u16) -> Result<Response, ContractError> {
+    if bps > MAX_BPS {
+        return Err(ContractError::ReferralFeeTooHigh { max: MAX_BPS });
+    }
+
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let old_bps = contract_info.referral_fee_bps;
+    contract_info.referral_fee_bps = bps;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    let mut res = Response::new().add_attribute("action", "update_referral_fee");
+    UpdateReferralFeeEvent {
+        old_bps,
+        new_bps: bps,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+pub fn update_fee_tiers(
+    deps: DepsMut,
+    denom: String,
+    mut tiers: Vec<FeeTier>,
+) -> Result<Response, ContractError> {
+    if tiers.iter().any(|tier| tier.discount_bps > MAX_BPS) {
+        return Err(ContractError::FeeDiscountTooHigh { max: MAX_BPS });
+    }
+    tiers.sort_by_key(|tier| tier.min_stake);
+
+    let old = FEE_TIERS.may_load(deps.storage)?;
+    FEE_TIERS.save(
+        deps.storage,
+        &FeeTiers {
+            denom: denom.clone(),
+            tiers: tiers.clone(),
+        },
+    )?;
+
+    let mut res = Response::new().add_attribute("action", "update_fee_tiers");
+    UpdateFeeTiersEvent {
+        old: old.as_ref(),
+        new_denom: &denom,
+        new_tiers: &tiers,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
 }
 
-pub fn force_withdraw_all(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
-    check_executer_is_authorized_fee_receiver(deps.as_ref(), info.sender.to_string())?;
+pub fn update_wash_trading(
+    deps: DepsMut,
+    holding_period: u64,
+    pair_window: u64,
+    reject: bool,
+) -> Result<Response, ContractError> {
+    // all zero (off) until the first update
+    let old = WASH_TRADING.may_load(deps.storage)?.unwrap_or_default();
+    let new = WashTrading {
+        holding_period,
+        pair_window,
+        reject,
+    };
+    WASH_TRADING.save(deps.storage, &new)?;
 
-    // get all offerings, loop through them.
-    let offerings = queries::query_offerings(deps.as_ref(), None)?;
+    let mut res = Response::new().add_attribute("action", "update_wash_trading");
+    UpdateWashTradingEvent {
+        old: &old,
+        new: &new,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+pub fn update_rebates(
+    deps: DepsMut,
+    env: Env,
+    token: String,
+    rates: Vec<RebateRate>,
+) -> Result<Response, ContractError> {
+    let token = deps.api.addr_validate(&token)?;
+
+    // checked here so a misconfigured token is caught before the first sale
+    let minter: Option<MinterResponse> = deps
+        .querier
+        .query_wasm_smart(&token, &Cw20QueryMsg::Minter {})?;
+    if minter.map(|minter| minter.minter) != Some(env.contract.address.to_string()) {
+        return Err(ContractError::NotRebateMinter {
+            token: token.into_string(),
+        });
+    }
+
+    let old = REBATE_CONFIG.may_load(deps.storage)?;
+    REBATE_CONFIG.save(
+        deps.storage,
+        &RebateConfig {
+            token: token.clone(),
+            rates: rates.clone(),
+        },
+    )?;
 
+    let mut res = Response::new().add_attribute("action", "update_rebates");
+    UpdateRebatesEvent {
+        old: old.as_ref(),
+        new_token: token.as_str(),
+        new_rates: &rates,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+pub fn set_referrer_limit(
+    deps: DepsMut,
+    referrer: String,
+    max_bps: Option<u16>,
+) -> Result<Response, ContractError> {
+    let referrer = deps.api.addr_validate(&referrer)?;
+    let old_max_bps = REFERRER_LIMITS.may_load(deps.storage, &referrer)?;
+    match max_bps {
+        Some(max_bps) => REFERRER_LIMITS.save(deps.storage, &referrer, &max_bps)?,
+        None => REFERRER_LIMITS.remove(deps.storage, &referrer),
+    }
+
+    let mut res = Response::new().add_attribute("action", "set_referrer_limit");
+    SetReferrerLimitEvent {
+        referrer: referrer.as_str(),
+        old_max_bps,
+        new_max_bps: max_bps,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+pub fn update_featured_slots(deps: DepsMut, slots: u32) -> Result<Response, ContractError> {
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let old_slots = contract_info.featured_slots;
+    contract_info.featured_slots = slots;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    // promotions above the new limit run until they expire or are outbid
+    let mut res = Response::new().add_attribute("action", "update_featured_slots");
+    UpdateFeaturedSlotsEvent {
+        old_slots,
+        new_slots: slots,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+pub fn update_max_price_age(deps: DepsMut, max_price_age: u64) -> Result<Response, ContractError> {
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let old_max_price_age = contract_info.max_price_age;
+    contract_info.max_price_age = max_price_age;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    let mut res = Response::new().add_attribute("action", "update_max_price_age");
+    UpdateMaxPriceAgeEvent {
+        old_max_price_age,
+        new_max_price_age: max_price_age,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+pub fn promote_offering(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    offering_id: u64,
+    duration: u64,
+) -> Result<Response, ContractError> {
+    let off = offerings().load(deps.storage, offering_id)?;
+    if off.seller != info.sender {
+        return Err(ContractError::Unauthorized {
+            msg: "You are not the seller of this token, so you can not promote it.".to_string(),
+        });
+    }
+    check_not_blocked(deps.as_ref(), &env.block, &info.sender)?;
+    if duration == 0 || duration > MAX_PROMOTION_DURATION {
+        return Err(ContractError::InvalidPromotionDuration {
+            max: MAX_PROMOTION_DURATION,
+        });
+    }
+    let bid = Coin::new(must_pay(&info, &off.list_denom)?.u128(), &off.list_denom);
+
+    let config = CONTRACT_INFO.load(deps.storage)?;
+    let mut res = Response::new().add_attribute("action", "promote_offering");
+
+    // settle the expired promotions of the collection first, their bids are the fee receivers now
+    let promotions: Vec<(u64, Promotion)> = PROMOTIONS
+        .prefix(&off.contract_addr)
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    let mut active = vec![];
+    for (id, promotion) in promotions {
+        if promotion.expires > env.block.time {
+            active.push((id, promotion));
+            continue;
+        }
+        PROMOTIONS.remove(deps.storage, (&off.contract_addr, id));
+        res = res.add_message(BankMsg::Send {
+            to_address: config.fee_receive_address.clone(),
+            amount: vec![promotion.bid],
+        });
+    }
+
+    if active.iter().any(|(id, _)| *id == offering_id) {
+        return Err(ContractError::AlreadyPromoted { id: offering_id });
+    }
+
+    // every slot is taken, the lowest bid has to go. Ties keep the promotion which came first
+    let mut outbid = None;
+    if active.len() >= config.featured_slots as usize {
+        let (lowest_id, lowest) = active
+            .iter()
+            .min_by_key(|(_, promotion)| promotion.bid.amount)
+            .ok_or(ContractError::NoFeaturedSlots {})?;
+        if lowest.bid.amount >= bid.amount {
+            return Err(ContractError::PromotionBidTooLow {
+                min: lowest.bid.to_string(),
+            });
+        }
+
+        PROMOTIONS.remove(deps.storage, (&off.contract_addr, *lowest_id));
+        res = res.add_message(BankMsg::Send {
+            to_address: lowest.seller.to_string(),
+            amount: vec![lowest.bid.clone()],
+        });
+        outbid = Some(*lowest_id);
+    }
+
+    let promotion = Promotion {
+        seller: info.sender,
+        bid,
+        expires: env.block.time.plus_seconds(duration),
+    };
+    PROMOTIONS.save(deps.storage, (&off.contract_addr, offering_id), &promotion)?;
+
+    PromoteEvent {
+        offering_id,
+        collection: off.contract_addr.as_str(),
+        seller: promotion.seller.as_str(),
+        amount: promotion.bid.amount,
+        denom: &promotion.bid.denom,
+        expires: promotion.expires,
+        outbid,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+pub fn update_pull_payments(deps: DepsMut, enabled: bool) -> Result<Response, ContractError> {
+    let mut contract_info = CONTRACT_INFO.load(deps.storage)?;
+    let old_enabled = contract_info.pull_payments;
+    contract_info.pull_payments = enabled;
+    CONTRACT_INFO.save(deps.storage, &contract_info)?;
+
+    let mut res = Response::new().add_attribute("action", "update_pull_payments");
+    UpdatePullPaymentsEvent {
+        old_enabled,
+        new_enabled: enabled,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+// sends the senders credited proceeds. Still works after pull_payments is turned off
+pub fn claim_proceeds(
+    deps: DepsMut,
+    info: MessageInfo,
+    denoms: Option<Vec<String>>,
+) -> Result<Response, ContractError> {
+    let credited: Vec<(String, Uint128)> = PROCEEDS
+        .prefix(&info.sender)
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+
+    let mut amount = vec![];
+    for (denom, value) in credited {
+        if let Some(denoms) = &denoms {
+            if !denoms.contains(&denom) {
+                continue;
+            }
+        }
+        PROCEEDS.remove(deps.storage, (&info.sender, &denom));
+        amount.push(Coin {
+            denom,
+            amount: value,
+        });
+    }
+
+    if amount.is_empty() {
+        return Err(ContractError::NoProceeds {});
+    }
+
+    let mut res = Response::new().add_attribute("action", "claim_proceeds");
+    ClaimProceedsEvent {
+        recipient: info.sender.as_str(),
+        amount: &amount,
+    }
+    .add_attributes(&mut res);
+
+    Ok(res.add_message(BankMsg::Send {
+        to_address: info.sender.into_string(),
+        amount,
+    }))
+}
+
+pub fn force_withdraw(
+    deps: DepsMut,
+    limit: Option<u32>,
+    start_after: Option<u64>,
+    collection: Option<String>,
+) -> Result<Response, ContractError> {
+    let limit = limit
+        .unwrap_or(DEFAULT_FORCE_WITHDRAW_LIMIT)
+        .min(MAX_FORCE_WITHDRAW_LIMIT) as usize;
+
+    // load one more than the page so we know if another call is needed
+    let page: Vec<(u64, Offering)> = match collection {
+        Some(collection) => {
+            let collection = deps.api.addr_validate(&collection)?;
+            offerings()
+                .idx
+                .contract
+                .prefix(collection)
+                .range(
+                    deps.storage,
+                    start_after.map(Bound::exclusive),
+                    None,
+                    Order::Ascending,
+                )
+                .take(limit + 1)
+                .collect::<StdResult<_>>()?
+        }
+        None => offerings()
+            .range(
+                deps.storage,
+                start_after.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit + 1)
+            .collect::<StdResult<_>>()?,
+    };
+
+    let has_more = page.len() > limit;
+
+    let mut res = Response::new().add_attribute("action", "force_withdraw");
     let mut sub_messages_vector: Vec<SubMsg> = vec![];
+    let mut withdrawn = 0;
+    let mut last_offering_id: Option<u64> = None;
+
+    for (offering_id, offering) in page.into_iter().take(limit) {
+        if let Some(payment) = remove_offering(deps.storage, offering_id, &offering)? {
+            res = res.add_message(payment);
+        }
+        res = res.add_messages(release_deposit(&offering, offering.seller.as_str()));
+
+        WithdrawEvent {
+            offering_id,
+            collection: offering.contract_addr.as_str(),
+            token_id: &offering.token_id,
+            seller: offering.seller.as_str(),
+            forced: true,
+        }
+        .add_attributes(&mut res);
 
-    for offering in offerings {
         // transfer token back to original owner
-        let transfer_cw721_msg = Cw721ExecuteMsg::TransferNft {
-            recipient: offering.seller.clone().into_string(),
-            token_id: offering.token_id.clone(),
-        };
+        sub_messages_vector.extend(return_nft(deps.storage, offering_id, offering)?);
+        withdrawn += 1;
+        last_offering_id = Some(offering_id);
+    }
 
-        let exec_cw721_transfer = WasmMsg::Execute {
-            contract_addr: offering.contract_addr.into_string(),
-            msg: to_binary(&transfer_cw721_msg)?,
+    let page = ForceWithdrawResponse {
+        withdrawn,
+        next_start_after: if has_more {
+            last_offering_id.map(OfferingId)
+        } else {
+            None
+        },
+    };
+
+    Ok(res
+        .add_attribute("withdrawn", page.withdrawn.to_string())
+        .add_attribute(
+            "next_start_after",
+            page.next_start_after
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+        )
+        .set_data(to_binary(&page)?)
+        .add_submessages(sub_messages_vector))
+}
+
+pub fn retry_transfer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    offering_id: u64,
+) -> Result<Response, ContractError> {
+    let transfer = load_stuck_transfer(deps.as_ref(), offering_id)?;
+
+    if info.sender != transfer.recipient && info.sender != transfer.offering.seller {
+        return Err(ContractError::Unauthorized {
+            msg: "Only the seller or the recipient can retry this transfer.".to_string(),
+        });
+    }
+
+    STUCK_TRANSFERS.remove(deps.storage, offering_id);
+    // the packet of an ICS-721 delivery gets a new timeout
+    let ibc = transfer.ibc.map(|mut ibc| {
+        ibc.msg.timeout = ics721_timeout(&env);
+        ibc
+    });
+    let cw721_submsg = send_nft_transfer(
+        deps.storage,
+        offering_id,
+        NftTransfer {
+            ibc,
+            error: None,
+            ..transfer
+        },
+    )?;
+
+    let mut res = Response::new()
+        .add_attribute("action", "retry_transfer")
+        .add_submessage(cw721_submsg);
+    RetryTransferEvent {
+        offering_id,
+        sender: info.sender.as_str(),
+    }
+    .add_attributes(&mut res);
+
+    Ok(res)
+}
+
+// the buyer of a stuck purchase gets their funds back & the NFT is sent back to the seller. For a
+// gift the payer claims it, not the recipient
+pub fn claim_refund(
+    deps: DepsMut,
+    info: MessageInfo,
+    offering_id: u64,
+) -> Result<Response, ContractError> {
+    let transfer = load_stuck_transfer(deps.as_ref(), offering_id)?;
+
+    if transfer.kind != TransferKind::Purchase || info.sender != *transfer.payer() {
+        return Err(ContractError::Unauthorized {
+            msg: "Only the buyer of a stuck purchase can claim a refund.".to_string(),
+        });
+    }
+
+    STUCK_TRANSFERS.remove(deps.storage, offering_id);
+
+    // the sale never completed, so it does not count towards the volume. Wash trades were never
+    // added to it
+    if !transfer.wash_trade {
+        COLLECTION_VOLUME.update(
+            deps.storage,
+            transfer.offering.contract_addr.as_str(),
+            |value| -> StdResult<Uint128> {
+                Ok(value
+                    .unwrap_or_default()
+                    .saturating_sub(transfer.offering.list_price))
+            },
+        )?;
+    }
+    forget_sale(deps.storage, &transfer)?;
+
+    let mut res = Response::new().add_attribute("action", "claim_refund");
+    RefundEvent {
+        offering_id,
+        collection: transfer.offering.contract_addr.as_str(),
+        token_id: &transfer.offering.token_id,
+        buyer: transfer.payer().as_str(),
+        amount: transfer.offering.list_price,
+        denom: &transfer.offering.list_denom,
+    }
+    .add_attributes(&mut res);
+
+    let refund = BankMsg::Send {
+        to_address: transfer.payer().to_string(),
+        amount: transfer.paid,
+    };
+    let cw721_submsg = return_nft(deps.storage, offering_id, transfer.offering)?;
+
+    Ok(res.add_message(refund).add_submessages(cw721_submsg))
+}
+
+// called for every NftTransfer sent with send_nft_transfer
+pub fn transfer_reply(mut deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    let mut transfer = PENDING_TRANSFERS.load(deps.storage, msg.id)?;
+    PENDING_TRANSFERS.remove(deps.storage, msg.id);
+
+    let error = match msg.result {
+        SubMsgResult::Ok(_) => None,
+        SubMsgResult::Err(err) => Some(err),
+    };
+
+    let mut res = Response::new();
+    TransferEvent {
+        offering_id: msg.id,
+        collection: transfer.offering.contract_addr.as_str(),
+        token_id: &transfer.offering.token_id,
+        recipient: transfer.recipient.as_str(),
+        error: error.as_deref(),
+    }
+    .add_attributes(&mut res);
+
+    match error {
+        None => {
+            if let Some(referral) = &transfer.referral {
+                REFERRAL_TOTALS.update(
+                    deps.storage,
+                    &referral.recipient,
+                    |totals| -> StdResult<_> {
+                        let mut totals = totals.unwrap_or_default();
+                        let mut earned = NativeBalance(totals.earned) + referral.amount.clone();
+                        earned.normalize();
+                        totals.earned = earned.into_vec();
+                        totals.purchases += 1;
+                        Ok(totals)
+                    },
+                )?;
+            }
+            // wash trades earn no rebates, they would be farmed
+            if transfer.kind == TransferKind::Purchase && !transfer.wash_trade {
+                mint_rebates(deps.branch(), &env, msg.id, &transfer, &mut res)?;
+            }
+
+            // the recipient owns the NFT now, release the payments
+            let payouts = transfer
+                .payouts
+                .into_iter()
+                .chain(transfer.referral)
+                .filter(|payout| !payout.amount.amount.is_zero());
+
+            if CONTRACT_INFO.load(deps.storage)?.pull_payments {
+                // credited instead of sent, so a recipient which can't receive funds does not
+                // fail the purchase. Claimed later with ClaimProceeds
+                for payout in payouts {
+                    PROCEEDS.update(
+                        deps.storage,
+                        (&payout.recipient, &payout.amount.denom),
+                        |value| -> StdResult<Uint128> {
+                            Ok(value.unwrap_or_default() + payout.amount.amount)
+                        },
+                    )?;
+                    CreditProceedsEvent {
+                        offering_id: msg.id,
+                        recipient: payout.recipient.as_str(),
+                        amount: payout.amount.amount,
+                        denom: &payout.amount.denom,
+                    }
+                    .add_attributes(&mut res);
+                }
+                return Ok(res.add_attribute("action", "transfer_complete"));
+            }
+
+            let payments: Vec<BankMsg> = payouts
+                .map(|payout| BankMsg::Send {
+                    to_address: payout.recipient.into_string(),
+                    amount: vec![payout.amount],
+                })
+                .collect();
+
+            Ok(res
+                .add_attribute("action", "transfer_complete")
+                .add_messages(payments))
+        }
+        Some(err) => {
+            // the collection rejected it (paused, frozen, buggy..). Keep the NFT & funds here
+            // so the seller / buyer can retry or refund instead of reverting the whole tx
+            transfer.error = Some(err);
+            STUCK_TRANSFERS.save(deps.storage, msg.id, &transfer)?;
+
+            Ok(res.add_attribute("action", "transfer_stuck"))
+        }
+    }
+}
+
+// the reply ids of the buyers & the sellers rebate mint, above any offering id
+pub const REBATE_REPLY_IDS: [u64; 2] = [u64::MAX - 1, u64::MAX];
+
+// mints the loyalty token rebates of a delivered purchase to the payer & the seller. Rebates
+// stop once the cap of the token is reached, the buyers rebate is minted first. Nothing is
+// minted when the marketplace lost the minter role or the token can't be queried, & a failed
+// mint only replies, so a sale never fails because of rebates
+fn mint_rebates(
+    deps: DepsMut,
+    env: &Env,
+    offering_id: u64,
+    transfer: &NftTransfer,
+    res: &mut Response,
+) -> StdResult<()> {
+    let config = match REBATE_CONFIG.may_load(deps.storage)? {
+        Some(config) => config,
+        None => return Ok(()),
+    };
+    let paid = match transfer.paid.first() {
+        Some(paid) => paid,
+        None => return Ok(()),
+    };
+    let rate = match config.rates.iter().find(|rate| rate.denom == paid.denom) {
+        Some(rate) => rate,
+        None => return Ok(()),
+    };
+
+    let query_failed = |err: StdError, res: &mut Response| {
+        RebateFailedEvent {
+            offering_id,
+            token: config.token.as_str(),
+            recipient: None,
+            amount: None,
+            error: &err.to_string(),
+        }
+        .add_attributes(res);
+        Ok(())
+    };
+    let minter: Option<MinterResponse> = match deps
+        .querier
+        .query_wasm_smart(&config.token, &Cw20QueryMsg::Minter {})
+    {
+        Ok(minter) => minter,
+        Err(err) => return query_failed(err, res),
+    };
+    let cap = match minter {
+        Some(minter) if minter.minter == env.contract.address.as_str() => minter.cap,
+        _ => return Ok(()),
+    };
+    let mut remaining = match cap {
+        Some(cap) => {
+            let info: TokenInfoResponse = match deps
+                .querier
+                .query_wasm_smart(&config.token, &Cw20QueryMsg::TokenInfo {})
+            {
+                Ok(info) => info,
+                Err(err) => return query_failed(err, res),
+            };
+            cap.saturating_sub(info.total_supply)
+        }
+        None => Uint128::MAX,
+    };
+
+    let buyer = transfer.payer();
+    let seller = &transfer.offering.seller;
+    let mut capped = false;
+    let mut minted = vec![];
+    let mints = [(buyer, rate.buyer), (seller, rate.seller)];
+    for (&(recipient, rate), reply_id) in mints.iter().zip(REBATE_REPLY_IDS) {
+        let rebate = paid.amount * rate;
+        let amount = rebate.min(remaining);
+        capped |= amount < rebate;
+        remaining -= amount;
+        minted.push(amount);
+        if amount.is_zero() {
+            continue;
+        }
+
+        REBATES.update(deps.storage, recipient, |total| -> StdResult<_> {
+            Ok(total.unwrap_or_default() + amount)
+        })?;
+        let mint = RebateMint {
+            offering_id,
+            token: config.token.clone(),
+            recipient: recipient.clone(),
+            amount,
+        };
+        REBATE_MINTS.save(deps.storage, reply_id, &mint)?;
+        let msg = WasmMsg::Execute {
+            contract_addr: config.token.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Mint {
+                recipient: recipient.to_string(),
+                amount,
+            })?,
             funds: vec![],
         };
+        res.messages.push(SubMsg::reply_on_error(msg, reply_id));
+    }
+
+    RebateEvent {
+        offering_id,
+        token: config.token.as_str(),
+        buyer: buyer.as_str(),
+        buyer_amount: minted[0],
+        seller: seller.as_str(),
+        seller_amount: minted[1],
+        capped,
+    }
+    .add_attributes(res);
 
-        let cw721_transfer_cosmos_msg: CosmosMsg = exec_cw721_transfer.into();
+    Ok(())
+}
+
+// called when the token failed a rebate mint, the sale stands & the rebate is taken back
+pub fn rebate_reply(deps: DepsMut, msg: Reply) -> Result<Response, ContractError> {
+    let error = match msg.result {
+        SubMsgResult::Ok(_) => return Ok(Response::new()),
+        SubMsgResult::Err(err) => err,
+    };
+    let mint = REBATE_MINTS.load(deps.storage, msg.id)?;
+    REBATE_MINTS.remove(deps.storage, msg.id);
+    REBATES.update(deps.storage, &mint.recipient, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default().saturating_sub(mint.amount))
+    })?;
 
-        // remove offering from the OFFERINGS? is this needed or done for them
-        OFFERINGS.remove(deps.storage, &offering.offering_id);
-        sub_messages_vector.push(SubMsg::new(cw721_transfer_cosmos_msg));
+    let mut res = Response::new().add_attribute("action", "rebate_failed");
+    RebateFailedEvent {
+        offering_id: mint.offering_id,
+        token: mint.token.as_str(),
+        recipient: Some(mint.recipient.as_str()),
+        amount: Some(mint.amount),
+        error: &error,
     }
+    .add_attributes(&mut res);
 
-    Ok(Response::new()
-        .add_attribute("action", "force_withdraw_all")
-        .add_submessages(sub_messages_vector))
+    Ok(res)
+}
+
+// the bridge reports a packet of BuyNftOverIbc. A failed packet returned the NFT to the marketplace,
+// it is passed on to whoever it was bought for. The sale itself already completed
+pub fn ics721_ack_callback(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: Ics721AckCallbackMsg,
+) -> Result<Response, ContractError> {
+    check_ics721_bridge(deps.as_ref(), &info.sender)?;
+    // anyone can name the marketplace as the ack callback of their own packets
+    if msg.original_packet.sender != env.contract.address {
+        return Err(ContractError::Unauthorized {
+            msg: "The packet was not sent by the marketplace.".to_string(),
+        });
+    }
+    let callback: IbcDeliveryCallback = from_binary(&msg.msg)?;
+    let error = match &msg.status {
+        Ics721Status::Success => None,
+        Ics721Status::Failed(error) => Some(error.as_str()),
+    };
+
+    let mut res = Response::new().add_attribute("action", "ibc_delivery");
+    for token_id in &msg.original_packet.token_ids {
+        IbcDeliveryEvent {
+            offering_id: callback.offering_id,
+            collection: &msg.nft_contract,
+            token_id,
+            receiver: &msg.original_packet.receiver,
+            error,
+            recipient: callback.recipient.as_str(),
+        }
+        .add_attributes(&mut res);
+
+        if error.is_some() {
+            res = res.add_message(WasmMsg::Execute {
+                contract_addr: msg.nft_contract.clone(),
+                msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+                    recipient: callback.recipient.to_string(),
+                    token_id: token_id.clone(),
+                })?,
+                funds: vec![],
+            });
+        }
+    }
+
+    Ok(res)
 }
 
-fn check_executer_is_authorized_fee_receiver(
+// sends a bought NFT back over the channel its class was bridged in on
+fn ibc_delivery(
     deps: Deps,
-    executer_address: String,
+    env: &Env,
+    off: &Offering,
+    offering_id: u64,
+    recipient: &Addr,
+    receiver: String,
+) -> Result<IbcDelivery, ContractError> {
+    let bridge = CONTRACT_INFO
+        .load(deps.storage)?
+        .ics721_bridge
+        .map(Addr::unchecked)
+        .ok_or(ContractError::NoIcs721Bridge {})?;
+    let class_id = query_class_id(&deps.querier, &bridge, &off.contract_addr)?;
+    let channel_id = class_id
+        .as_deref()
+        .and_then(|class_id| source_channel(&bridge, class_id))
+        .ok_or_else(|| ContractError::NotBridged {
+            collection: off.contract_addr.to_string(),
+        })?
+        .to_string();
+
+    let callback = IbcDeliveryCallback {
+        offering_id,
+        recipient: recipient.clone(),
+    };
+    let memo = Ics721Memo {
+        callbacks: Some(Ics721Callbacks {
+            ack_callback_data: Some(to_binary(&callback)?),
+            ack_callback_addr: Some(env.contract.address.to_string()),
+            receive_callback_data: None,
+            receive_callback_addr: None,
+        }),
+    };
+    let memo = String::from_utf8(to_vec(&memo)?).map_err(StdError::from)?;
+
+    Ok(IbcDelivery {
+        bridge,
+        msg: IbcOutgoingMsg {
+            receiver,
+            channel_id,
+            timeout: ics721_timeout(env),
+            memo: Some(memo),
+        },
+    })
+}
+
+fn ics721_timeout(env: &Env) -> IbcTimeout {
+    IbcTimeout {
+        block: None,
+        timestamp: Some(env.block.time.plus_seconds(ICS721_TIMEOUT)),
+    }
+}
+
+// the callbacks are only trusted from the configured bridge
+fn check_ics721_bridge(deps: Deps, sender: &Addr) -> Result<Addr, ContractError> {
+    match CONTRACT_INFO.load(deps.storage)?.ics721_bridge {
+        Some(bridge) if bridge == *sender => Ok(sender.clone()),
+        Some(_) => Err(ContractError::Unauthorized {
+            msg: "Only the ICS-721 bridge can call this.".to_string(),
+        }),
+        None => Err(ContractError::NoIcs721Bridge {}),
+    }
+}
+
+fn load_stuck_transfer(deps: Deps, offering_id: u64) -> Result<NftTransfer, ContractError> {
+    STUCK_TRANSFERS
+        .may_load(deps.storage, offering_id)?
+        .ok_or(ContractError::NoStuckTransferWithGivenID { id: offering_id })
+}
+
+fn withdraw_transfer(offering: Offering) -> NftTransfer {
+    NftTransfer {
+        kind: TransferKind::Withdraw,
+        recipient: offering.seller.clone(),
+        offering,
+        buyer: None,
+        paid: vec![],
+        payouts: vec![],
+        referral: None,
+        wash_trade: false,
+        ibc: None,
+        error: None,
+    }
+}
+
+// sends a listed NFT back to its seller. Approval listings never took the NFT, nothing is sent
+fn return_nft(
+    storage: &mut dyn Storage,
+    offering_id: u64,
+    offering: Offering,
+) -> Result<Option<SubMsg>, ContractError> {
+    if offering.non_custodial {
+        return Ok(None);
+    }
+    send_nft_transfer(storage, offering_id, withdraw_transfer(offering)).map(Some)
+}
+
+// saves the transfer as pending & returns the cw721 TransferNft submessage for it
+fn send_nft_transfer(
+    storage: &mut dyn Storage,
+    offering_id: u64,
+    transfer: NftTransfer,
+) -> Result<SubMsg, ContractError> {
+    let transfer_cw721_msg = match &transfer.ibc {
+        None => Cw721ExecuteMsg::TransferNft {
+            recipient: transfer.recipient.to_string(),
+            token_id: transfer.offering.token_id.clone(),
+        },
+        // the bridge takes it from here, the reply only says it accepted the NFT
+        Some(ibc) => Cw721ExecuteMsg::SendNft {
+            contract: ibc.bridge.to_string(),
+            token_id: transfer.offering.token_id.clone(),
+            msg: to_binary(&ibc.msg)?,
+        },
+    };
+    let exec_cw721_transfer = WasmMsg::Execute {
+        contract_addr: transfer.offering.contract_addr.to_string(),
+        msg: to_binary(&transfer_cw721_msg)?,
+        funds: vec![],
+    };
+
+    PENDING_TRANSFERS.save(storage, offering_id, &transfer)?;
+
+    // reply on success too, purchases only pay out once the NFT has been delivered. The offering id
+    // doubles as the reply id
+    Ok(SubMsg::reply_always(exec_cw721_transfer, offering_id))
+}
+
+// rejects addresses on the blocklist. Only new listings, purchases & promotions are checked, so a
+// blocked seller can still withdraw their NFTs & claim refunds or proceeds
+pub fn check_not_blocked(
+    deps: Deps,
+    block: &BlockInfo,
+    address: &Addr,
 ) -> Result<(), ContractError> {
+    match active_block(deps.storage, block, address)? {
+        Some(entry) => Err(ContractError::Blocked {
+            address: address.to_string(),
+            reason: entry.reason.unwrap_or_default(),
+        }),
+        None => Ok(()),
+    }
+}
+
+// the admin handlers above do not check the sender, contract::execute does before calling them.
+// sudo calls them without a check
+pub fn check_executer_is_admin(deps: Deps, executer_address: &Addr) -> Result<(), ContractError> {
     let contract_info = CONTRACT_INFO.load(deps.storage)?;
-    if executer_address != contract_info.fee_receive_address {
+    if executer_address.as_str() != contract_info.admin {
         return Err(ContractError::Unauthorized {
-            msg: "You are not the current fee_receiver".to_string(),
+            msg: "You are not the current admin".to_string(),
         });
     }
     Ok(())
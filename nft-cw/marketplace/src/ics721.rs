@@ -0,0 +1,149 @@
+use cosmwasm_std::{Addr, Binary, QuerierWrapper, StdResult, Timestamp};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The ICS-721 packet of an NFT transfer, as the bridge passes it to its callbacks.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NonFungibleTokenPacketData {
+    /// prefixed with the `{port}/{channel}/` of every hop it was bridged over, so the class of an
+    /// NFT bridged to this chain starts with the local end of the channel it came in on
+    pub class_id: String,
+    #[serde(default)]
+    pub class_uri: Option<String>,
+    #[serde(default)]
+    pub class_data: Option<Binary>,
+    pub token_ids: Vec<String>,
+    #[serde(default)]
+    pub token_uris: Option<Vec<String>>,
+    #[serde(default)]
+    pub token_data: Option<Vec<Binary>>,
+    /// the address on the sending chain
+    pub sender: String,
+    pub receiver: String,
+    #[serde(default)]
+    pub memo: Option<String>,
+}
+
+/// Sent by the bridge to the `receive_callback_addr` of the packet memo, once the NFTs are minted
+/// to the receiver. An error makes the bridge fail the packet & return the NFTs.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Ics721ReceiveCallbackMsg {
+    pub original_packet: NonFungibleTokenPacketData,
+    /// the local cw721 contract the bridge minted the NFTs on
+    pub nft_contract: String,
+    /// the `receive_callback_data` of the memo
+    pub msg: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Ics721Status {
+    Success,
+    /// the NFTs were sent back to the packet sender
+    Failed(String),
+}
+
+/// Sent by the bridge to the `ack_callback_addr` of the packet memo, once the packet it sent was
+/// acknowledged or timed out.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Ics721AckCallbackMsg {
+    pub status: Ics721Status,
+    pub nft_contract: String,
+    pub original_packet: NonFungibleTokenPacketData,
+    /// the `ack_callback_data` of the memo
+    pub msg: Binary,
+}
+
+/// The message of a cw721 SendNft to the bridge, which sends the NFT over `channel_id`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IbcOutgoingMsg {
+    pub receiver: String,
+    pub channel_id: String,
+    pub timeout: IbcTimeout,
+    pub memo: Option<String>,
+}
+
+/// Same layout as the IbcTimeout of cosmwasm-std, which needs the stargate feature.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IbcTimeout {
+    pub block: Option<IbcTimeoutBlock>,
+    pub timestamp: Option<Timestamp>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IbcTimeoutBlock {
+    pub revision: u64,
+    pub height: u64,
+}
+
+/// The packet memo the bridge reads its callbacks from.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Ics721Memo {
+    pub callbacks: Option<Ics721Callbacks>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Ics721Callbacks {
+    pub ack_callback_data: Option<Binary>,
+    pub ack_callback_addr: Option<String>,
+    pub receive_callback_data: Option<Binary>,
+    pub receive_callback_addr: Option<String>,
+}
+
+/// The ack_callback_data of the packets sent by BuyNftOverIbc.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IbcDeliveryCallback {
+    pub offering_id: u64,
+    /// gets the NFT if the packet fails
+    pub recipient: Addr,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Ics721QueryMsg {
+    /// the class a local cw721 contract of the bridge was instantiated for
+    ClassId { contract: String },
+    /// the local cw721 contract of a class, if any NFT of it was bridged in
+    NftContract { class_id: String },
+}
+
+pub fn query_class_id(
+    querier: &QuerierWrapper,
+    bridge: &Addr,
+    contract: &Addr,
+) -> StdResult<Option<String>> {
+    querier.query_wasm_smart(
+        bridge,
+        &Ics721QueryMsg::ClassId {
+            contract: contract.to_string(),
+        },
+    )
+}
+
+pub fn query_nft_contract(
+    querier: &QuerierWrapper,
+    bridge: &Addr,
+    class_id: &str,
+) -> StdResult<Option<Addr>> {
+    querier.query_wasm_smart(
+        bridge,
+        &Ics721QueryMsg::NftContract {
+            class_id: class_id.to_string(),
+        },
+    )
+}
+
+/// The local channel a class was bridged in over, when it came in through `bridge`. Sending an
+/// NFT of it back over that channel returns it to the chain it came from.
+pub fn source_channel<'a>(bridge: &Addr, class_id: &'a str) -> Option<&'a str> {
+    let mut hops = class_id.splitn(3, '/');
+    let port = hops.next()?;
+    let channel = hops.next()?;
+    // the base class follows the first hop
+    hops.next()?;
+    match port.strip_prefix("wasm.") {
+        Some(contract) if contract == bridge.as_str() => Some(channel),
+        _ => None,
+    }
+}
@@ -1,7 +1,11 @@
 pub mod coin_helpers;
 pub mod contract;
 mod error;
+pub mod events;
+pub mod ics721;
+mod migrations;
 pub mod msg;
+pub mod oracle;
 pub mod state;
 
 pub mod execute;
@@ -0,0 +1,302 @@
+use cosmwasm_std::{DepsMut, Env, Order, StdError, StdResult, Timestamp, Uint128};
+use cw_storage_plus::{Item, Map};
+use cw_utils::Expiration;
+use semver::Version;
+
+use crate::error::ContractError;
+use crate::msg::{ContractInfoResponse, MigrateMsg, DEFAULT_FEATURED_SLOTS, DEFAULT_MAX_PRICE_AGE};
+use crate::state::{
+    offerings, Offering, Promotion, APPROVAL_LISTINGS, CONTRACT_INFO, OFFERING_METADATA, PROMOTIONS,
+};
+
+type MigrationStep = fn(DepsMut, &Env, &MigrateMsg) -> Result<(), ContractError>;
+
+/// Each step rewrites the stored state into the layout of its version. They run in order for every
+/// version newer than the stored cw2 version, up to and including the version being migrated to.
+/// Add new steps to the end of the list whenever a state layout changes.
+const MIGRATIONS: &[(&str, MigrationStep)] = &[("0.4.0", v0_4_0), ("0.5.0", v0_5_0)];
+
+/// Runs every step in (from, to]. Returns the versions which were applied.
+pub fn run_migrations(
+    mut deps: DepsMut,
+    env: &Env,
+    from: &Version,
+    to: &Version,
+    msg: &MigrateMsg,
+) -> Result<Vec<String>, ContractError> {
+    let mut applied = vec![];
+    for (version, step) in MIGRATIONS {
+        let version = Version::parse(version)?;
+        if &version > from && &version <= to {
+            step(deps.branch(), env, msg)?;
+            applied.push(version.to_string());
+        }
+    }
+    Ok(applied)
+}
+
+// ==== v0.3.x layouts ====
+mod v0_3 {
+    use cosmwasm_std::{Addr, Uint128};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Offering {
+        pub token_id: String,
+        pub list_denom: String,
+        pub contract_addr: Addr,
+        pub seller: Addr,
+        pub list_price: Uint128,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct ContractInfoResponse {
+        pub name: String,
+        pub denom: String,
+        pub fee_receive_address: String,
+        pub platform_fee: u128,
+        pub version: String,
+        pub contact: String,
+    }
+}
+
+// ==== v0.4.x layouts ====
+// the 0.4.x releases added settings & offering fields as they went, later ones are defaulted so
+// the state of every 0.4.x release loads
+pub(crate) mod v0_4 {
+    use cosmwasm_std::{Addr, Decimal, Uint128};
+    use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
+    use cw_utils::Expiration;
+    use serde::{Deserialize, Serialize};
+
+    use crate::msg::{DEFAULT_FEATURED_SLOTS, DEFAULT_MAX_PRICE_AGE};
+    use crate::state::{NftMetadata, Promotion};
+
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct Offering {
+        pub token_id: String,
+        pub list_denom: String,
+        pub contract_addr: Addr,
+        pub seller: Addr,
+        pub list_price: Uint128,
+        pub expiration: Expiration,
+        #[serde(default)]
+        pub usd_price: Option<Decimal>,
+        #[serde(default)]
+        pub non_custodial: bool,
+        #[serde(default)]
+        pub deposit: Uint128,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct ContractInfoResponse {
+        pub name: String,
+        pub denom: String,
+        pub fee_receive_address: String,
+        pub platform_fee: u128,
+        pub version: String,
+        pub contact: String,
+        pub admin: String,
+        #[serde(default)]
+        pub pull_payments: bool,
+        #[serde(default)]
+        pub verify_collections: bool,
+        #[serde(default = "default_max_price_age")]
+        pub max_price_age: u64,
+        #[serde(default = "default_featured_slots")]
+        pub featured_slots: u32,
+        #[serde(default)]
+        pub referral_fee_bps: u16,
+        #[serde(default)]
+        pub listing_deposit: Uint128,
+        #[serde(default)]
+        pub max_listings_per_seller: u32,
+    }
+
+    fn default_max_price_age() -> u64 {
+        DEFAULT_MAX_PRICE_AGE
+    }
+
+    fn default_featured_slots() -> u32 {
+        DEFAULT_FEATURED_SLOTS
+    }
+
+    pub const CONTRACT_INFO: Item<ContractInfoResponse> = Item::new("marketplace_info");
+
+    // the offering ids were the string form of OFFERINGS_COUNT
+    pub const OFFERING_METADATA: Map<&str, NftMetadata> = Map::new("offering_metadata");
+    pub const PROMOTIONS: Map<(&Addr, &str), Promotion> = Map::new("promotions");
+    pub const APPROVAL_LISTINGS: Map<(&Addr, &str), String> = Map::new("approval_listings");
+
+    pub struct OfferingIndexes<'a> {
+        pub seller: MultiIndex<'a, Addr, Offering, String>,
+        pub contract: MultiIndex<'a, Addr, Offering, String>,
+    }
+
+    impl<'a> IndexList<Offering> for OfferingIndexes<'a> {
+        fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Offering>> + '_> {
+            let v: Vec<&dyn Index<Offering>> = vec![&self.seller, &self.contract];
+            Box::new(v.into_iter())
+        }
+    }
+
+    pub fn offerings<'a>() -> IndexedMap<'a, &'a str, Offering, OfferingIndexes<'a>> {
+        let indexes = OfferingIndexes {
+            seller: MultiIndex::new(
+                |o: &Offering| o.seller.clone(),
+                "offerings",
+                "offerings__seller",
+            ),
+            contract: MultiIndex::new(
+                |o: &Offering| o.contract_addr.clone(),
+                "offerings",
+                "offerings__contract",
+            ),
+        };
+        IndexedMap::new("offerings", indexes)
+    }
+}
+
+/// Adds the config admin & an expiration to every offering. Offerings before v0.3.6 were saved
+/// without the seller / contract indexes, so everything is re-saved through offerings().
+fn v0_4_0(deps: DepsMut, _env: &Env, msg: &MigrateMsg) -> Result<(), ContractError> {
+    let old_info: Item<v0_3::ContractInfoResponse> = Item::new("marketplace_info");
+    let old = old_info.load(deps.storage)?;
+
+    let admin = match &msg.admin {
+        Some(admin) => deps.api.addr_validate(admin)?.into_string(),
+        None => old.fee_receive_address.clone(),
+    };
+
+    v0_4::CONTRACT_INFO.save(
+        deps.storage,
+        &v0_4::ContractInfoResponse {
+            name: old.name,
+            denom: old.denom,
+            fee_receive_address: old.fee_receive_address,
+            platform_fee: old.platform_fee,
+            version: old.version,
+            contact: old.contact,
+            admin,
+            pull_payments: false,
+            verify_collections: false,
+            max_price_age: DEFAULT_MAX_PRICE_AGE,
+            featured_slots: DEFAULT_FEATURED_SLOTS,
+            referral_fee_bps: 0,
+            listing_deposit: Uint128::zero(),
+            max_listings_per_seller: 0,
+        },
+    )?;
+
+    let old_offerings: Map<&str, v0_3::Offering> = Map::new("offerings");
+    let all: Vec<(String, v0_3::Offering)> = old_offerings
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+
+    for (id, off) in all {
+        // removed first so the indexed save does not try to parse the old layout
+        old_offerings.remove(deps.storage, &id);
+        v0_4::offerings().save(
+            deps.storage,
+            &id,
+            &v0_4::Offering {
+                token_id: off.token_id,
+                list_denom: off.list_denom,
+                contract_addr: off.contract_addr,
+                seller: off.seller,
+                list_price: off.list_price,
+                expiration: Expiration::Never {},
+                usd_price: None,
+                non_custodial: false,
+                deposit: Uint128::zero(),
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Carries the config & offerings of the 0.4.x releases over, settings & fields they didn't have
+/// yet start off or at their defaults. Offering ids become integer keys,
+/// so offerings iterate in the order they were listed ("10" used to sort before "2"). Everything
+/// keyed by or pointing to an offering id is rewritten, the offering indexes with it, which also
+/// adds them to the price, listing time & token indexes. Transfers were already keyed by the
+/// numeric id.
+fn v0_5_0(deps: DepsMut, _env: &Env, _msg: &MigrateMsg) -> Result<(), ContractError> {
+    let old = v0_4::CONTRACT_INFO.load(deps.storage)?;
+    CONTRACT_INFO.save(
+        deps.storage,
+        &ContractInfoResponse {
+            name: old.name,
+            denom: old.denom,
+            fee_receive_address: old.fee_receive_address,
+            platform_fee: old.platform_fee,
+            version: old.version,
+            contact: old.contact,
+            admin: old.admin,
+            pull_payments: old.pull_payments,
+            verify_collections: old.verify_collections,
+            max_price_age: old.max_price_age,
+            featured_slots: old.featured_slots,
+            referral_fee_bps: old.referral_fee_bps,
+            listing_deposit: old.listing_deposit,
+            max_listings_per_seller: old.max_listings_per_seller,
+            ics721_bridge: None,
+        },
+    )?;
+
+    let all: Vec<(String, v0_4::Offering)> = v0_4::offerings()
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    for (id, off) in all {
+        v0_4::offerings().remove(deps.storage, &id)?;
+        offerings().save(
+            deps.storage,
+            parse_id(&id)?,
+            &Offering {
+                token_id: off.token_id,
+                list_denom: off.list_denom,
+                contract_addr: off.contract_addr,
+                seller: off.seller,
+                list_price: off.list_price,
+                expiration: off.expiration,
+                usd_price: off.usd_price,
+                non_custodial: off.non_custodial,
+                deposit: off.deposit,
+                listed_height: 0,
+                listed_at: Timestamp::default(),
+            },
+        )?;
+    }
+
+    let all: Vec<(String, _)> = v0_4::OFFERING_METADATA
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    for (id, metadata) in all {
+        v0_4::OFFERING_METADATA.remove(deps.storage, &id);
+        OFFERING_METADATA.save(deps.storage, parse_id(&id)?, &metadata)?;
+    }
+
+    let all: Vec<((_, String), Promotion)> = v0_4::PROMOTIONS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    for ((collection, id), promotion) in all {
+        v0_4::PROMOTIONS.remove(deps.storage, (&collection, &id));
+        PROMOTIONS.save(deps.storage, (&collection, parse_id(&id)?), &promotion)?;
+    }
+
+    // same keys, only the offering id they point to changes
+    let all: Vec<((_, String), String)> = v0_4::APPROVAL_LISTINGS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    for ((collection, token_id), id) in all {
+        APPROVAL_LISTINGS.save(deps.storage, (&collection, &token_id), &parse_id(&id)?)?;
+    }
+
+    Ok(())
+}
+
+fn parse_id(id: &str) -> StdResult<u64> {
+    id.parse()
+        .map_err(|_| StdError::generic_err(format!("invalid offering id {}", id)))
+}
@@ -1,9 +1,15 @@
 // use cw20::{Cw20Coin, Cw20ReceiveMsg};
 // use cosmwasm_std::{Coin};
-use cosmwasm_std::{Addr, Uint128};
+use crate::ics721::{Ics721AckCallbackMsg, Ics721ReceiveCallbackMsg};
+use crate::state::{BlockEntry, FeeTier, NftMetadata, Payout, RebateRate, TransferKind};
+use cosmwasm_std::{Addr, Coin, Decimal, Timestamp, Uint128};
 use cw721::Cw721ReceiveMsg;
+use cw_utils::Expiration;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InitMsg {
@@ -11,21 +17,82 @@ pub struct InitMsg {
     pub denom: String,               // ucraft
     pub fee_receive_address: String, // where we pay the 'tax' (platform fee) too, a craft multisig addr
     pub platform_fee: u128,          // 5 = 5%
+    pub admin: Option<String>,       // can change the config, defaults to the fee_receive_address
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
     WithdrawNft {
-        offering_id: String,
+        offering_id: OfferingId,
     },
     BuyNft {
-        offering_id: String,
+        offering_id: OfferingId,
+        // gift the NFT to another address, the funds are still taken from the sender
+        #[serde(default)]
+        recipient: Option<String>,
+        // the storefront which sent the buyer, paid a share of the platform fee
+        #[serde(default)]
+        referrer: Option<String>,
+    },
+    // buys an NFT which was bridged in over ICS-721 & sends it back to `receiver` on the chain it
+    // came from. The sender gets the NFT here if the packet fails
+    BuyNftOverIbc {
+        offering_id: OfferingId,
+        receiver: String,
+        #[serde(default)]
+        referrer: Option<String>,
     },
     ReceiveNft(Cw721ReceiveMsg),
+    // lists NFTs as the ICS-721 bridge mints them to the marketplace, with a BridgedSellNft as the
+    // receive_callback_data of the packet memo
+    Ics721ReceiveCallback(Ics721ReceiveCallbackMsg),
+    // the bridge reports the packets of BuyNftOverIbc here
+    Ics721AckCallback(Ics721AckCallbackMsg),
+    // lets `sender` on the other end of `channel_id` list bridged NFTs for the caller. Their
+    // listings draw the callers prepaid deposits & count towards the callers listing cap
+    AllowBridgedSender {
+        channel_id: String,
+        sender: String,
+    },
+    RevokeBridgedSender {
+        channel_id: String,
+        sender: String,
+    },
+    // lists a token which stays with the seller. The marketplace must be approved for it first,
+    // with Approve or ApproveAll on the collection
+    List {
+        collection: String,
+        token_id: String,
+        price: Uint128,
+    },
+    // removes approval listings which went stale, one page of offerings at a time. Anyone can
+    // call it, keep calling with the returned `next_start_after` until it is None
+    PruneListings {
+        limit: Option<u32>,
+        start_after: Option<OfferingId>,
+    },
+    // anyone can take down an expired offering. The NFT goes back to the seller & its listing
+    // deposit to the fee receiver, unless the seller reclaims it themselves
+    ReclaimExpired {
+        offering_id: OfferingId,
+    },
+    // pays listing deposits ahead with the sent funds, SendNft can't carry any. Each NFT listed
+    // with SendNft then draws its deposit from them
+    PrepayDeposit {},
+    // pays the prepaid deposits which were not used back to the sender
+    WithdrawPrepaidDeposit {},
+
+    // when a collection rejected the transfer out of the marketplace (see GetStuckTransfers)
+    RetryTransfer {
+        offering_id: OfferingId,
+    },
+    ClaimRefund {
+        offering_id: OfferingId,
+    },
 
     UpdateListingPrice {
-        offering_id: String,
+        offering_id: OfferingId,
         new_price: Uint128,
     },
 
@@ -35,13 +102,269 @@ pub enum ExecuteMsg {
     UpdatePlatformFee {
         new_fee: u128,
     },
-    ForceWithdrawAll {}, // contract admin sends ALL NFTs back to original owners
+    UpdatePullPayments {
+        enabled: bool,
+    },
+    UpdateFeaturedSlots {
+        slots: u32,
+    },
+    UpdateMaxPriceAge {
+        max_price_age: u64,
+    },
+    // the share of the platform fee paid to the referrer of a purchase, in basis points
+    UpdateReferralFee {
+        bps: u16,
+    },
+    // caps the share a referrer gets below the referral fee, None removes the cap
+    SetReferrerLimit {
+        referrer: String,
+        max_bps: Option<u16>,
+    },
+    // replaces the staking tiers which discount the platform fee of sellers delegating `denom`.
+    // An empty list turns the discounts off
+    UpdateFeeTiers {
+        denom: String,
+        tiers: Vec<FeeTier>,
+    },
+    // mints `token` rebates to the buyer & seller of every sale, at the rate of the denom paid.
+    // The marketplace must be the tokens minter. An empty list stops the rebates
+    UpdateRebates {
+        token: String,
+        rates: Vec<RebateRate>,
+    },
+    // bought tokens can't be listed again within `holding_period` seconds, trades between the
+    // same two addresses within `pair_window` seconds are flagged. Flagged sales are left out of
+    // the volume & rebates, or rejected with `reject`. Zero turns a check off
+    UpdateWashTrading {
+        holding_period: u64,
+        pair_window: u64,
+        reject: bool,
+    },
+    // the refundable deposit new listings hold & how many offerings a seller can have listed,
+    // zero turns either off. Existing offerings keep the deposit they were listed with
+    UpdateListingLimits {
+        deposit: Uint128,
+        max_per_seller: u32,
+    },
+    // the ICS-721 bridge trusted with the receive & ack callbacks, None turns them off
+    UpdateIcs721Bridge {
+        address: Option<String>,
+    },
+
+    // the seller bids the sent funds (in the listing denom) to feature the offering for `duration`
+    // seconds. When every slot of the collection is taken the lowest bid is outbid & refunded
+    PromoteOffering {
+        offering_id: OfferingId,
+        duration: u64,
+    },
+
+    // the collection registry. RegisterCollection stores the name & symbol the collections
+    // ContractInfo must return, which receive_nft checks while verify_collections is set
+    RegisterCollection {
+        address: String,
+        name: String,
+        symbol: String,
+    },
+    UnregisterCollection {
+        address: String,
+    },
+    UpdateVerifyCollections {
+        enabled: bool,
+    },
+
+    // fetches the NftInfo of the offering again, for when the collection changed it
+    RefreshMetadata {
+        offering_id: OfferingId,
+    },
+
+    // pays out the senders credited proceeds, only the given denoms if set
+    ClaimProceeds {
+        denoms: Option<Vec<String>>,
+    },
+    // contract admin sends listed NFTs back to their original owners, one page at a time.
+    // Keep calling with the returned `next_start_after` until it is None.
+    ForceWithdraw {
+        limit: Option<u32>,
+        start_after: Option<OfferingId>,
+        collection: Option<String>,
+    },
+
+    // the admin blocks an address from listing & buying, until `expires` if set. Blocking an
+    // address which is already blocked replaces its reason & expiry
+    Block {
+        address: String,
+        reason: Option<String>,
+        #[serde(default)]
+        expires: Expiration,
+    },
+    Unblock {
+        address: String,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct SellNft {
+    // ignored when usd_price is set
+    #[serde(default)]
+    pub list_price: Uint128,
+    // the price in USD, converted to the marketplace denom with the oracle when bought. The funds
+    // sent with BuyNft are the most the buyer pays, so they cap the slippage
+    #[serde(default)]
+    pub usd_price: Option<Decimal>,
+    #[serde(default)]
+    pub expiration: Expiration,
+}
+
+// the receive_callback_data of NFTs bridged in to be listed. The packet sender is an address on
+// the other chain, so the seller who is paid & can withdraw them is named here
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct BridgedSellNft {
+    pub seller: String,
     pub list_price: Uint128,
+    #[serde(default)]
+    pub usd_price: Option<Decimal>,
+    #[serde(default)]
+    pub expiration: Expiration,
+}
+
+impl BridgedSellNft {
+    pub fn into_parts(self) -> (String, SellNft) {
+        let msg = SellNft {
+            list_price: self.list_price,
+            usd_price: self.usd_price,
+            expiration: self.expiration,
+        };
+        (self.seller, msg)
+    }
+}
+
+/// The id of an offering. Read from a number or a numeric string, so messages which still send
+/// the ids as strings keep working. Written as a string, like Uint64, so responses don't change
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OfferingId(pub u64);
+
+// the forms an OfferingId is read from
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum RawOfferingId {
+    Number(u64),
+    String(String),
+}
+
+impl From<u64> for OfferingId {
+    fn from(id: u64) -> Self {
+        OfferingId(id)
+    }
+}
+
+impl fmt::Display for OfferingId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Serialize for OfferingId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for OfferingId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match RawOfferingId::deserialize(deserializer)? {
+            RawOfferingId::Number(id) => Ok(OfferingId(id)),
+            RawOfferingId::String(id) => id
+                .parse()
+                .map(OfferingId)
+                .map_err(|_| de::Error::custom(format!("invalid offering id {}", id))),
+        }
+    }
+}
+
+impl JsonSchema for OfferingId {
+    fn schema_name() -> String {
+        "OfferingId".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        RawOfferingId::json_schema(gen)
+    }
+}
+
+// the admin messages, run by the chain through a governance "sudo contract" proposal. They skip
+// the admin check, so the config can be changed without any single key
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SudoMsg {
+    UpdateFeeReceiverAddress {
+        new_address: String,
+    },
+    UpdatePlatformFee {
+        new_fee: u128,
+    },
+    UpdatePullPayments {
+        enabled: bool,
+    },
+    UpdateFeaturedSlots {
+        slots: u32,
+    },
+    UpdateMaxPriceAge {
+        max_price_age: u64,
+    },
+    UpdateReferralFee {
+        bps: u16,
+    },
+    SetReferrerLimit {
+        referrer: String,
+        max_bps: Option<u16>,
+    },
+    UpdateFeeTiers {
+        denom: String,
+        tiers: Vec<FeeTier>,
+    },
+    UpdateRebates {
+        token: String,
+        rates: Vec<RebateRate>,
+    },
+    UpdateWashTrading {
+        holding_period: u64,
+        pair_window: u64,
+        reject: bool,
+    },
+    UpdateListingLimits {
+        deposit: Uint128,
+        max_per_seller: u32,
+    },
+    UpdateIcs721Bridge {
+        address: Option<String>,
+    },
+    RegisterCollection {
+        address: String,
+        name: String,
+        symbol: String,
+    },
+    UnregisterCollection {
+        address: String,
+    },
+    UpdateVerifyCollections {
+        enabled: bool,
+    },
+    ForceWithdraw {
+        limit: Option<u32>,
+        start_after: Option<OfferingId>,
+        collection: Option<String>,
+    },
+    Block {
+        address: String,
+        reason: Option<String>,
+        #[serde(default)]
+        expires: Expiration,
+    },
+    Unblock {
+        address: String,
+    },
 }
 
 // ======= RESPONSES =======
@@ -54,17 +377,179 @@ pub struct ContractInfoResponse {
     pub platform_fee: u128, // 5 = 5%.
     pub version: String,
     pub contact: String,
+    pub admin: String,
+    // when set, purchase payouts are credited to PROCEEDS & withdrawn with ClaimProceeds
+    #[serde(default)]
+    pub pull_payments: bool,
+    // when set, only collections in the registry can be listed (see RegisterCollection)
+    #[serde(default)]
+    pub verify_collections: bool,
+    // how old (seconds) an oracle price can be to buy a USD listing with it
+    #[serde(default = "default_max_price_age")]
+    pub max_price_age: u64,
+    // how many offerings of a collection can be promoted at the same time
+    #[serde(default = "default_featured_slots")]
+    pub featured_slots: u32,
+    // the share of the platform fee paid to the referrer of a purchase, in basis points
+    #[serde(default)]
+    pub referral_fee_bps: u16,
+    // refundable deposit every new listing holds, in the marketplace denom. Zero for none
+    #[serde(default)]
+    pub listing_deposit: Uint128,
+    // how many offerings a seller can have listed at once, zero for no limit
+    #[serde(default)]
+    pub max_listings_per_seller: u32,
+    // the ICS-721 bridge contract NFTs are listed from & sent back over, None when not set
+    #[serde(default)]
+    pub ics721_bridge: Option<String>,
     // pub code_id: String,
 }
 
+pub const DEFAULT_FEATURED_SLOTS: u32 = 3;
+pub const DEFAULT_MAX_PRICE_AGE: u64 = 5 * 60;
+
+fn default_max_price_age() -> u64 {
+    DEFAULT_MAX_PRICE_AGE
+}
+
+fn default_featured_slots() -> u32 {
+    DEFAULT_FEATURED_SLOTS
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct QueryOfferingsResult {
-    pub offering_id: String,
+    pub offering_id: OfferingId,
     pub token_id: String,
     pub list_denom: String,
     pub list_price: Uint128,
+    pub usd_price: Option<Decimal>,
     pub contract_addr: Addr,
     pub seller: Addr,
+    pub expiration: Expiration,
+    // the NFT is still held by the seller, see ExecuteMsg::List
+    pub non_custodial: bool,
+    // when it was listed, zero for offerings listed before v0.5
+    pub listed_height: u64,
+    pub listed_at: Timestamp,
+    // only set by GetOfferings with include_metadata
+    pub metadata: Option<NftMetadata>,
+}
+
+// where a GetOfferings page ended. It carries the values the offerings are sorted by, so the page
+// after an offering which was sold or withdrawn in the meantime can still be found
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OfferingsCursor {
+    pub offering_id: OfferingId,
+    pub list_price: Uint128,
+    pub listed_at: Timestamp,
+}
+
+// the order of GetOfferings. USD listings are sorted by their price at listing time
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OfferingSort {
+    Newest,
+    Oldest,
+    LowestPrice,
+    HighestPrice,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeTiersResponse {
+    pub denom: String,
+    pub tiers: Vec<FeeTier>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RebatesResponse {
+    pub address: Addr,
+    pub total: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EffectiveOwnerResponse {
+    pub collection: Addr,
+    pub token_id: String,
+    // only None in GetEffectiveOwners, when the collection failed to return it (see `error`)
+    pub owner: Option<String>,
+    // set while the token is listed
+    pub listing: Option<ListingState>,
+    // set while the marketplace holds the token after its transfer failed, it is owned by the
+    // recipient
+    pub stuck_transfer: Option<StuckTransferResult>,
+    // why the collection failed to return the owner, e.g. for a burned token
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EffectiveOwnersResponse {
+    pub owners: Vec<EffectiveOwnerResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListingState {
+    pub offering_id: OfferingId,
+    pub seller: Addr,
+    pub list_price: Uint128,
+    pub list_denom: String,
+    // the marketplace holds the token. Approval listings (see ExecuteMsg::List) leave it with the
+    // seller, so their owner is whoever the collection returns
+    pub escrowed: bool,
+    // the offering can no longer be bought, only withdrawn or reclaimed
+    pub expired: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EscrowedToken {
+    pub collection: Addr,
+    pub token_id: String,
+    // set for the tokens of the owners listings
+    pub listing: Option<ListingState>,
+    // set for the tokens whose transfer to the owner failed
+    pub stuck_transfer: Option<StuckTransferResult>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EscrowedTokensResponse {
+    pub owner: Addr,
+    pub tokens: Vec<EscrowedToken>,
+    // the last offering checked, continue from it while set
+    pub next_start_after: Option<OfferingId>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PrepaidDepositResponse {
+    pub address: Addr,
+    pub amount: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReferralTotalsResponse {
+    pub referrer: Addr,
+    // the share of the platform fee the referrer gets, in basis points
+    pub share_bps: u16,
+    // purchases they referred which were delivered
+    pub purchases: u64,
+    pub earned: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct StuckTransferResult {
+    pub offering_id: OfferingId,
+    pub kind: TransferKind,
+    pub token_id: String,
+    pub contract_addr: Addr,
+    pub seller: Addr,
+    pub recipient: Addr,
+    // who paid, when they bought the NFT for someone else
+    pub buyer: Option<Addr>,
+    pub paid: Vec<Coin>,
+    pub error: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StuckTransfersResponse {
+    pub transfers: Vec<StuckTransferResult>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -73,9 +558,131 @@ pub struct CollectionVolumeResponse {
     pub denom: String,
 }
 
+// set as the data of a ForceWithdraw response
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ForceWithdrawResponse {
+    pub withdrawn: u32,
+    pub next_start_after: Option<OfferingId>,
+}
+
+// why a BuyNft would currently fail
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PurchaseFailure {
+    Expired,
+    BuyerIsSeller,
+    // the buyer, recipient or seller is on the blocklist
+    Blocked,
+    // approval listings only, the seller moved the token
+    OwnerChanged,
+    // approval listings only, the seller revoked the marketplaces approval
+    NotApproved,
+    // wash trades, only a failure while the safeguards reject them. The seller bought the token
+    // within the holding period
+    HoldingPeriod,
+    // the buyer & seller already traded within the pair window
+    PingPong,
+    // USD listings only, the oracle has no recent price for the list denom
+    PriceUnavailable,
+}
+
+impl PurchaseFailure {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PurchaseFailure::Expired => "expired",
+            PurchaseFailure::BuyerIsSeller => "buyer_is_seller",
+            PurchaseFailure::Blocked => "blocked",
+            PurchaseFailure::OwnerChanged => "owner_changed",
+            PurchaseFailure::NotApproved => "not_approved",
+            PurchaseFailure::HoldingPeriod => "holding_period",
+            PurchaseFailure::PingPong => "ping_pong",
+            PurchaseFailure::PriceUnavailable => "price_unavailable",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StaleListing {
+    pub offering_id: OfferingId,
+    pub reason: PurchaseFailure,
+}
+
+// also set as the data of a PruneListings response, with the listings it removed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StaleListingsResponse {
+    pub listings: Vec<StaleListing>,
+    pub next_start_after: Option<OfferingId>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateBuyResponse {
+    // the exact funds to send with BuyNft
+    pub funds: Vec<Coin>,
+    pub platform_fee: Payout,
+    pub seller_payout: Payout,
+    // the referrers share, taken out of the platform fee
+    pub referral_fee: Option<Payout>,
+    // the staking discount of the seller, already taken off the platform fee
+    pub fee_discount_bps: u16,
+    // set when BuyNft would currently fail for the buyer
+    pub failure: Option<PurchaseFailure>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RegisteredCollection {
+    pub address: Addr,
+    pub name: String,
+    pub symbol: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RegisteredCollectionsResponse {
+    pub collections: Vec<RegisteredCollection>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeaturedOffering {
+    pub offering: QueryOfferingsResult,
+    pub bid: Coin,
+    pub expires: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeaturedResponse {
+    pub featured: Vec<FeaturedOffering>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BlockedResponse {
+    pub blocked: bool,
+    // the entry while the address is blocked, None once it expired
+    pub entry: Option<BlockEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BlocklistEntry {
+    pub address: Addr,
+    pub reason: Option<String>,
+    pub expires: Expiration,
+}
+
+// every stored entry, expired ones included until they are unblocked
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BlocklistResponse {
+    pub entries: Vec<BlocklistEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingProceedsResponse {
+    pub proceeds: Vec<Coin>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct OfferingsResponse {
     pub offerings: Vec<QueryOfferingsResult>,
+    // the start_after of the next page, set when the page is full
+    #[serde(default)]
+    pub next_start_after: Option<OfferingsCursor>,
 }
 
 impl Iterator for OfferingsResponse {
@@ -88,16 +695,114 @@ impl Iterator for OfferingsResponse {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    // GetOfferings returns a list of all offerings
+    // GetOfferings returns a list of all offerings, in the order they were listed unless sorted.
+    // Pages continue after the `start_after` cursor, the `next_start_after` of the last page
     GetOfferings {
-        filter_seller: Option<String>
+        filter_seller: Option<String>,
+        include_metadata: Option<bool>,
+        // only the offerings of this collection
+        #[serde(default)]
+        collection: Option<String>,
+        #[serde(default)]
+        sort: Option<OfferingSort>,
+        #[serde(default)]
+        start_after: Option<OfferingsCursor>,
+        // every matching offering when not set
+        #[serde(default)]
+        limit: Option<u32>,
     },
     // Returns info about the contract such as name, denom, dao_address, and the tax_rate (platform fee)
     GetContractInfo {},
 
-    GetCollectionVolume { address: String },
+    GetCollectionVolume {
+        address: String,
+    },
+
+    // NFT transfers a collection contract rejected, optionally only where the address is the seller or recipient
+    GetStuckTransfers {
+        filter_address: Option<String>,
+    },
+
+    // What the buyer has to send for an offering & where it goes, without buying it
+    SimulateBuy {
+        offering_id: OfferingId,
+        buyer: String,
+        #[serde(default)]
+        referrer: Option<String>,
+    },
+
+    // proceeds credited to the address which have not been claimed yet
+    GetPendingProceeds {
+        address: String,
+    },
+
+    GetRegisteredCollections {},
+
+    // offerings currently promoted, highest bid first
+    GetFeatured {
+        collection: Option<String>,
+    },
+
+    // approval listings which can no longer be bought, checking one page of offerings
+    GetStaleListings {
+        start_after: Option<OfferingId>,
+        limit: Option<u32>,
+    },
+
+    IsBlocked {
+        address: String,
+    },
+    GetBlocklist {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    // the referral fees a referrer earned & the share they currently get
+    GetReferralTotals {
+        referrer: String,
+    },
+
+    GetFeeTiers {},
+
+    // the loyalty token & the rebate rates, None until configured
+    GetRebateConfig {},
+    // the loyalty tokens minted to the address as rebates
+    GetRebates {
+        address: String,
+    },
+
+    GetWashTrading {},
+
+    // the listing deposits the address paid ahead & has not used yet
+    GetPrepaidDeposit {
+        address: String,
+    },
+
+    // who a token belongs to, the seller while the marketplace holds it for a listing. Otherwise
+    // the owner the collection returns
+    GetEffectiveOwner {
+        collection: String,
+        token_id: String,
+    },
+    // the same for up to 100 tokens of a collection
+    GetEffectiveOwners {
+        collection: String,
+        token_ids: Vec<String>,
+    },
+    // the tokens the marketplace holds for the owners listings & failed transfers to the owner,
+    // one page of offering ids at a time
+    GetEscrowedTokens {
+        owner: String,
+        start_after: Option<OfferingId>,
+        limit: Option<u32>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-pub struct MigrateMsg {}
+pub struct MigrateMsg {
+    // optional config changes applied after the state migrations
+    pub admin: Option<String>,
+    pub fee_receive_address: Option<String>,
+    pub platform_fee: Option<u128>,
+}
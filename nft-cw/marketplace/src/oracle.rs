@@ -0,0 +1,42 @@
+use cosmwasm_std::{
+    from_binary, to_vec, ContractResult, CustomQuery, Decimal, QuerierWrapper, QueryRequest,
+    StdError, StdResult, SystemResult, Timestamp,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Custom queries answered by the chain's wasm query plugin, backed by the x/oracle price feed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CraftQuery {
+    /// the USD value of one unit (e.g. 1ucraft) of `denom`
+    UsdPrice { denom: String },
+}
+
+impl CustomQuery for CraftQuery {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UsdPriceResponse {
+    pub price: Decimal,
+    /// block time the feed last updated the price
+    pub updated_at: Timestamp,
+}
+
+// the entry points use the default Empty custom query type, so the request is serialized here &
+// sent raw instead of making every Deps generic over CraftQuery
+pub fn query_usd_price(querier: &QuerierWrapper, denom: &str) -> StdResult<UsdPriceResponse> {
+    let request = to_vec(&QueryRequest::Custom(CraftQuery::UsdPrice {
+        denom: denom.to_string(),
+    }))?;
+    match querier.raw_query(&request) {
+        SystemResult::Err(system_err) => Err(StdError::generic_err(format!(
+            "Querier system error: {}",
+            system_err
+        ))),
+        SystemResult::Ok(ContractResult::Err(contract_err)) => Err(StdError::generic_err(format!(
+            "Querier contract error: {}",
+            contract_err
+        ))),
+        SystemResult::Ok(ContractResult::Ok(value)) => from_binary(&value),
+    }
+}
@@ -1,45 +1,164 @@
 use crate::msg::{OfferingsResponse, QueryOfferingsResult}; // TODO: move these to msg
                                                            // use crate::msg::{PlatformFeeResponse, DenomResponse, DaoAddressResponse};
-use crate::msg::{CollectionVolumeResponse, ContractInfoResponse};
-use cosmwasm_std::{Deps, Order, StdResult, Uint128};
+use crate::error::ContractError;
+use crate::execute::{
+    check_not_blocked, current_price, purchase_failure, purchase_payouts, referral_share,
+    rejected_wash_trade, stale_listing, stale_listings,
+};
+use crate::msg::{
+    BlockedResponse, BlocklistEntry, BlocklistResponse, CollectionVolumeResponse,
+    ContractInfoResponse, EffectiveOwnerResponse, EffectiveOwnersResponse, EscrowedToken,
+    EscrowedTokensResponse, FeaturedOffering, FeaturedResponse, FeeTiersResponse, ListingState,
+    OfferingId, OfferingSort, OfferingsCursor, PendingProceedsResponse, PrepaidDepositResponse,
+    PurchaseFailure, RebatesResponse, ReferralTotalsResponse, RegisteredCollection,
+    RegisteredCollectionsResponse, SimulateBuyResponse, StaleListing, StaleListingsResponse,
+    StuckTransferResult, StuckTransfersResponse,
+};
+use cosmwasm_std::{Addr, Coin, Deps, Env, Order, StdError, StdResult, Uint128};
+use cw721_base::helpers::Cw721Contract;
+use cw_storage_plus::{Bound, PrimaryKey};
+use std::cmp::Reverse;
 
-use crate::state::{Offering, COLLECTION_VOLUME, CONTRACT_INFO, OFFERINGS};
+use crate::state::{
+    active_block, offerings, NftTransfer, Offering, BLOCKLIST, COLLECTIONS, COLLECTION_VOLUME,
+    CONTRACT_INFO, FEE_TIERS, OFFERING_METADATA, PREPAID_DEPOSITS, PROCEEDS, PROMOTIONS, REBATES,
+    REFERRAL_TOTALS, STUCK_TRANSFERS,
+};
+
+// shown by GetFeeTiers until the admin configures the tiers
+const DEFAULT_STAKING_DENOM: &str = "uexp";
+
+const DEFAULT_BLOCKLIST_LIMIT: u32 = 30;
+const MAX_BLOCKLIST_LIMIT: u32 = 100;
+
+const DEFAULT_ESCROWED_LIMIT: u32 = 30;
+const MAX_ESCROWED_LIMIT: u32 = 100;
+
+// token_ids a single GetEffectiveOwners can look up, each one can cost an OwnerOf query
+const MAX_EFFECTIVE_OWNERS: usize = 100;
 
 // gets all offerings
 // ============================== Query Handlers ==============================
-pub fn query_offerings(deps: Deps, filter_seller: Option<String>) -> StdResult<OfferingsResponse> {
-    let res: StdResult<Vec<QueryOfferingsResult>> = OFFERINGS
-        .range(deps.storage, None, None, Order::Ascending)
-        // .map(|kv_item| parse_offering(kv_item))
-        .map(parse_offering)
+pub fn query_offerings(
+    deps: Deps,
+    filter_seller: Option<String>,
+    include_metadata: bool,
+    collection: Option<String>,
+    sort: Option<OfferingSort>,
+    start_after: Option<OfferingsCursor>,
+    limit: Option<u32>,
+) -> StdResult<OfferingsResponse> {
+    let sort = sort.unwrap_or(OfferingSort::Oldest);
+    let order = match sort {
+        OfferingSort::Oldest | OfferingSort::LowestPrice => Order::Ascending,
+        OfferingSort::Newest | OfferingSort::HighestPrice => Order::Descending,
+    };
+    let collection = collection
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    let filter_seller = filter_seller
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    // the sorted indexes need the value the last page ended on, not just its id
+    let start = start_after.map(|cursor| (cursor.offering_id.0, cursor));
 
-        // get just offerings from the seller we requested.
-        .filter(|item| {
-            match item {
-                Ok(item) => {
-                    if let Some(filter_seller) = &filter_seller {
-                        item.seller.to_string() == filter_seller.to_string()
-                    } else {
-                        true
-                    }
-                }
-                Err(_) => false,
+    let listings: Box<dyn Iterator<Item = StdResult<(u64, Offering)>>> = match (sort, collection) {
+        // ids only go up, so the primary key is already in listing order. So are the ids of a
+        // seller in the seller index
+        (OfferingSort::Oldest | OfferingSort::Newest, None) => {
+            let (min, max) = page_bounds(start.map(|(id, _)| id), order);
+            match &filter_seller {
+                Some(seller) => offerings().idx.seller.prefix(seller.clone()).range(
+                    deps.storage,
+                    min,
+                    max,
+                    order,
+                ),
+                None => offerings().range(deps.storage, min, max, order),
+            }
         }
+        (OfferingSort::Oldest | OfferingSort::Newest, Some(collection)) => {
+            let (min, max) = page_bounds(start.map(|(id, c)| (c.listed_at.nanos(), id)), order);
+            offerings()
+                .idx
+                .listed_at
+                .sub_prefix(collection)
+                .range(deps.storage, min, max, order)
+        }
+        (OfferingSort::LowestPrice | OfferingSort::HighestPrice, Some(collection)) => {
+            let (min, max) = page_bounds(start.map(|(id, c)| (c.list_price.u128(), id)), order);
+            offerings()
+                .idx
+                .price
+                .sub_prefix(collection)
+                .range(deps.storage, min, max, order)
+        }
+        (OfferingSort::LowestPrice | OfferingSort::HighestPrice, None) => {
+            let (min, max) = page_bounds(start.map(|(id, c)| (c.list_price.u128(), id)), order);
+            offerings()
+                .idx
+                .all_price
+                .range(deps.storage, min, max, order)
+        }
+    };
+
+    let res: StdResult<Vec<QueryOfferingsResult>> = listings
+        .map(parse_offering)
+        // get just offerings from the seller we requested, errors are kept to be returned
+        .filter(|item| match (item, &filter_seller) {
+            (Ok(item), Some(filter_seller)) => item.seller == *filter_seller,
+            _ => true,
+        })
+        .take(limit.map_or(usize::MAX, |limit| limit as usize))
+        .map(|item| {
+            let mut item = item?;
+            if include_metadata {
+                item.metadata = OFFERING_METADATA.may_load(deps.storage, item.offering_id.0)?;
+            }
+            Ok(item)
         })
         .collect();
+    let res = res?;
+    let next_start_after = match (limit, res.last()) {
+        (Some(limit), Some(last)) if res.len() == limit as usize => Some(OfferingsCursor {
+            offering_id: last.offering_id,
+            list_price: last.list_price,
+            listed_at: last.listed_at,
+        }),
+        _ => None,
+    };
     Ok(OfferingsResponse {
-        offerings: res?, // Placeholder
+        offerings: res, // Placeholder
+        next_start_after,
     })
 }
 
-fn parse_offering(item: StdResult<(String, Offering)>) -> StdResult<QueryOfferingsResult> {
+// the range after `start` in the given order
+fn page_bounds<'a, K: PrimaryKey<'a>>(
+    start: Option<K>,
+    order: Order,
+) -> (Option<Bound<'a, K>>, Option<Bound<'a, K>>) {
+    let bound = start.map(Bound::exclusive);
+    match order {
+        Order::Ascending => (bound, None),
+        Order::Descending => (None, bound),
+    }
+}
+
+fn parse_offering(item: StdResult<(u64, Offering)>) -> StdResult<QueryOfferingsResult> {
     item.map(|(k, offering)| QueryOfferingsResult {
-        offering_id: k,
+        offering_id: k.into(),
         token_id: offering.token_id,
         list_denom: offering.list_denom,
         list_price: offering.list_price,
+        usd_price: offering.usd_price,
         contract_addr: offering.contract_addr,
         seller: offering.seller,
+        expiration: offering.expiration,
+        non_custodial: offering.non_custodial,
+        listed_height: offering.listed_height,
+        listed_at: offering.listed_at,
+        metadata: None,
     })
 }
 
@@ -52,6 +171,15 @@ pub fn query_contract_info(deps: Deps) -> StdResult<ContractInfoResponse> {
         platform_fee: config.platform_fee,
         version: config.version,
         contact: "reece@crafteconomy.io".to_string(),
+        admin: config.admin,
+        pull_payments: config.pull_payments,
+        verify_collections: config.verify_collections,
+        max_price_age: config.max_price_age,
+        featured_slots: config.featured_slots,
+        referral_fee_bps: config.referral_fee_bps,
+        listing_deposit: config.listing_deposit,
+        max_listings_per_seller: config.max_listings_per_seller,
+        ics721_bridge: config.ics721_bridge,
     })
 }
 
@@ -67,3 +195,445 @@ pub fn query_collection_volume(
         denom,
     })
 }
+
+pub fn query_stuck_transfers(
+    deps: Deps,
+    filter_address: Option<String>,
+) -> StdResult<StuckTransfersResponse> {
+    let transfers = STUCK_TRANSFERS
+        .range(deps.storage, None, None, Order::Ascending)
+        .filter(|item| match (item, &filter_address) {
+            (Ok((_, transfer)), Some(address)) => {
+                transfer.offering.seller == *address
+                    || transfer.recipient == *address
+                    || transfer.buyer.iter().any(|buyer| buyer == address)
+            }
+            _ => true,
+        })
+        .map(|item| item.map(|(id, transfer)| stuck_transfer_result(id, transfer)))
+        .collect::<StdResult<_>>()?;
+
+    Ok(StuckTransfersResponse { transfers })
+}
+
+fn stuck_transfer_result(offering_id: u64, transfer: NftTransfer) -> StuckTransferResult {
+    StuckTransferResult {
+        offering_id: offering_id.into(),
+        kind: transfer.kind,
+        token_id: transfer.offering.token_id,
+        contract_addr: transfer.offering.contract_addr,
+        seller: transfer.offering.seller,
+        recipient: transfer.recipient,
+        buyer: transfer.buyer,
+        paid: transfer.paid,
+        error: transfer.error.unwrap_or_default(),
+    }
+}
+
+// the failed transfers of a collection, there are few of them & nothing indexes them
+fn collection_stuck_transfers(deps: Deps, collection: &Addr) -> StdResult<Vec<(u64, NftTransfer)>> {
+    STUCK_TRANSFERS
+        .range(deps.storage, None, None, Order::Ascending)
+        .filter(|item| match item {
+            Ok((_, transfer)) => transfer.offering.contract_addr == *collection,
+            Err(_) => true,
+        })
+        .collect()
+}
+
+pub fn query_simulate_buy(
+    deps: Deps,
+    env: Env,
+    offering_id: u64,
+    buyer: String,
+    referrer: Option<String>,
+) -> StdResult<SimulateBuyResponse> {
+    let buyer = deps.api.addr_validate(&buyer)?;
+    let referrer = referrer
+        .map(|referrer| deps.api.addr_validate(&referrer))
+        .transpose()?;
+    let off = offerings().load(deps.storage, offering_id)?;
+    // without a recent oracle price the amount from listing time is shown
+    let (off, price_failure) = match current_price(deps, &env, &off) {
+        Ok(list_price) => (Offering { list_price, ..off }, None),
+        Err(_) => (off, Some(PurchaseFailure::PriceUnavailable)),
+    };
+    let split = purchase_payouts(deps, &off, referrer.as_ref())?;
+
+    let mut failure = purchase_failure(&off, &buyer, &env.block);
+    for address in [&buyer, &off.seller]
+        .iter()
+        .copied()
+        .chain(referrer.as_ref())
+    {
+        match check_not_blocked(deps, &env.block, address) {
+            Ok(()) => {}
+            Err(ContractError::Blocked { .. }) => {
+                failure = failure.or(Some(PurchaseFailure::Blocked));
+            }
+            Err(err) => return Err(StdError::generic_err(err.to_string())),
+        }
+    }
+    let failure = failure
+        .or(price_failure)
+        .or_else(|| stale_listing(deps, &env, &off));
+    let failure = match failure {
+        Some(failure) => Some(failure),
+        None => rejected_wash_trade(deps.storage, &env.block, &off, &buyer)?,
+    };
+
+    Ok(SimulateBuyResponse {
+        funds: vec![Coin::new(off.list_price.u128(), &off.list_denom)],
+        platform_fee: split.platform_fee,
+        seller_payout: split.seller,
+        referral_fee: split.referral,
+        fee_discount_bps: split.fee_discount_bps,
+        failure,
+    })
+}
+
+pub fn query_pending_proceeds(deps: Deps, address: String) -> StdResult<PendingProceedsResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let proceeds = PROCEEDS
+        .prefix(&address)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect::<StdResult<_>>()?;
+
+    Ok(PendingProceedsResponse { proceeds })
+}
+
+pub fn query_registered_collections(deps: Deps) -> StdResult<RegisteredCollectionsResponse> {
+    let collections = COLLECTIONS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            item.map(|(address, info)| RegisteredCollection {
+                address,
+                name: info.name,
+                symbol: info.symbol,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+
+    Ok(RegisteredCollectionsResponse { collections })
+}
+
+pub fn query_featured(
+    deps: Deps,
+    env: Env,
+    collection: Option<String>,
+) -> StdResult<FeaturedResponse> {
+    let promotions: Vec<_> = match collection {
+        Some(collection) => {
+            let collection = deps.api.addr_validate(&collection)?;
+            PROMOTIONS
+                .prefix(&collection)
+                .range(deps.storage, None, None, Order::Ascending)
+                .collect::<StdResult<_>>()?
+        }
+        None => PROMOTIONS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|((_, offering_id), promotion)| (offering_id, promotion)))
+            .collect::<StdResult<_>>()?,
+    };
+
+    let mut featured = vec![];
+    for (offering_id, promotion) in promotions {
+        // expired ones are only cleaned up by the next PromoteOffering of the collection
+        if promotion.expires <= env.block.time {
+            continue;
+        }
+        let offering = offerings().load(deps.storage, offering_id)?;
+        featured.push(FeaturedOffering {
+            offering: parse_offering(Ok((offering_id, offering)))?,
+            bid: promotion.bid,
+            expires: promotion.expires,
+        });
+    }
+    featured.sort_by_key(|f| Reverse(f.bid.amount));
+
+    Ok(FeaturedResponse { featured })
+}
+
+pub fn query_stale_listings(
+    deps: Deps,
+    env: Env,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<StaleListingsResponse> {
+    let (stale, next_start_after) = stale_listings(deps, &env, start_after, limit)?;
+    Ok(StaleListingsResponse {
+        listings: stale
+            .into_iter()
+            .map(|(offering_id, _, reason)| StaleListing {
+                offering_id: offering_id.into(),
+                reason,
+            })
+            .collect(),
+        next_start_after: next_start_after.map(OfferingId),
+    })
+}
+
+pub fn query_is_blocked(deps: Deps, env: Env, address: String) -> StdResult<BlockedResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let entry = active_block(deps.storage, &env.block, &address)?;
+    Ok(BlockedResponse {
+        blocked: entry.is_some(),
+        entry,
+    })
+}
+
+pub fn query_blocklist(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<BlocklistResponse> {
+    let start_after = start_after
+        .map(|address| deps.api.addr_validate(&address))
+        .transpose()?;
+    let limit = limit
+        .unwrap_or(DEFAULT_BLOCKLIST_LIMIT)
+        .min(MAX_BLOCKLIST_LIMIT) as usize;
+
+    let entries = BLOCKLIST
+        .range(
+            deps.storage,
+            start_after.as_ref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|item| {
+            item.map(|(address, entry)| BlocklistEntry {
+                address,
+                reason: entry.reason,
+                expires: entry.expires,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+
+    Ok(BlocklistResponse { entries })
+}
+
+pub fn query_referral_totals(deps: Deps, referrer: String) -> StdResult<ReferralTotalsResponse> {
+    let referrer = deps.api.addr_validate(&referrer)?;
+    let totals = REFERRAL_TOTALS
+        .may_load(deps.storage, &referrer)?
+        .unwrap_or_default();
+    Ok(ReferralTotalsResponse {
+        share_bps: referral_share(deps.storage, &referrer)?,
+        referrer,
+        purchases: totals.purchases,
+        earned: totals.earned,
+    })
+}
+
+pub fn query_fee_tiers(deps: Deps) -> StdResult<FeeTiersResponse> {
+    Ok(match FEE_TIERS.may_load(deps.storage)? {
+        Some(fee_tiers) => FeeTiersResponse {
+            denom: fee_tiers.denom,
+            tiers: fee_tiers.tiers,
+        },
+        None => FeeTiersResponse {
+            denom: DEFAULT_STAKING_DENOM.to_string(),
+            tiers: vec![],
+        },
+    })
+}
+
+pub fn query_rebates(deps: Deps, address: String) -> StdResult<RebatesResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    Ok(RebatesResponse {
+        total: REBATES
+            .may_load(deps.storage, &address)?
+            .unwrap_or_default(),
+        address,
+    })
+}
+
+pub fn query_prepaid_deposit(deps: Deps, address: String) -> StdResult<PrepaidDepositResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let amount = PREPAID_DEPOSITS
+        .may_load(deps.storage, &address)?
+        .unwrap_or_default();
+    Ok(PrepaidDepositResponse {
+        address,
+        amount: Coin::new(amount.u128(), CONTRACT_INFO.load(deps.storage)?.denom),
+    })
+}
+
+pub fn query_effective_owner(
+    deps: Deps,
+    env: Env,
+    collection: String,
+    token_id: String,
+) -> StdResult<EffectiveOwnerResponse> {
+    let collection = deps.api.addr_validate(&collection)?;
+    let stuck = collection_stuck_transfers(deps, &collection)?;
+    effective_owner(deps, &env, &stuck, collection, token_id)
+}
+
+pub fn query_effective_owners(
+    deps: Deps,
+    env: Env,
+    collection: String,
+    token_ids: Vec<String>,
+) -> StdResult<EffectiveOwnersResponse> {
+    if token_ids.len() > MAX_EFFECTIVE_OWNERS {
+        return Err(StdError::generic_err(format!(
+            "at most {} token_ids can be queried at once",
+            MAX_EFFECTIVE_OWNERS
+        )));
+    }
+    let collection = deps.api.addr_validate(&collection)?;
+    let stuck = collection_stuck_transfers(deps, &collection)?;
+    // a burned token must not fail the whole batch, its owner is left out with the error
+    let owners = token_ids
+        .into_iter()
+        .map(|token_id| {
+            effective_owner(deps, &env, &stuck, collection.clone(), token_id.clone())
+                .unwrap_or_else(|err| EffectiveOwnerResponse {
+                    collection: collection.clone(),
+                    token_id,
+                    owner: None,
+                    listing: None,
+                    stuck_transfer: None,
+                    error: Some(err.to_string()),
+                })
+        })
+        .collect();
+    Ok(EffectiveOwnersResponse { owners })
+}
+
+// the seller of a token the marketplace holds for a listing, or the recipient of its failed
+// transfer. Otherwise the owner the collection returns
+fn effective_owner(
+    deps: Deps,
+    env: &Env,
+    stuck: &[(u64, NftTransfer)],
+    collection: Addr,
+    token_id: String,
+) -> StdResult<EffectiveOwnerResponse> {
+    // a stale approval listing can be left next to a newer listing of the token, the escrowed
+    // one wins
+    let listings = offerings()
+        .idx
+        .token
+        .prefix((collection.clone(), token_id.clone()))
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let listing = listings
+        .iter()
+        .find(|(_, off)| !off.non_custodial)
+        .or_else(|| listings.first())
+        .map(|(id, off)| listing_state(env, *id, off));
+    let stuck_transfer = stuck
+        .iter()
+        .find(|(_, transfer)| transfer.offering.token_id == token_id)
+        .map(|(id, transfer)| stuck_transfer_result(*id, transfer.clone()));
+
+    let owner = match (&listing, &stuck_transfer) {
+        (Some(listing), _) if listing.escrowed => listing.seller.to_string(),
+        (_, Some(transfer)) => transfer.recipient.to_string(),
+        _ => {
+            Cw721Contract(collection.clone())
+                .owner_of(&deps.querier, &token_id, false)?
+                .owner
+        }
+    };
+    Ok(EffectiveOwnerResponse {
+        collection,
+        token_id,
+        owner: Some(owner),
+        listing,
+        stuck_transfer,
+        error: None,
+    })
+}
+
+fn listing_state(env: &Env, offering_id: u64, off: &Offering) -> ListingState {
+    ListingState {
+        offering_id: offering_id.into(),
+        seller: off.seller.clone(),
+        list_price: off.list_price,
+        list_denom: off.list_denom.clone(),
+        escrowed: !off.non_custodial,
+        expired: off.expiration.is_expired(&env.block),
+    }
+}
+
+pub fn query_escrowed_tokens(
+    deps: Deps,
+    env: Env,
+    owner: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<EscrowedTokensResponse> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let limit = limit
+        .unwrap_or(DEFAULT_ESCROWED_LIMIT)
+        .min(MAX_ESCROWED_LIMIT) as usize;
+
+    // approval listings are on the page but left out, the owner still holds those
+    let listed = offerings()
+        .idx
+        .seller
+        .prefix(owner.clone())
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|item| {
+            item.map(|(id, off)| {
+                let token = (!off.non_custodial).then(|| EscrowedToken {
+                    listing: Some(listing_state(&env, id, &off)),
+                    stuck_transfer: None,
+                    collection: off.contract_addr,
+                    token_id: off.token_id,
+                });
+                (id, token)
+            })
+        });
+    // a failed transfer keeps the id of its offering, the two never share one
+    let stuck = STUCK_TRANSFERS
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .filter(|item| match item {
+            Ok((_, transfer)) => transfer.recipient == owner,
+            Err(_) => true,
+        })
+        .take(limit)
+        .map(|item| {
+            item.map(|(id, transfer)| {
+                let token = EscrowedToken {
+                    collection: transfer.offering.contract_addr.clone(),
+                    token_id: transfer.offering.token_id.clone(),
+                    listing: None,
+                    stuck_transfer: Some(stuck_transfer_result(id, transfer)),
+                };
+                (id, Some(token))
+            })
+        });
+    let mut page = listed.chain(stuck).collect::<StdResult<Vec<_>>>()?;
+    page.sort_by_key(|(id, _)| *id);
+    page.truncate(limit);
+
+    let next_start_after = match page.len() {
+        len if len == limit => page.last().map(|(id, _)| OfferingId(*id)),
+        _ => None,
+    };
+    let tokens = page.into_iter().filter_map(|(_, token)| token).collect();
+
+    Ok(EscrowedTokensResponse {
+        owner,
+        tokens,
+        next_start_after,
+    })
+}
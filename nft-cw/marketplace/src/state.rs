@@ -1,10 +1,15 @@
+use crate::ics721::IbcOutgoingMsg;
 use crate::msg::ContractInfoResponse;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Addr, StdResult, Storage, Uint128};
+use cosmwasm_std::{
+    Addr, BankMsg, BlockInfo, Coin, Decimal, Empty, StdResult, Storage, Timestamp, Uint128,
+};
 // use cosmwasm_std::Coin;
+use cw721::ContractInfoResponse as Cw721ContractInfo;
 use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
+use cw_utils::Expiration;
 
 pub static _CONFIGKEY: &[u8] = b"config";
 
@@ -15,16 +20,279 @@ pub struct Offering {
     pub contract_addr: Addr,
     pub seller: Addr,
     pub list_price: Uint128,
+    /// once expired the offering can no longer be bought, only withdrawn
+    pub expiration: Expiration,
+    /// set for listings priced in USD. list_price is then only the amount at listing time, the
+    /// price is converted again with the oracle when bought
+    #[serde(default)]
+    pub usd_price: Option<Decimal>,
+    /// set for listings backed by a cw721 approval. The NFT stays with the seller until it is
+    /// bought, so the listing goes stale once they move the token or revoke the approval
+    #[serde(default)]
+    pub non_custodial: bool,
+    /// the listing deposit held for the offering, in the list_denom. Returned to the seller when
+    /// it sells or is withdrawn, see ReclaimExpired for when it is not
+    #[serde(default)]
+    pub deposit: Uint128,
+    /// the block the offering was listed at, zero for offerings listed before v0.5
+    #[serde(default)]
+    pub listed_height: u64,
+    #[serde(default)]
+    pub listed_at: Timestamp,
 }
 
-/// OFFERINGS is a map which maps the offering_id to an offering. Offering_id is derived from OFFERINGS_COUNT.
-pub const OFFERINGS: Map<&str, Offering> = Map::new("offerings");
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferKind {
+    Purchase,
+    Withdraw,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Payout {
+    pub recipient: Addr,
+    pub amount: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+pub struct Trait {
+    pub display_type: Option<String>,
+    pub trait_type: String,
+    pub value: String,
+}
+
+/// The cw721 NftInfo of a listed token. The extension follows the usual on-chain metadata
+/// layout, unknown fields are dropped. See MAX_METADATA_LEN / MAX_METADATA_TRAITS for the bounds.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+pub struct NftMetadata {
+    pub token_uri: Option<String>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub external_url: Option<String>,
+    pub animation_url: Option<String>,
+    pub attributes: Option<Vec<Trait>>,
+}
+
+/// An NFT leaving the marketplace. Kept until the cw721 contract has accepted the transfer,
+/// so the seller is only paid once the buyer actually owns the token.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct NftTransfer {
+    pub kind: TransferKind,
+    pub offering: Offering,
+    pub recipient: Addr,
+    /// set when the purchase was a gift, the payer is refunded instead of the recipient
+    #[serde(default)]
+    pub buyer: Option<Addr>,
+    pub paid: Vec<Coin>,
+    pub payouts: Vec<Payout>,
+    /// the referrers share of the platform fee, paid out with the payouts
+    #[serde(default)]
+    pub referral: Option<Payout>,
+    /// a purchase flagged as a wash trade, it earns no rebates
+    #[serde(default)]
+    pub wash_trade: bool,
+    /// set when the NFT is sent over ICS-721 instead of to the recipient
+    #[serde(default)]
+    pub ibc: Option<IbcDelivery>,
+    pub error: Option<String>,
+}
+
+/// An NFT sent over ICS-721 with a SendNft of `msg` to the bridge. The recipient only gets it if
+/// the packet fails.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct IbcDelivery {
+    pub bridge: Addr,
+    pub msg: IbcOutgoingMsg,
+}
+
+/// the senders on other chains a seller lets list bridged NFTs for them, keyed by the seller, the
+/// local channel the NFTs come in on & the remote sender. Nothing on this chain vouches for the
+/// seller a packet names, so without an entry here the listing is refused
+pub const BRIDGED_SENDERS: Map<(&Addr, &str, &str), Empty> = Map::new("bridged_senders");
+
+impl NftTransfer {
+    /// who gets the funds back if a stuck purchase is refunded
+    pub fn payer(&self) -> &Addr {
+        self.buyer.as_ref().unwrap_or(&self.recipient)
+    }
+}
+
+/// in flight transfers, keyed by the offering_id which is also used as the reply id
+pub const PENDING_TRANSFERS: Map<u64, NftTransfer> = Map::new("pending_transfers");
+
+/// transfers the cw721 contract rejected. The buyer / seller can retry these or claim a refund.
+pub const STUCK_TRANSFERS: Map<u64, NftTransfer> = Map::new("stuck_transfers");
+
+/// unclaimed purchase payouts when pull_payments is enabled, keyed by (recipient, denom)
+pub const PROCEEDS: Map<(&Addr, &str), Uint128> = Map::new("proceeds");
+
+/// the most of the platform fee (in basis points) a referrer can get, when it is less than the
+/// configured referral_fee_bps. Set by the admin with SetReferrerLimit
+pub const REFERRER_LIMITS: Map<&Addr, u16> = Map::new("referrer_limits");
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+pub struct ReferralTotals {
+    pub purchases: u64,
+    pub earned: Vec<Coin>,
+}
+
+/// referral fees paid or credited to each referrer, counted once the purchase is delivered
+pub const REFERRAL_TOTALS: Map<&Addr, ReferralTotals> = Map::new("referral_totals");
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Promotion {
+    pub seller: Addr,
+    pub bid: Coin,
+    pub expires: Timestamp,
+}
+
+/// NftInfo of each offering, fetched when it is listed & with RefreshMetadata
+pub const OFFERING_METADATA: Map<u64, NftMetadata> = Map::new("offering_metadata");
+
+/// paid promotions keyed by (collection, offering_id). The bid is held here until the promotion
+/// ends: refunded when it is outbid, paid to the fee receiver once it expires or the offering leaves
+pub const PROMOTIONS: Map<(&Addr, u64), Promotion> = Map::new("promotions");
+
+/// the approval listing of each (collection, token_id), so a token is only listed once
+pub const APPROVAL_LISTINGS: Map<(&Addr, &str), u64> = Map::new("approval_listings");
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct BlockEntry {
+    pub reason: Option<String>,
+    /// the address is allowed again once this expires
+    pub expires: Expiration,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct FeeTier {
+    /// the delegated stake a seller needs for this tier
+    pub min_stake: Uint128,
+    /// taken off the platform fee, in basis points of the fee
+    pub discount_bps: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct FeeTiers {
+    /// the staking denom counted, exp on craft
+    pub denom: String,
+    /// sorted by min_stake, the highest tier a seller reaches applies
+    pub tiers: Vec<FeeTier>,
+}
+
+/// platform fee discounts for stakers, unset until the admin configures them with UpdateFeeTiers
+pub const FEE_TIERS: Item<FeeTiers> = Item::new("fee_tiers");
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RebateRate {
+    pub denom: String,
+    /// loyalty tokens minted per unit of `denom` paid, to the buyer & to the seller
+    pub buyer: Decimal,
+    pub seller: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RebateConfig {
+    /// the cw20 loyalty token, the marketplace must be its minter
+    pub token: Addr,
+    pub rates: Vec<RebateRate>,
+}
+
+/// trading rebates minted on every sale, unset until the admin configures them with UpdateRebates
+pub const REBATE_CONFIG: Item<RebateConfig> = Item::new("rebate_config");
+
+/// loyalty tokens minted to each address as rebates
+pub const REBATES: Map<&Addr, Uint128> = Map::new("rebates");
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RebateMint {
+    pub offering_id: u64,
+    pub token: Addr,
+    pub recipient: Addr,
+    pub amount: Uint128,
+}
+
+/// the rebate mints of the last sale by their reply id. The token only replies when it fails a
+/// mint, which is then taken back off REBATES
+pub const REBATE_MINTS: Map<u64, RebateMint> = Map::new("rebate_mints");
+
+/// listing deposits paid ahead with PrepayDeposit, drawn from when an NFT is listed with SendNft
+pub const PREPAID_DEPOSITS: Map<&Addr, Uint128> = Map::new("prepaid_deposits");
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+pub struct WashTrading {
+    /// seconds a bought token has to be held before the buyer can sell it again
+    pub holding_period: u64,
+    /// seconds in which a second trade between the same two addresses is a ping-pong trade
+    pub pair_window: u64,
+    /// reject flagged sales, instead of leaving them out of the volume & rebates
+    pub reject: bool,
+}
+
+/// the wash trading safeguards, off (all zero) until the admin sets them with UpdateWashTrading
+pub const WASH_TRADING: Item<WashTrading> = Item::new("wash_trading");
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct LastSale {
+    pub buyer: Addr,
+    pub time: Timestamp,
+}
+
+/// the last sale of every token, keyed by (collection, token_id)
+pub const LAST_SALES: Map<(&Addr, &str), LastSale> = Map::new("last_sales");
+
+/// when two addresses last traded with each other, keyed by the pair sorted
+pub const PAIR_TRADES: Map<(&Addr, &Addr), Timestamp> = Map::new("pair_trades");
+
+/// addresses the admin blocked from listing & buying. Withdrawals, refunds & proceeds are never
+/// blocked, so the marketplace does not hold on to their assets
+pub const BLOCKLIST: Map<&Addr, BlockEntry> = Map::new("blocklist");
+
+/// the block on the address, if it has one which has not expired
+pub fn active_block(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    address: &Addr,
+) -> StdResult<Option<BlockEntry>> {
+    Ok(BLOCKLIST
+        .may_load(storage, address)?
+        .filter(|entry| !entry.expires.is_expired(block)))
+}
+
+/// the collection registry, the ContractInfo each collection must report
+pub const COLLECTIONS: Map<&Addr, Cw721ContractInfo> = Map::new("collections");
 
 pub const COLLECTION_VOLUME: Map<&str, Uint128> = Map::new("collection_volume");
 
 pub const OFFERINGS_COUNT: Item<u64> = Item::new("num_offerings");
 pub const CONTRACT_INFO: Item<ContractInfoResponse> = Item::new("marketplace_info");
 
+/// removes an offering which is leaving the marketplace. Returns the payment of its promotion, if
+/// it had one, to the fee receiver
+pub fn remove_offering(
+    storage: &mut dyn Storage,
+    offering_id: u64,
+    offering: &Offering,
+) -> StdResult<Option<BankMsg>> {
+    offerings().remove(storage, offering_id)?;
+    OFFERING_METADATA.remove(storage, offering_id);
+    if offering.non_custodial {
+        APPROVAL_LISTINGS.remove(storage, (&offering.contract_addr, &offering.token_id));
+    }
+
+    let key = (&offering.contract_addr, offering_id);
+    let promotion = match PROMOTIONS.may_load(storage, key)? {
+        Some(promotion) => promotion,
+        None => return Ok(None),
+    };
+    PROMOTIONS.remove(storage, key);
+
+    Ok(Some(BankMsg::Send {
+        to_address: CONTRACT_INFO.load(storage)?.fee_receive_address,
+        amount: vec![promotion.bid],
+    }))
+}
+
 pub fn num_offerings(storage: &dyn Storage) -> StdResult<u64> {
     Ok(OFFERINGS_COUNT.may_load(storage)?.unwrap_or_default())
 }
@@ -36,18 +304,36 @@ pub fn increment_offerings(storage: &mut dyn Storage) -> StdResult<u64> {
 }
 
 pub struct OfferingIndexes<'a> {
-    pub seller: MultiIndex<'a, Addr, Offering, (Addr, Vec<u8>)>,
-    pub contract: MultiIndex<'a, Addr, Offering, (Addr, Vec<u8>)>,
+    pub seller: MultiIndex<'a, Addr, Offering, u64>,
+    pub contract: MultiIndex<'a, Addr, Offering, u64>,
+    /// (collection, list_price), for GetOfferings sorted by price
+    pub price: MultiIndex<'a, (Addr, u128), Offering, u64>,
+    /// list_price across all collections, for GetOfferings sorted by price without a collection
+    pub all_price: MultiIndex<'a, u128, Offering, u64>,
+    /// (collection, listed_at in nanos), for GetOfferings sorted by listing time
+    pub listed_at: MultiIndex<'a, (Addr, u64), Offering, u64>,
+    /// (collection, token_id), to find the offering of a token
+    pub token: MultiIndex<'a, (Addr, String), Offering, u64>,
 }
 
 impl<'a> IndexList<Offering> for OfferingIndexes<'a> {
     fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Offering>> + '_> {
-        let v: Vec<&dyn Index<Offering>> = vec![&self.seller, &self.contract];
+        let v: Vec<&dyn Index<Offering>> = vec![
+            &self.seller,
+            &self.contract,
+            &self.price,
+            &self.all_price,
+            &self.listed_at,
+            &self.token,
+        ];
         Box::new(v.into_iter())
     }
 }
 
-pub fn offerings<'a>() -> IndexedMap<'a, &'a str, Offering, OfferingIndexes<'a>> {
+/// offerings maps the offering_id to an offering. Offering_id is derived from OFFERINGS_COUNT, the
+/// integer keys keep them in listing order.
+/// Always write through this so the indexes stay in sync.
+pub fn offerings<'a>() -> IndexedMap<'a, u64, Offering, OfferingIndexes<'a>> {
     let indexes = OfferingIndexes {
         seller: MultiIndex::new(
             |o: &Offering| o.seller.clone(),
@@ -60,6 +346,30 @@ pub fn offerings<'a>() -> IndexedMap<'a, &'a str, Offering, OfferingIndexes<'a>>
             "offerings",
             "offerings__contract",
         ),
+
+        price: MultiIndex::new(
+            |o: &Offering| (o.contract_addr.clone(), o.list_price.u128()),
+            "offerings",
+            "offerings__price",
+        ),
+
+        all_price: MultiIndex::new(
+            |o: &Offering| o.list_price.u128(),
+            "offerings",
+            "offerings__all_price",
+        ),
+
+        listed_at: MultiIndex::new(
+            |o: &Offering| (o.contract_addr.clone(), o.listed_at.nanos()),
+            "offerings",
+            "offerings__listed_at",
+        ),
+
+        token: MultiIndex::new(
+            |o: &Offering| (o.contract_addr.clone(), o.token_id.clone()),
+            "offerings",
+            "offerings__token",
+        ),
     };
     IndexedMap::new("offerings", indexes)
 }
@@ -158,11 +158,11 @@ craftd tx wasm execute $ADDRM '{"buy_nft":{"offering_id":"2"}}' --gas-prices="0.
 craftd tx wasm execute $ADDRM '{"update_listing_price":{"offering_id":"1","new_price":"6"}}' --gas-prices="0.025ucraft" -y --from $KEY
 
 # update params as the DAO
-# update_fee_receiver_address, update_platform_fee, force_withdraw_all
+# update_fee_receiver_address, update_platform_fee, force_withdraw
 craftd query wasm contract-state smart $ADDRM '{"get_contract_info": {}}'
 
 craftd tx wasm execute $ADDRM '{"update_platform_fee":{"new_fee":"0"}}' --gas-prices="0.025ucraft" -y --from $KEY
-craftd tx wasm execute $ADDRM '{"force_withdraw_all":{}}' --gas-prices="0.025ucraft" -y --from $KEY
+craftd tx wasm execute $ADDRM '{"force_withdraw":{"limit":30}}' --gas-prices="0.025ucraft" -y --from $KEY
 
 # FUTURE TO DO
 craftd tx wasm migrate $ADDRM 3 '{"migrate_msg":{}}' --gas-prices="0.025ucraft" -y --from $KEY
\ No newline at end of file
@@ -0,0 +1,2 @@
+#/bin/sh
+cargo clippy --all-targets -- --D warnings
@@ -0,0 +1,24 @@
+use std::env::current_dir;
+use std::fs::create_dir_all;
+
+use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
+
+use craft_vault::msg::{ExecuteMsg, FractionalizeMsg, InitMsg, QueryMsg, ShareReceiveMsg};
+use craft_vault::msg::{VaultResponse, VaultsResponse};
+use craft_vault::state::Config;
+
+fn main() {
+    let mut out_dir = current_dir().unwrap();
+    out_dir.push("schema");
+    create_dir_all(&out_dir).unwrap();
+    remove_schemas(&out_dir).unwrap();
+
+    export_schema(&schema_for!(InitMsg), &out_dir);
+    export_schema(&schema_for!(ExecuteMsg), &out_dir);
+    export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(FractionalizeMsg), &out_dir);
+    export_schema(&schema_for!(ShareReceiveMsg), &out_dir);
+    export_schema(&schema_for!(Config), &out_dir);
+    export_schema(&schema_for!(VaultResponse), &out_dir);
+    export_schema(&schema_for!(VaultsResponse), &out_dir);
+}
@@ -0,0 +1,92 @@
+use cosmwasm_std::{
+    entry_point, to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdResult,
+};
+use cw2::set_contract_version;
+
+use crate::error::ContractError;
+use crate::execute;
+use crate::msg::{ExecuteMsg, InitMsg, MigrateMsg, QueryMsg};
+use crate::queries;
+use crate::state::{Config, CONFIG};
+
+pub const CONTRACT_NAME: &str = "crates.io:craft-vault";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[entry_point]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: InitMsg,
+) -> StdResult<Response> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    let admin = match msg.admin {
+        Some(admin) => deps.api.addr_validate(&admin)?,
+        None => info.sender,
+    };
+    CONFIG.save(
+        deps.storage,
+        &Config {
+            cw20_code_id: msg.cw20_code_id,
+            denom: msg.denom,
+            admin,
+        },
+    )?;
+    Ok(Response::new().add_attribute("action", "instantiate"))
+}
+
+#[entry_point]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::ReceiveNft(msg) => execute::receive_nft(deps, env, info, msg),
+        ExecuteMsg::Receive(msg) => execute::receive_shares(deps, info, msg),
+        ExecuteMsg::Buyout {
+            vault_id,
+            recipient,
+        } => execute::buyout(deps, info, vault_id, recipient),
+        ExecuteMsg::UpdateCw20CodeId { code_id } => {
+            execute::update_cw20_code_id(deps, info, code_id)
+        }
+    }
+}
+
+#[entry_point]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::GetConfig {} => to_binary(&queries::query_config(deps)?),
+        QueryMsg::GetVault { vault_id } => to_binary(&queries::query_vault(deps, vault_id)?),
+        QueryMsg::GetVaults { start_after, limit } => {
+            to_binary(&queries::query_vaults(deps, start_after, limit)?)
+        }
+    }
+}
+
+// every reply is the instantiation of a vault's share token
+#[entry_point]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    execute::shares_reply(deps, msg)
+}
+
+#[entry_point]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let ver = cw2::get_contract_version(deps.storage)?;
+    if ver.contract != CONTRACT_NAME {
+        return Err(ContractError::CannotMigrate {
+            contract: ver.contract,
+            from: ver.version,
+            to: CONTRACT_VERSION.to_string(),
+        });
+    }
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::default()
+        .add_attribute("action", "migration")
+        .add_attribute("from_version", ver.version)
+        .add_attribute("version", CONTRACT_VERSION))
+}
@@ -0,0 +1,434 @@
+use cosmwasm_std::testing::{
+    mock_env, mock_info, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
+};
+use cosmwasm_std::{
+    coin, coins, from_binary, to_binary, Addr, BankMsg, Binary, ContractResult, CosmosMsg, DepsMut,
+    OwnedDeps, Reply, ReplyOn, SubMsgResponse, SubMsgResult, SystemResult, Uint128, WasmMsg,
+    WasmQuery,
+};
+use cw20::{Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg, TokenInfoResponse};
+use cw20_base::msg::InstantiateMsg as Cw20InstantiateMsg;
+use cw721::{Cw721ExecuteMsg, Cw721QueryMsg, Cw721ReceiveMsg, OwnerOfResponse};
+
+use crate::contract::{execute, instantiate, query, reply};
+use crate::error::ContractError;
+use crate::events::EVENT_SCHEMA_VERSION;
+use crate::msg::{
+    ExecuteMsg, FractionalizeMsg, InitMsg, QueryMsg, ShareReceiveMsg, VaultResponse, VaultsResponse,
+};
+use crate::state::Buyout;
+
+const DENOM: &str = "ucraft";
+const CW20_CODE_ID: u64 = 7;
+const SHARES: &str = "shares_token";
+// share token whose holders burned every share before the buyout
+const BURNED_SHARES: &str = "burned_shares_token";
+
+// OwnerOf answers with the vault, unless the token id starts with "unsent".
+// TokenInfo reports a supply of 1000 shares, or 0 for BURNED_SHARES.
+fn mock_deps() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+    let mut querier = MockQuerier::new(&[]);
+    querier.update_wasm(|query| match query {
+        WasmQuery::Smart { contract_addr, msg } => {
+            let res = if let Ok(Cw721QueryMsg::OwnerOf { token_id, .. }) = from_binary(msg) {
+                let owner = match token_id.starts_with("unsent") {
+                    true => "depositor",
+                    false => MOCK_CONTRACT_ADDR,
+                };
+                to_binary(&OwnerOfResponse {
+                    owner: owner.to_string(),
+                    approvals: vec![],
+                })
+            } else if let Ok(Cw20QueryMsg::TokenInfo {}) = from_binary(msg) {
+                let total_supply = match contract_addr.as_str() {
+                    BURNED_SHARES => Uint128::zero(),
+                    _ => Uint128::new(1000),
+                };
+                to_binary(&TokenInfoResponse {
+                    name: "Shares".to_string(),
+                    symbol: "SHR".to_string(),
+                    decimals: 6,
+                    total_supply,
+                })
+            } else {
+                panic!("unexpected query {:?}", msg)
+            };
+            SystemResult::Ok(ContractResult::Ok(res.unwrap()))
+        }
+        _ => panic!("unexpected query {:?}", query),
+    });
+    OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier,
+        custom_query_type: Default::default(),
+    }
+}
+
+fn initialize_contract(deps: DepsMut) {
+    let msg = InitMsg {
+        cw20_code_id: CW20_CODE_ID,
+        denom: DENOM.to_string(),
+        admin: None,
+    };
+    instantiate(deps, mock_env(), mock_info("admin", &[]), msg).unwrap();
+}
+
+fn fractionalize(
+    deps: DepsMut,
+    token_id: &str,
+    supply: u128,
+    reserve_price: u128,
+) -> Result<cosmwasm_std::Response, ContractError> {
+    let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+        sender: "depositor".to_string(),
+        token_id: token_id.to_string(),
+        msg: to_binary(&FractionalizeMsg {
+            name: "Craft Tower Shares".to_string(),
+            symbol: "TOWER".to_string(),
+            supply: Uint128::new(supply),
+            reserve_price: Uint128::new(reserve_price),
+        })
+        .unwrap(),
+    });
+    execute(deps, mock_env(), mock_info("collection", &[]), msg)
+}
+
+// MsgInstantiateContractResponse { contract_address } as the chain encodes it
+fn instantiate_reply(id: u64, contract_address: &str) -> Reply {
+    let mut data = vec![0x0a, contract_address.len() as u8];
+    data.extend_from_slice(contract_address.as_bytes());
+    Reply {
+        id,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: Some(Binary(data)),
+        }),
+    }
+}
+
+fn create_vault(mut deps: DepsMut, shares: &str, supply: u128, reserve_price: u128) -> u64 {
+    let res = fractionalize(deps.branch(), "tower", supply, reserve_price).unwrap();
+    let id = res.messages[0].id;
+    reply(deps, mock_env(), instantiate_reply(id, shares)).unwrap();
+    id
+}
+
+fn redeem(
+    deps: DepsMut,
+    shares: &str,
+    holder: &str,
+    amount: u128,
+) -> Result<cosmwasm_std::Response, ContractError> {
+    let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+        sender: holder.to_string(),
+        amount: Uint128::new(amount),
+        msg: to_binary(&ShareReceiveMsg::Redeem {}).unwrap(),
+    });
+    execute(deps, mock_env(), mock_info(shares, &[]), msg)
+}
+
+fn get_vault(deps: cosmwasm_std::Deps, vault_id: u64) -> VaultResponse {
+    from_binary(&query(deps, mock_env(), QueryMsg::GetVault { vault_id }).unwrap()).unwrap()
+}
+
+#[test]
+fn fractionalize_instantiates_share_token() {
+    let mut deps = mock_deps();
+    initialize_contract(deps.as_mut());
+
+    let res = fractionalize(deps.as_mut(), "tower", 1000, 50_000_000).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    let sub = &res.messages[0];
+    assert_eq!(sub.id, 1);
+    assert_eq!(sub.reply_on, ReplyOn::Success);
+    match &sub.msg {
+        CosmosMsg::Wasm(WasmMsg::Instantiate { code_id, msg, .. }) => {
+            assert_eq!(*code_id, CW20_CODE_ID);
+            let msg: Cw20InstantiateMsg = from_binary(msg).unwrap();
+            assert_eq!(msg.symbol, "TOWER");
+            assert_eq!(msg.initial_balances.len(), 1);
+            assert_eq!(msg.initial_balances[0].address, "depositor");
+            assert_eq!(msg.initial_balances[0].amount, Uint128::new(1000));
+            // a fixed supply, nobody can mint more shares
+            assert!(msg.mint.is_none());
+        }
+        msg => panic!("unexpected message {:?}", msg),
+    }
+    let event = &res.events[0];
+    assert_eq!(event.ty, "craft_vault.fractionalize");
+    assert!(event
+        .attributes
+        .iter()
+        .any(|a| a.key == "schema_version" && a.value == EVENT_SCHEMA_VERSION));
+    assert_eq!(get_vault(deps.as_ref(), 1).shares, None);
+
+    // the reply captures the share token address
+    reply(deps.as_mut(), mock_env(), instantiate_reply(1, SHARES)).unwrap();
+    let vault = get_vault(deps.as_ref(), 1);
+    assert_eq!(vault.shares, Some(SHARES.to_string()));
+    assert_eq!(vault.collection, "collection");
+    assert_eq!(vault.depositor, "depositor");
+    assert_eq!(vault.reserve_price, coin(50_000_000, DENOM));
+
+    let res: VaultsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetVaults {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.vaults, vec![vault]);
+}
+
+#[test]
+fn fractionalize_rejects_bad_deposits() {
+    let mut deps = mock_deps();
+    initialize_contract(deps.as_mut());
+
+    let err = fractionalize(deps.as_mut(), "unsent_tower", 1000, 50).unwrap_err();
+    assert!(matches!(err, ContractError::NftNotReceived { .. }));
+    let err = fractionalize(deps.as_mut(), "tower", 0, 50).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidVault {}));
+    let err = fractionalize(deps.as_mut(), "tower", 1000, 0).unwrap_err();
+    assert!(matches!(err, ContractError::InvalidVault {}));
+
+    // a failed instantiate never reaches the vault, it reverts the deposit
+    let err = reply(
+        deps.as_mut(),
+        mock_env(),
+        Reply {
+            id: 1,
+            result: SubMsgResult::Err("bad symbol".to_string()),
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::ParseReply(_)));
+}
+
+#[test]
+fn buyout_sends_nft_and_refunds_change() {
+    let mut deps = mock_deps();
+    initialize_contract(deps.as_mut());
+    let id = create_vault(deps.as_mut(), SHARES, 1000, 500);
+
+    let buy = ExecuteMsg::Buyout {
+        vault_id: id,
+        recipient: None,
+    };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("buyer", &coins(499, DENOM)),
+        buy.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::InsufficientFundsSend { .. }));
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("buyer", &coins(500, "uother")),
+        buy.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Payment(_)));
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("buyer", &coins(600, DENOM)),
+        buy.clone(),
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "collection".to_string(),
+            msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: "buyer".to_string(),
+                token_id: "tower".to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    );
+    assert_eq!(
+        res.messages[1].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "buyer".to_string(),
+            amount: coins(100, DENOM),
+        })
+    );
+    assert_eq!(
+        get_vault(deps.as_ref(), id).buyout,
+        Some(Buyout {
+            buyer: Addr::unchecked("buyer"),
+            remaining_payment: Uint128::new(500),
+            remaining_shares: Uint128::new(1000),
+        })
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("other", &coins(500, DENOM)),
+        buy,
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::AlreadyBoughtOut { .. }));
+}
+
+#[test]
+fn buyout_of_burned_shares_pays_depositor() {
+    let mut deps = mock_deps();
+    initialize_contract(deps.as_mut());
+    let id = create_vault(deps.as_mut(), BURNED_SHARES, 1000, 500);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("buyer", &coins(500, DENOM)),
+        ExecuteMsg::Buyout {
+            vault_id: id,
+            recipient: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[1].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "depositor".to_string(),
+            amount: coins(500, DENOM),
+        })
+    );
+    let buyout = get_vault(deps.as_ref(), id).buyout.unwrap();
+    assert!(buyout.remaining_payment.is_zero());
+}
+
+#[test]
+fn buyout_as_gift() {
+    let mut deps = mock_deps();
+    initialize_contract(deps.as_mut());
+    let id = create_vault(deps.as_mut(), SHARES, 1000, 500);
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("server", &coins(500, DENOM)),
+        ExecuteMsg::Buyout {
+            vault_id: id,
+            recipient: Some("player".to_string()),
+        },
+    )
+    .unwrap();
+    match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => {
+            let msg: Cw721ExecuteMsg = from_binary(msg).unwrap();
+            assert_eq!(
+                msg,
+                Cw721ExecuteMsg::TransferNft {
+                    recipient: "player".to_string(),
+                    token_id: "tower".to_string(),
+                }
+            );
+        }
+        msg => panic!("unexpected message {:?}", msg),
+    }
+    let attributes = &res.events[0].attributes;
+    assert!(attributes
+        .iter()
+        .any(|a| a.key == "buyer" && a.value == "server"));
+    assert!(attributes
+        .iter()
+        .any(|a| a.key == "recipient" && a.value == "player"));
+    // the payer holds the buyout, not the player
+    assert_eq!(
+        get_vault(deps.as_ref(), id).buyout.unwrap().buyer,
+        Addr::unchecked("server")
+    );
+}
+
+#[test]
+fn redeem_pays_pro_rata_without_dust() {
+    let mut deps = mock_deps();
+    initialize_contract(deps.as_mut());
+    let id = create_vault(deps.as_mut(), SHARES, 1000, 1001);
+
+    let err = redeem(deps.as_mut(), SHARES, "holder1", 300).unwrap_err();
+    assert!(matches!(err, ContractError::NotBoughtOut { .. }));
+    let err = redeem(deps.as_mut(), "fake_shares", "holder1", 300).unwrap_err();
+    assert!(matches!(err, ContractError::UnknownShareToken { .. }));
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("buyer", &coins(1001, DENOM)),
+        ExecuteMsg::Buyout {
+            vault_id: id,
+            recipient: None,
+        },
+    )
+    .unwrap();
+
+    let mut paid = 0;
+    for (holder, shares, expected) in [
+        ("holder1", 300, 300),
+        ("holder2", 3, 3),
+        ("depositor", 697, 698),
+    ] {
+        let res = redeem(deps.as_mut(), SHARES, holder, shares).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: SHARES.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Burn {
+                    amount: Uint128::new(shares),
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+        assert_eq!(
+            res.messages[1].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: holder.to_string(),
+                amount: coins(expected, DENOM),
+            })
+        );
+        paid += expected;
+    }
+    assert_eq!(paid, 1001);
+
+    // every share was redeemed, nothing more can be burned for a payout
+    let err = redeem(deps.as_mut(), SHARES, "holder1", 1).unwrap_err();
+    assert!(matches!(err, ContractError::Std(_)));
+}
+
+#[test]
+fn update_cw20_code_id_admin_only() {
+    let mut deps = mock_deps();
+    initialize_contract(deps.as_mut());
+
+    let msg = ExecuteMsg::UpdateCw20CodeId { code_id: 9 };
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("anyone", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized { .. }));
+    execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+    let res = fractionalize(deps.as_mut(), "tower", 10, 10).unwrap();
+    match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Instantiate { code_id, .. }) => assert_eq!(*code_id, 9),
+        msg => panic!("unexpected message {:?}", msg),
+    }
+}
@@ -0,0 +1,49 @@
+use cosmwasm_std::StdError;
+use cw_utils::{ParseReplyError, PaymentError};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
+    #[error("{0}")]
+    ParseReply(#[from] ParseReplyError),
+
+    #[error("Unauthorized. Reason: {msg:?}")]
+    Unauthorized { msg: String },
+
+    #[error("The vault did not receive {collection} {token_id}")]
+    NftNotReceived {
+        collection: String,
+        token_id: String,
+    },
+
+    #[error("Supply and reserve price must both be above zero")]
+    InvalidVault {},
+
+    #[error("Vault {id} does not have its share token yet")]
+    SharesNotCreated { id: u64 },
+
+    #[error("Vault {id} was already bought out")]
+    AlreadyBoughtOut { id: u64 },
+
+    #[error("Vault {id} has not been bought out, shares can not be redeemed yet")]
+    NotBoughtOut { id: u64 },
+
+    #[error("{address} is not the share token of any vault")]
+    UnknownShareToken { address: String },
+
+    #[error("Insufficient funds. Needed: {needed:?}, Received: {received:?}")]
+    InsufficientFundsSend { needed: String, received: String },
+
+    #[error("Cannot migrate {contract} from {from} to {to}")]
+    CannotMigrate {
+        contract: String,
+        from: String,
+        to: String,
+    },
+}
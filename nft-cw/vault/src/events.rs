@@ -0,0 +1,114 @@
+use cosmwasm_std::{Event as WasmEvent, Response, Uint128};
+use cw_utils::Event;
+
+// Same layout as the marketplace: every action emits one `wasm-craft_vault.<action>` event. Bump
+// EVENT_SCHEMA_VERSION whenever an attribute is renamed or removed.
+pub const EVENT_SCHEMA_VERSION: &str = "1";
+const EVENT_PREFIX: &str = "craft_vault";
+
+fn vault_event(action: &str) -> WasmEvent {
+    WasmEvent::new(format!("{}.{}", EVENT_PREFIX, action))
+        .add_attribute("schema_version", EVENT_SCHEMA_VERSION)
+}
+
+pub struct FractionalizeEvent<'a> {
+    pub vault_id: u64,
+    pub collection: &'a str,
+    pub token_id: &'a str,
+    pub depositor: &'a str,
+    pub supply: Uint128,
+    pub reserve_price: Uint128,
+    pub denom: &'a str,
+}
+
+impl<'a> Event for FractionalizeEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            vault_event("fractionalize")
+                .add_attribute("vault_id", self.vault_id.to_string())
+                .add_attribute("collection", self.collection)
+                .add_attribute("token_id", self.token_id)
+                .add_attribute("depositor", self.depositor)
+                .add_attribute("supply", self.supply)
+                .add_attribute("reserve_price", self.reserve_price)
+                .add_attribute("denom", self.denom),
+        );
+    }
+}
+
+pub struct SharesCreatedEvent<'a> {
+    pub vault_id: u64,
+    pub shares: &'a str,
+}
+
+impl<'a> Event for SharesCreatedEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            vault_event("shares_created")
+                .add_attribute("vault_id", self.vault_id.to_string())
+                .add_attribute("shares", self.shares),
+        );
+    }
+}
+
+pub struct BuyoutEvent<'a> {
+    pub vault_id: u64,
+    pub collection: &'a str,
+    pub token_id: &'a str,
+    pub buyer: &'a str,
+    /// who receives the NFT, the buyer unless it was bought as a gift
+    pub recipient: &'a str,
+    pub amount: Uint128,
+    pub denom: &'a str,
+}
+
+impl<'a> Event for BuyoutEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            vault_event("buyout")
+                .add_attribute("vault_id", self.vault_id.to_string())
+                .add_attribute("collection", self.collection)
+                .add_attribute("token_id", self.token_id)
+                .add_attribute("buyer", self.buyer)
+                .add_attribute("recipient", self.recipient)
+                .add_attribute("amount", self.amount)
+                .add_attribute("denom", self.denom),
+        );
+    }
+}
+
+pub struct RedeemEvent<'a> {
+    pub vault_id: u64,
+    pub holder: &'a str,
+    pub shares: Uint128,
+    pub amount: Uint128,
+    pub denom: &'a str,
+}
+
+impl<'a> Event for RedeemEvent<'a> {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            vault_event("redeem")
+                .add_attribute("vault_id", self.vault_id.to_string())
+                .add_attribute("holder", self.holder)
+                .add_attribute("shares", self.shares)
+                .add_attribute("amount", self.amount)
+                .add_attribute("denom", self.denom),
+        );
+    }
+}
+
+pub struct UpdateCw20CodeIdEvent {
+    pub old_code_id: u64,
+    pub new_code_id: u64,
+}
+
+impl Event for UpdateCw20CodeIdEvent {
+    fn add_attributes(&self, response: &mut Response) {
+        response.events.push(
+            vault_event("update_cw20_code_id")
+                .add_attribute("old_code_id", self.old_code_id.to_string())
+                .add_attribute("new_code_id", self.new_code_id.to_string()),
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use cosmwasm_std::{
+    coin, from_binary, to_binary, Addr, BankMsg, DepsMut, Env, MessageInfo, Reply, Response,
+    StdError, SubMsg, Uint128, WasmMsg,
+};
+use cw20::{Cw20Coin, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg, TokenInfoResponse};
+use cw20_base::msg::InstantiateMsg as Cw20InstantiateMsg;
+use cw721::{Cw721ExecuteMsg, Cw721QueryMsg, Cw721ReceiveMsg, OwnerOfResponse};
+use cw_utils::{must_pay, parse_reply_instantiate_data, Event};
+
+use crate::error::ContractError;
+use crate::events::{
+    BuyoutEvent, FractionalizeEvent, RedeemEvent, SharesCreatedEvent, UpdateCw20CodeIdEvent,
+};
+use crate::msg::{FractionalizeMsg, ShareReceiveMsg};
+use crate::state::{increment_vaults, Buyout, Vault, CONFIG, SHARE_TOKENS, VAULTS};
+
+pub const SHARE_DECIMALS: u8 = 6;
+
+pub fn receive_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rcv_msg: Cw721ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let msg: FractionalizeMsg = from_binary(&rcv_msg.msg)?;
+    if msg.supply.is_zero() || msg.reserve_price.is_zero() {
+        return Err(ContractError::InvalidVault {});
+    }
+
+    // anyone can send a ReceiveNft, so ask the sending collection if we really hold the token now
+    let owner: OwnerOfResponse = deps.querier.query_wasm_smart(
+        &info.sender,
+        &Cw721QueryMsg::OwnerOf {
+            token_id: rcv_msg.token_id.clone(),
+            include_expired: None,
+        },
+    )?;
+    if owner.owner != env.contract.address {
+        return Err(ContractError::NftNotReceived {
+            collection: info.sender.into_string(),
+            token_id: rcv_msg.token_id,
+        });
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    let id = increment_vaults(deps.storage)?;
+    let vault = Vault {
+        collection: info.sender,
+        token_id: rcv_msg.token_id,
+        depositor: deps.api.addr_validate(&rcv_msg.sender)?,
+        shares: None,
+        supply: msg.supply,
+        reserve_price: coin(msg.reserve_price.u128(), config.denom),
+        buyout: None,
+    };
+    VAULTS.save(deps.storage, id, &vault)?;
+
+    // no minter, the whole fixed supply goes to the depositor
+    let instantiate = WasmMsg::Instantiate {
+        admin: None,
+        code_id: config.cw20_code_id,
+        msg: to_binary(&Cw20InstantiateMsg {
+            name: msg.name,
+            symbol: msg.symbol,
+            decimals: SHARE_DECIMALS,
+            initial_balances: vec![Cw20Coin {
+                address: vault.depositor.to_string(),
+                amount: vault.supply,
+            }],
+            mint: None,
+            marketing: None,
+        })?,
+        funds: vec![],
+        label: format!("craft vault {} shares", id),
+    };
+
+    let mut res = Response::new()
+        .add_attribute("action", "fractionalize")
+        .add_attribute("vault_id", id.to_string())
+        // the reply id is the vault id, so the reply knows which vault the token belongs to
+        .add_submessage(SubMsg::reply_on_success(instantiate, id));
+    FractionalizeEvent {
+        vault_id: id,
+        collection: vault.collection.as_str(),
+        token_id: &vault.token_id,
+        depositor: vault.depositor.as_str(),
+        supply: vault.supply,
+        reserve_price: vault.reserve_price.amount,
+        denom: &vault.reserve_price.denom,
+    }
+    .add_attributes(&mut res);
+    Ok(res)
+}
+
+// stores the address of the share token which was instantiated by receive_nft
+pub fn shares_reply(deps: DepsMut, msg: Reply) -> Result<Response, ContractError> {
+    let id = msg.id;
+    let res = parse_reply_instantiate_data(msg)?;
+    let shares = deps.api.addr_validate(&res.contract_address)?;
+
+    let mut vault = VAULTS.load(deps.storage, id)?;
+    vault.shares = Some(shares.clone());
+    VAULTS.save(deps.storage, id, &vault)?;
+    SHARE_TOKENS.save(deps.storage, &shares, &id)?;
+
+    let mut res = Response::new()
+        .add_attribute("action", "shares_created")
+        .add_attribute("shares", shares.as_str());
+    SharesCreatedEvent {
+        vault_id: id,
+        shares: shares.as_str(),
+    }
+    .add_attributes(&mut res);
+    Ok(res)
+}
+
+pub fn buyout(
+    deps: DepsMut,
+    info: MessageInfo,
+    vault_id: u64,
+    recipient: Option<String>,
+) -> Result<Response, ContractError> {
+    let recipient = match recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => info.sender.clone(),
+    };
+    let mut vault = VAULTS.load(deps.storage, vault_id)?;
+    if vault.buyout.is_some() {
+        return Err(ContractError::AlreadyBoughtOut { id: vault_id });
+    }
+    let shares = vault
+        .shares
+        .clone()
+        .ok_or(ContractError::SharesNotCreated { id: vault_id })?;
+
+    let price = &vault.reserve_price;
+    let paid = must_pay(&info, &price.denom)?;
+    if paid < price.amount {
+        return Err(ContractError::InsufficientFundsSend {
+            needed: price.to_string(),
+            received: coin(paid.u128(), &price.denom).to_string(),
+        });
+    }
+
+    // holders may have burned shares directly on the token, only what is left gets paid out
+    let token_info: TokenInfoResponse = deps
+        .querier
+        .query_wasm_smart(&shares, &Cw20QueryMsg::TokenInfo {})?;
+
+    let mut bank_msgs = vec![];
+    let change = paid - price.amount;
+    if !change.is_zero() {
+        bank_msgs.push(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![coin(change.u128(), &price.denom)],
+        });
+    }
+    let mut remaining_payment = price.amount;
+    if token_info.total_supply.is_zero() {
+        // nobody is left to redeem, the depositor gets the whole payment
+        bank_msgs.push(BankMsg::Send {
+            to_address: vault.depositor.to_string(),
+            amount: vec![price.clone()],
+        });
+        remaining_payment = Uint128::zero();
+    }
+
+    vault.buyout = Some(Buyout {
+        buyer: info.sender.clone(),
+        remaining_payment,
+        remaining_shares: token_info.total_supply,
+    });
+    VAULTS.save(deps.storage, vault_id, &vault)?;
+
+    let transfer = WasmMsg::Execute {
+        contract_addr: vault.collection.to_string(),
+        msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+            recipient: recipient.to_string(),
+            token_id: vault.token_id.clone(),
+        })?,
+        funds: vec![],
+    };
+
+    let mut res = Response::new()
+        .add_attribute("action", "buyout")
+        .add_attribute("vault_id", vault_id.to_string())
+        .add_message(transfer)
+        .add_messages(bank_msgs);
+    BuyoutEvent {
+        vault_id,
+        collection: vault.collection.as_str(),
+        token_id: &vault.token_id,
+        buyer: info.sender.as_str(),
+        recipient: recipient.as_str(),
+        amount: price.amount,
+        denom: &price.denom,
+    }
+    .add_attributes(&mut res);
+    Ok(res)
+}
+
+pub fn receive_shares(
+    deps: DepsMut,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let msg: ShareReceiveMsg = from_binary(&wrapper.msg)?;
+    let holder = deps.api.addr_validate(&wrapper.sender)?;
+    match msg {
+        ShareReceiveMsg::Redeem {} => redeem(deps, info.sender, holder, wrapper.amount),
+    }
+}
+
+// burns the shares the vault just received & pays the holder their cut of the buyout
+fn redeem(
+    deps: DepsMut,
+    shares: Addr,
+    holder: Addr,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let vault_id = SHARE_TOKENS
+        .may_load(deps.storage, &shares)?
+        .ok_or_else(|| ContractError::UnknownShareToken {
+            address: shares.to_string(),
+        })?;
+    let mut vault = VAULTS.load(deps.storage, vault_id)?;
+    let mut buyout = vault
+        .buyout
+        .clone()
+        .ok_or(ContractError::NotBoughtOut { id: vault_id })?;
+
+    // paying out of what is left, instead of the original price, leaves no rounding dust behind
+    let left = buyout
+        .remaining_shares
+        .checked_sub(amount)
+        .map_err(StdError::overflow)?;
+    let payout = buyout
+        .remaining_payment
+        .multiply_ratio(amount, buyout.remaining_shares);
+    buyout.remaining_payment -= payout;
+    buyout.remaining_shares = left;
+    vault.buyout = Some(buyout);
+    VAULTS.save(deps.storage, vault_id, &vault)?;
+
+    let denom = vault.reserve_price.denom;
+    let mut res = Response::new()
+        .add_attribute("action", "redeem")
+        .add_attribute("vault_id", vault_id.to_string())
+        .add_message(WasmMsg::Execute {
+            contract_addr: shares.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Burn { amount })?,
+            funds: vec![],
+        });
+    if !payout.is_zero() {
+        res = res.add_message(BankMsg::Send {
+            to_address: holder.to_string(),
+            amount: vec![coin(payout.u128(), &denom)],
+        });
+    }
+    RedeemEvent {
+        vault_id,
+        holder: holder.as_str(),
+        shares: amount,
+        amount: payout,
+        denom: &denom,
+    }
+    .add_attributes(&mut res);
+    Ok(res)
+}
+
+pub fn update_cw20_code_id(
+    deps: DepsMut,
+    info: MessageInfo,
+    code_id: u64,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    if info.sender != config.admin {
+        return Err(ContractError::Unauthorized {
+            msg: "You are not the current admin".to_string(),
+        });
+    }
+
+    let old_code_id = config.cw20_code_id;
+    config.cw20_code_id = code_id;
+    CONFIG.save(deps.storage, &config)?;
+
+    let mut res = Response::new()
+        .add_attribute("action", "update_cw20_code_id")
+        .add_attribute("code_id", code_id.to_string());
+    UpdateCw20CodeIdEvent {
+        old_code_id,
+        new_code_id: code_id,
+    }
+    .add_attributes(&mut res);
+    Ok(res)
+}
@@ -0,0 +1,10 @@
+pub mod contract;
+mod error;
+pub mod events;
+pub mod execute;
+pub mod msg;
+pub mod queries;
+pub mod state;
+
+#[cfg(test)]
+mod contract_tests;
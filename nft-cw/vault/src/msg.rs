@@ -0,0 +1,85 @@
+use cosmwasm_std::{Coin, Uint128};
+use cw20::Cw20ReceiveMsg;
+use cw721::Cw721ReceiveMsg;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::state::Buyout;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InitMsg {
+    pub cw20_code_id: u64,
+    pub denom: String,
+    /// defaults to the instantiator
+    pub admin: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Deposits an NFT through cw721 SendNft, the msg is a FractionalizeMsg
+    ReceiveNft(Cw721ReceiveMsg),
+    /// Share tokens sent back through cw20 Send, the msg is a ShareReceiveMsg
+    Receive(Cw20ReceiveMsg),
+    /// Pays the reserve price of a vault & sends its NFT to the sender, or to `recipient`
+    Buyout {
+        vault_id: u64,
+        #[serde(default)]
+        recipient: Option<String>,
+    },
+    UpdateCw20CodeId {
+        code_id: u64,
+    },
+}
+
+/// Sent as the msg of SendNft to fractionalize the token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FractionalizeMsg {
+    /// name & symbol of the share token, validated by cw20-base
+    pub name: String,
+    pub symbol: String,
+    /// the fixed number of shares minted to the depositor
+    pub supply: Uint128,
+    /// price anyone can pay to take the NFT out of the vault, in the configured denom
+    pub reserve_price: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareReceiveMsg {
+    /// Burns the sent shares for their pro-rata cut of the buyout payment
+    Redeem {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    GetConfig {},
+    GetVault {
+        vault_id: u64,
+    },
+    GetVaults {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct VaultResponse {
+    pub id: u64,
+    pub collection: String,
+    pub token_id: String,
+    pub depositor: String,
+    pub shares: Option<String>,
+    pub supply: Uint128,
+    pub reserve_price: Coin,
+    pub buyout: Option<Buyout>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct VaultsResponse {
+    pub vaults: Vec<VaultResponse>,
+}
@@ -0,0 +1,49 @@
+use cosmwasm_std::{Deps, Order, StdResult};
+use cw_storage_plus::Bound;
+
+use crate::msg::{VaultResponse, VaultsResponse};
+use crate::state::{Config, Vault, CONFIG, VAULTS};
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+pub fn query_config(deps: Deps) -> StdResult<Config> {
+    CONFIG.load(deps.storage)
+}
+
+pub fn query_vault(deps: Deps, vault_id: u64) -> StdResult<VaultResponse> {
+    let vault = VAULTS.load(deps.storage, vault_id)?;
+    Ok(parse_vault(vault_id, vault))
+}
+
+pub fn query_vaults(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<VaultsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let vaults = VAULTS
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|item| item.map(|(id, vault)| parse_vault(id, vault)))
+        .collect::<StdResult<_>>()?;
+    Ok(VaultsResponse { vaults })
+}
+
+fn parse_vault(id: u64, vault: Vault) -> VaultResponse {
+    VaultResponse {
+        id,
+        collection: vault.collection.into_string(),
+        token_id: vault.token_id,
+        depositor: vault.depositor.into_string(),
+        shares: vault.shares.map(|shares| shares.into_string()),
+        supply: vault.supply,
+        reserve_price: vault.reserve_price,
+        buyout: vault.buyout,
+    }
+}
@@ -0,0 +1,48 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, Coin, StdResult, Storage, Uint128};
+use cw_storage_plus::{Item, Map};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Config {
+    /// code id of the cw20-base contract instantiated for every vault
+    pub cw20_code_id: u64,
+    /// denom the reserve price is paid in
+    pub denom: String,
+    pub admin: Addr,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Buyout {
+    pub buyer: Addr,
+    /// what is left of the reserve price for the shares which have not been redeemed yet
+    pub remaining_payment: Uint128,
+    pub remaining_shares: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Vault {
+    pub collection: Addr,
+    pub token_id: String,
+    pub depositor: Addr,
+    /// the cw20 share token, set once its instantiate reply comes back
+    pub shares: Option<Addr>,
+    pub supply: Uint128,
+    pub reserve_price: Coin,
+    pub buyout: Option<Buyout>,
+}
+
+pub const CONFIG: Item<Config> = Item::new("config");
+
+pub const VAULT_COUNT: Item<u64> = Item::new("num_vaults");
+pub const VAULTS: Map<u64, Vault> = Map::new("vaults");
+
+// share token -> vault id, used to find the vault when shares are sent back for redemption
+pub const SHARE_TOKENS: Map<&Addr, u64> = Map::new("share_tokens");
+
+pub fn increment_vaults(store: &mut dyn Storage) -> StdResult<u64> {
+    let val = VAULT_COUNT.may_load(store)?.unwrap_or_default() + 1;
+    VAULT_COUNT.save(store, &val)?;
+    Ok(val)
+}
@@ -0,0 +1,2 @@
+# https://nexte.st/book/pre-built-binaries.html
+cargo nextest run
\ No newline at end of file